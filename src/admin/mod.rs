@@ -0,0 +1,287 @@
+use std::sync::Arc;
+
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    audit::{AuditKind, AuditUtil},
+    db::gen::{
+        forum,
+        prelude::{Forum, Thread, User},
+        thread, user,
+    },
+    user::{is_valid_handle, Role, UserInfo},
+};
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("Only sysops can do that")]
+    NotPermitted,
+    #[error("User not found")]
+    NoSuchUser,
+    #[error("Forum not found")]
+    NoSuchForum,
+    #[error("That handle is already taken")]
+    HandleTaken,
+    #[error("Handles can't be empty")]
+    EmptyHandle,
+    #[error("Handles may only contain letters, digits, _ and -")]
+    InvalidHandle,
+    #[error("Forum names can't be empty")]
+    EmptyForumName,
+    #[error("You can't change your own role")]
+    OwnRole,
+    #[error("Forum still has threads or sub-forums")]
+    ForumNotEmpty,
+}
+
+/// Sysop-only operations on users and forums. Every method re-checks the acting user's role.
+pub struct AdminUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+    actor: UserInfo,
+}
+
+impl AdminUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>, actor: UserInfo) -> AdminUtil {
+        AdminUtil { db, actor }
+    }
+
+    fn check_permitted(&self) -> Result<(), AdminError> {
+        if self.actor.has_role(Role::Sysop) {
+            Ok(())
+        } else {
+            Err(AdminError::NotPermitted)
+        }
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<user::Model>, anyhow::Error> {
+        self.check_permitted()?;
+        let db = self.db.lock().await.to_owned();
+        Ok(User::find()
+            .order_by_asc(user::Column::Handle)
+            .all(&db)
+            .await?)
+    }
+
+    pub async fn rename_user(&self, user_id: i32, handle: &str) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        let handle = handle.trim();
+        if handle.is_empty() {
+            return Err(AdminError::EmptyHandle.into());
+        }
+        if !is_valid_handle(handle) {
+            return Err(AdminError::InvalidHandle.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        let existing = User::find()
+            .filter(user::Column::Handle.eq(handle))
+            .filter(user::Column::Id.ne(user_id))
+            .one(&db)
+            .await?;
+        if existing.is_some() {
+            return Err(AdminError::HandleTaken.into());
+        }
+        let user = User::find_by_id(user_id)
+            .one(&db)
+            .await?
+            .ok_or(AdminError::NoSuchUser)?;
+        let detail = format!("Renamed {} to {}", user.handle, handle);
+        let mut active = user.into_active_model();
+        active.handle = Set(handle.to_string());
+        active.update(&db).await?;
+        AuditUtil::log_committed(
+            &db,
+            AuditKind::Admin,
+            self.actor.id(),
+            Some(user_id),
+            None,
+            &detail,
+        )
+        .await;
+        Ok(())
+    }
+
+    pub async fn set_role(&self, user_id: i32, role: Role) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        if self.actor.id() == Some(user_id) {
+            return Err(AdminError::OwnRole.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        let user = User::find_by_id(user_id)
+            .one(&db)
+            .await?
+            .ok_or(AdminError::NoSuchUser)?;
+        let detail = format!(
+            "Changed {}'s role from {} to {}",
+            user.handle,
+            Role::from_i32(user.role),
+            role
+        );
+        let mut active = user.into_active_model();
+        active.role = Set(role.as_i32());
+        active.update(&db).await?;
+        AuditUtil::log_committed(
+            &db,
+            AuditKind::Admin,
+            self.actor.id(),
+            Some(user_id),
+            None,
+            &detail,
+        )
+        .await;
+        Ok(())
+    }
+
+    async fn siblings(
+        db: &DatabaseConnection,
+        parent: Option<i32>,
+    ) -> Result<Vec<forum::Model>, anyhow::Error> {
+        let query = match parent {
+            Some(parent) => Forum::find().filter(forum::Column::Parent.eq(parent)),
+            None => Forum::find().filter(forum::Column::Parent.is_null()),
+        };
+        Ok(query
+            .order_by_asc(forum::Column::Position)
+            .order_by_asc(forum::Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    pub async fn create_forum(
+        &self,
+        name: &str,
+        description: &str,
+        parent: Option<i32>,
+    ) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AdminError::EmptyForumName.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        if let Some(parent) = parent {
+            Forum::find_by_id(parent)
+                .one(&db)
+                .await?
+                .ok_or(AdminError::NoSuchForum)?;
+        }
+        let position = Self::siblings(&db, parent)
+            .await?
+            .last()
+            .map(|forum| forum.position + 1)
+            .unwrap_or(0);
+        let description = description.trim();
+        forum::ActiveModel {
+            name: Set(name.to_string()),
+            description: Set(if description.is_empty() {
+                None
+            } else {
+                Some(description.to_string())
+            }),
+            parent: Set(parent),
+            position: Set(position),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn update_forum(
+        &self,
+        forum_id: i32,
+        name: &str,
+        description: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AdminError::EmptyForumName.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        let forum = Forum::find_by_id(forum_id)
+            .one(&db)
+            .await?
+            .ok_or(AdminError::NoSuchForum)?;
+        let description = description.trim();
+        let mut active = forum.into_active_model();
+        active.name = Set(name.to_string());
+        active.description = Set(if description.is_empty() {
+            None
+        } else {
+            Some(description.to_string())
+        });
+        active.update(&db).await?;
+        Ok(())
+    }
+
+    /// Q&A forums rank replies by their votes instead of showing them in posting order.
+    pub async fn set_question_answer(
+        &self,
+        forum_id: i32,
+        question_answer: bool,
+    ) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        let db = self.db.lock().await.to_owned();
+        let forum = Forum::find_by_id(forum_id)
+            .one(&db)
+            .await?
+            .ok_or(AdminError::NoSuchForum)?;
+        let mut active = forum.into_active_model();
+        active.question_answer = Set(question_answer);
+        active.update(&db).await?;
+        Ok(())
+    }
+
+    /// Moves a forum up (negative offset) or down (positive offset) among its siblings.
+    pub async fn move_forum(&self, forum_id: i32, offset: i32) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        let db = self.db.lock().await.to_owned();
+        let forum = Forum::find_by_id(forum_id)
+            .one(&db)
+            .await?
+            .ok_or(AdminError::NoSuchForum)?;
+        let mut siblings = Self::siblings(&db, forum.parent).await?;
+        let index = siblings
+            .iter()
+            .position(|sibling| sibling.id == forum_id)
+            .ok_or(AdminError::NoSuchForum)?;
+        let target = (index as i32 + offset).clamp(0, siblings.len() as i32 - 1) as usize;
+        if target == index {
+            return Ok(());
+        }
+        let moved = siblings.remove(index);
+        siblings.insert(target, moved);
+        // Renumber every sibling so positions stay dense even if older rows collided.
+        for (position, sibling) in siblings.into_iter().enumerate() {
+            if sibling.position != position as i32 {
+                let mut active = sibling.into_active_model();
+                active.position = Set(position as i32);
+                active.update(&db).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn delete_forum(&self, forum_id: i32) -> Result<(), anyhow::Error> {
+        self.check_permitted()?;
+        let db = self.db.lock().await.to_owned();
+        let threads = Thread::find()
+            .filter(thread::Column::Forum.eq(forum_id))
+            .count(&db)
+            .await?;
+        let children = Forum::find()
+            .filter(forum::Column::Parent.eq(forum_id))
+            .count(&db)
+            .await?;
+        if threads > 0 || children > 0 {
+            return Err(AdminError::ForumNotEmpty.into());
+        }
+        Forum::delete_by_id(forum_id).exec(&db).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,224 @@
+//! Who did what to whose account, for when something goes wrong. Events are kept in the
+//! `audit_log` table and, if `audit_log_path` is set, appended to a JSON-lines file as well.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{
+        gen::{
+            audit_log,
+            prelude::{AuditLog, User},
+            user,
+        },
+        now,
+    },
+    settings::get_settings,
+};
+
+pub static AUDIT_PAGE_SIZE: u64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditKind {
+    Login,
+    Registration,
+    Profile,
+    Key,
+    Moderation,
+    Ban,
+    Admin,
+}
+
+impl AuditKind {
+    pub const ALL: [AuditKind; 7] = [
+        AuditKind::Login,
+        AuditKind::Registration,
+        AuditKind::Profile,
+        AuditKind::Key,
+        AuditKind::Moderation,
+        AuditKind::Ban,
+        AuditKind::Admin,
+    ];
+}
+
+impl Display for AuditKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AuditKind::Login => "login",
+            AuditKind::Registration => "registration",
+            AuditKind::Profile => "profile",
+            AuditKind::Key => "key",
+            AuditKind::Moderation => "moderation",
+            AuditKind::Ban => "ban",
+            AuditKind::Admin => "admin",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Which events to show. Empty fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub kind: Option<AuditKind>,
+    /// Handle of the user who did it.
+    pub actor: Option<String>,
+}
+
+/// An audit log entry with the handles of its actor and target resolved.
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    pub entry: audit_log::Model,
+    pub actor: Option<String>,
+    pub target: Option<String>,
+}
+
+/// One page of matching entries, newest first, and how many pages there are in all.
+#[derive(Debug, Clone, Default)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub pages: u64,
+}
+
+pub struct AuditUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl AuditUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> AuditUtil {
+        AuditUtil { db }
+    }
+
+    /// Records that `actor` did something, to `target`'s account if it was done to one.
+    /// `fingerprint` is the key they used, where it matters.
+    pub async fn record(
+        &self,
+        kind: AuditKind,
+        actor: Option<i32>,
+        target: Option<i32>,
+        fingerprint: Option<String>,
+        detail: &str,
+    ) -> Result<(), anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Self::log(&db, kind, actor, target, fingerprint, detail).await
+    }
+
+    /// Like `record`, for services that already hold a connection or transaction.
+    pub(crate) async fn log<C: ConnectionTrait>(
+        db: &C,
+        kind: AuditKind,
+        actor: Option<i32>,
+        target: Option<i32>,
+        fingerprint: Option<String>,
+        detail: &str,
+    ) -> Result<(), anyhow::Error> {
+        let entry = audit_log::ActiveModel {
+            kind: Set(kind.to_string()),
+            actor: Set(actor),
+            target: Set(target),
+            fingerprint: Set(fingerprint),
+            detail: Set(detail.to_string()),
+            created: Set(now()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        if let Some(path) = get_settings().audit_log_path {
+            // The table has the event either way, so a full disk shouldn't stop the action.
+            if let Err(err) = append_line(&path, &entry) {
+                error!("Failed to write audit log file {}: {}", path, err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `log`, for actions that have already been committed. Failing to record one is only
+    /// logged, since returning an error would report a change that did happen as failed.
+    pub(crate) async fn log_committed<C: ConnectionTrait>(
+        db: &C,
+        kind: AuditKind,
+        actor: Option<i32>,
+        target: Option<i32>,
+        fingerprint: Option<String>,
+        detail: &str,
+    ) {
+        if let Err(err) = Self::log(db, kind, actor, target, fingerprint, detail).await {
+            error!("Failed to record {} event \"{}\": {}", kind, detail, err);
+        }
+    }
+
+    /// Returns page `page` (from 0) of the entries matching `filter`.
+    pub async fn entries(
+        &self,
+        filter: &AuditFilter,
+        page: u64,
+    ) -> Result<AuditPage, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let mut query = AuditLog::find().order_by_desc(audit_log::Column::Id);
+        if let Some(kind) = filter.kind {
+            query = query.filter(audit_log::Column::Kind.eq(kind.to_string()));
+        }
+        if let Some(handle) = &filter.actor {
+            let actor = User::find()
+                .filter(user::Column::Handle.eq(handle.trim()))
+                .one(&db)
+                .await?;
+            match actor {
+                Some(actor) => query = query.filter(audit_log::Column::Actor.eq(actor.id)),
+                None => return Ok(AuditPage::default()),
+            }
+        }
+        let paginator = query.paginate(&db, AUDIT_PAGE_SIZE);
+        let pages = paginator.num_pages().await?;
+        let entries = paginator.fetch_page(page).await?;
+        let user_ids: Vec<i32> = entries
+            .iter()
+            .flat_map(|entry| [entry.actor, entry.target])
+            .flatten()
+            .collect();
+        let handles: HashMap<i32, String> = User::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.handle))
+            .collect();
+        Ok(AuditPage {
+            entries: entries
+                .into_iter()
+                .map(|entry| AuditEntry {
+                    actor: entry.actor.and_then(|actor| handles.get(&actor).cloned()),
+                    target: entry
+                        .target
+                        .and_then(|target| handles.get(&target).cloned()),
+                    entry,
+                })
+                .collect(),
+            pages,
+        })
+    }
+}
+
+fn append_line(path: &str, entry: &audit_log::Model) -> Result<(), std::io::Error> {
+    let line = json!({
+        "id": entry.id,
+        "created": entry.created,
+        "kind": entry.kind,
+        "actor": entry.actor,
+        "target": entry.target,
+        "fingerprint": entry.fingerprint,
+        "detail": entry.detail,
+    });
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(format!("{}\n", line).as_bytes())
+}
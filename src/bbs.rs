@@ -1,9 +1,12 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use log::info;
+use log::{error, info};
 use sea_orm::DatabaseConnection;
 use ssh_ui::{
     cursive::{
+        event::{Event, EventTrigger},
         view::{Margins, Nameable, Resizable},
         views::Dialog,
         Cursive,
@@ -11,13 +14,51 @@ use ssh_ui::{
     russh_keys::key::PublicKey,
     App, AppSession, SessionHandle,
 };
-use tokio::sync::{mpsc::Sender, Mutex};
+use tokio::{
+    spawn,
+    sync::{
+        mpsc::{channel, Sender},
+        Mutex,
+    },
+};
 
-use crate::ui::{
-    home::home_screen::home_screen,
-    stack::{Stack, STACK_NAME},
+use crate::{
+    audit::{AuditKind, AuditUtil},
+    moderation::{describe_expiry, BanTarget, ModerationUtil},
+    notification::{subscribe, unsubscribe},
+    profile::ProfileUtil,
+    session::{
+        add_session_task, end_session, on_session_end, register_session, session_idle_time,
+        session_kicked, set_session_address, set_session_deadline, set_session_fingerprint,
+        set_session_screen, set_session_user, shutdown_deadline, touch_session, CallAllowance,
+        UsageUtil,
+    },
+    settings::get_settings,
+    throttle::{self, Action},
+    ui::{
+        block_on,
+        editor::save_all_drafts,
+        forum::leave_chat,
+        get_user,
+        home::home_screen::home_screen,
+        stack::{Stack, STACK_NAME},
+        toast::Toasts,
+    },
+    user::{Role, UserUtil},
 };
 
+static SESSION_DIALOG: &str = "SESSION_DIALOG";
+
+/// How often time online is charged. A crash loses at most this much, and it's how long a session
+/// can take to notice time the same user spent in their other sessions.
+const ACCOUNTING_INTERVAL: Duration = Duration::from_secs(15);
+/// Time left at which users are warned that they'll be disconnected.
+const TIME_WARNINGS: [Duration; 2] = [Duration::from_secs(5 * 60), Duration::from_secs(60)];
+/// How long the time's-up notice stays up before the connection is closed.
+const TIME_UP_GRACE: Duration = Duration::from_secs(20);
+/// How long before an idle session is closed its user is asked if they're still there.
+const IDLE_WARNING: Duration = Duration::from_secs(60);
+
 pub(crate) struct BbsApp {
     db: Arc<Mutex<DatabaseConnection>>,
 }
@@ -32,6 +73,13 @@ impl App for BbsApp {
         Box::new(BbsAppSession {
             relayout_sender: None,
             db: self.db.clone(),
+            session_id: Some(register_session()),
+            user_id: None,
+            toasts: None,
+            clock: SessionClock::default(),
+            idle_timeout: get_settings().idle_timeout,
+            idle_warned: false,
+            shutdown_warned: false,
         })
     }
 }
@@ -47,9 +95,206 @@ impl BbsApp {
 struct BbsAppSession {
     relayout_sender: Option<Sender<()>>,
     db: Arc<Mutex<DatabaseConnection>>,
+    session_id: Option<u64>,
+    user_id: Option<i32>,
+    toasts: Option<Toasts>,
+    clock: SessionClock,
+    idle_timeout: Option<Duration>,
+    idle_warned: bool,
+    shutdown_warned: bool,
+}
+
+/// Time limit bookkeeping for one session.
+#[derive(Default)]
+struct SessionClock {
+    deadline: Option<Instant>,
+    /// When the per-session limit runs out, if there is one.
+    session_deadline: Option<Instant>,
+    /// The day's allowance, which all of the user's sessions draw on.
+    daily_limit: Option<Duration>,
+    /// Up to when time online has been written to the database.
+    accounted: Option<Instant>,
+    warnings_given: usize,
+    time_up: Option<Instant>,
+    shown_minutes: Option<u64>,
+}
+
+impl BbsAppSession {
+    /// Takes the time online that hasn't been written to the database yet, once there's at
+    /// least `at_least` of it.
+    fn unaccounted_time(&mut self, at_least: Duration) -> Option<(i32, Duration)> {
+        let user_id = self.user_id?;
+        let accounted = self.clock.accounted.as_mut()?;
+        let elapsed = Duration::from_secs(accounted.elapsed().as_secs());
+        if elapsed.is_zero() || elapsed < at_least {
+            return None;
+        }
+        *accounted += elapsed;
+        Some((user_id, elapsed))
+    }
+
+    /// Charges the time online since the last charge, and brings the deadline forward if the
+    /// user's other sessions have been using up the day's allowance too.
+    fn charge_time(&mut self, at_least: Duration) {
+        let (user_id, elapsed) = match self.unaccounted_time(at_least) {
+            Some(unaccounted) => unaccounted,
+            None => return,
+        };
+        let used_today = match block_on(UsageUtil::new(self.db.clone()).add_time(user_id, elapsed))
+        {
+            Ok(used_today) => used_today,
+            Err(err) => {
+                error!("Failed to record time online: {}", err);
+                return;
+            }
+        };
+        let daily = match self.clock.daily_limit {
+            Some(daily) => daily,
+            None => return,
+        };
+        let daily_deadline = Instant::now() + daily.saturating_sub(used_today);
+        let deadline = match self.clock.session_deadline {
+            Some(session_deadline) => session_deadline.min(daily_deadline),
+            None => daily_deadline,
+        };
+        self.clock.deadline = Some(deadline);
+        if let Some(session_id) = self.session_id {
+            set_session_deadline(session_id, Some(deadline));
+        }
+    }
+
+    fn check_idle(&mut self, siv: &mut Cursive) {
+        let (session_id, idle_timeout) = match (self.session_id, self.idle_timeout) {
+            (Some(session_id), Some(idle_timeout)) => (session_id, idle_timeout),
+            _ => return,
+        };
+        let idle = session_idle_time(session_id).unwrap_or_default();
+        if idle >= idle_timeout {
+            info!("Disconnecting idle session {}", session_id);
+            hang_up(siv);
+        } else if idle + IDLE_WARNING >= idle_timeout {
+            if !self.idle_warned {
+                self.idle_warned = true;
+                siv.add_layer(
+                    Dialog::text(format!(
+                        "Are you still there? You'll be disconnected in {} seconds.",
+                        (idle_timeout - idle).as_secs()
+                    ))
+                    .title("Still there?")
+                    .button("I'm here", move |siv| {
+                        touch_session(session_id);
+                        siv.pop_layer();
+                    }),
+                );
+                if let Some(relayout_sender) = &self.relayout_sender {
+                    let _ = relayout_sender.try_send(());
+                }
+            }
+        } else {
+            self.idle_warned = false;
+        }
+    }
+
+    fn check_shutdown(&mut self, siv: &mut Cursive, deadline: Instant) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            info!("Closing session {:?} for shutdown", self.session_id);
+            hang_up(siv);
+        } else if !self.shutdown_warned {
+            self.shutdown_warned = true;
+            siv.add_layer(
+                Dialog::text(format!(
+                    "The server is going down in {} seconds. Anything you're writing will be kept \
+                     as a draft. Please call back later!",
+                    left.as_secs()
+                ))
+                .title("Server going down")
+                .dismiss_button("Ok"),
+            );
+            if let Some(relayout_sender) = &self.relayout_sender {
+                let _ = relayout_sender.try_send(());
+            }
+        }
+    }
+
+    fn check_time_left(&mut self, siv: &mut Cursive, deadline: Instant) {
+        let left = deadline.saturating_duration_since(Instant::now());
+        let minutes = (left.as_secs() + 59) / 60;
+        if self.clock.shown_minutes != Some(minutes) {
+            self.clock.shown_minutes = Some(minutes);
+            let title = match minutes {
+                1 => "1 minute left".to_string(),
+                minutes => format!("{} minutes left", minutes),
+            };
+            siv.call_on_name(SESSION_DIALOG, |dialog: &mut Dialog| {
+                dialog.set_title(title)
+            });
+            if let Some(relayout_sender) = &self.relayout_sender {
+                let _ = relayout_sender.try_send(());
+            }
+        }
+        while self.clock.warnings_given < TIME_WARNINGS.len()
+            && left <= TIME_WARNINGS[self.clock.warnings_given]
+        {
+            self.clock.warnings_given += 1;
+            if let Some(toasts) = &self.toasts {
+                toasts.push(format!(
+                    "Heads up: you'll be disconnected in {} when your time runs out.",
+                    match minutes {
+                        0 | 1 => "a minute".to_string(),
+                        minutes => format!("{} minutes", minutes),
+                    }
+                ));
+            }
+        }
+        if !left.is_zero() {
+            return;
+        }
+        match self.clock.time_up {
+            None => {
+                self.clock.time_up = Some(Instant::now());
+                siv.add_layer(
+                    Dialog::text("Your time is up. Thanks for calling, and come back soon!")
+                        .title("Time's up")
+                        .button("Disconnect", hang_up),
+                );
+            }
+            Some(time_up) if time_up.elapsed() >= TIME_UP_GRACE => hang_up(siv),
+            Some(_) => {}
+        }
+    }
 }
 
-impl BbsAppSession {}
+/// Ends the session from this side, keeping anything that was being written as a draft.
+fn hang_up(siv: &mut Cursive) {
+    save_all_drafts(siv);
+    siv.quit();
+}
+
+impl Drop for BbsAppSession {
+    fn drop(&mut self) {
+        let session_id = self.session_id.take();
+        let unaccounted = self.unaccounted_time(Duration::ZERO);
+        let user_id = self.user_id.take();
+        let db = self.db.clone();
+        // The session stays registered until this is written, so shutdown waits for it.
+        spawn(async move {
+            if let Some((user_id, elapsed)) = unaccounted {
+                if let Err(err) = UsageUtil::new(db.clone()).add_time(user_id, elapsed).await {
+                    error!("Failed to record time online: {}", err);
+                }
+            }
+            if let Some(user_id) = user_id {
+                if let Err(err) = ProfileUtil::new(db).record_seen(user_id).await {
+                    error!("Failed to record last seen: {}", err);
+                }
+            }
+            if let Some(session_id) = session_id {
+                end_session(session_id);
+            }
+        });
+    }
+}
 
 impl AppSession for BbsAppSession {
     fn on_start(
@@ -57,29 +302,197 @@ impl AppSession for BbsAppSession {
         siv: &mut Cursive,
         _handle: SessionHandle,
         pub_key: Option<PublicKey>,
+        peer_addr: Option<SocketAddr>,
         force_relayout_sender: Sender<()>,
     ) -> Result<Box<dyn ssh_ui::cursive::View>, Box<dyn std::error::Error>> {
-        info!("Starting new session, user: {:?}", pub_key);
-        let mut stack = Stack::new(siv, force_relayout_sender.clone(), self.db.clone());
-        stack
-            .push(home_screen(
-                force_relayout_sender.clone(),
-                self.db.clone(),
-                pub_key.clone(),
+        info!(
+            "Starting new session, user: {:?}, address: {:?}",
+            pub_key, peer_addr
+        );
+        let user = get_user(self.db.clone(), pub_key.clone()).ok();
+        let ban_target = BanTarget {
+            user_id: user.as_ref().and_then(|user| user.id()),
+            fingerprint: pub_key.as_ref().map(|key| key.fingerprint()),
+            // The port changes with every connection, so bans are on the address alone.
+            address: peer_addr.map(|addr| addr.ip().to_string()),
+        };
+        let session_id = self.session_id.unwrap();
+        set_session_user(
+            session_id,
+            user.as_ref().and_then(|user| user.id()),
+            user.as_ref().map(|user| user.handle.clone()),
+        );
+        set_session_fingerprint(session_id, ban_target.fingerprint.clone());
+        set_session_address(session_id, ban_target.address.clone());
+        on_session_end(session_id, move || leave_chat(session_id));
+        if shutdown_deadline().is_some() {
+            info!(
+                "Turning away session, user: {:?}, server is shutting down",
+                pub_key
+            );
+            set_session_screen(session_id, "Shutting down");
+            let dialog = Dialog::text("The server is going down. Please call back later!")
+                .title("Server going down")
+                .button("Disconnect", |siv| siv.quit());
+            return Ok(Box::new(dialog));
+        }
+        // Checked before the ban lookup and call accounting, so redialing costs as little as
+        // possible.
+        if let Err(err) = throttle::check(session_id, Action::Connect) {
+            info!("Refusing session calling too often, user: {:?}", pub_key);
+            set_session_screen(session_id, "Throttled");
+            let dialog = Dialog::text(format!("You're calling back very quickly. {}", err))
+                .title("Slow down")
+                .button("Disconnect", |siv| siv.quit());
+            return Ok(Box::new(dialog));
+        }
+        if let Some(ban) = block_on(ModerationUtil::new(self.db.clone()).active_ban(&ban_target))? {
+            info!("Refusing banned session, user: {:?}", pub_key);
+            let audit_util = AuditUtil::new(self.db.clone());
+            let detail = format!("Refused, banned (ban #{})", ban.id);
+            let audit = audit_util.record(
+                AuditKind::Login,
+                ban_target.user_id,
+                None,
+                ban_target.fingerprint.clone(),
+                &detail,
+            );
+            if let Err(err) = block_on(audit) {
+                error!("Failed to record refused login: {}", err);
+            }
+            set_session_screen(session_id, "Banned");
+            let dialog = Dialog::text(format!(
+                "You have been banned {}.\n\nReason: {}",
+                describe_expiry(&ban.expires),
+                ban.reason
             ))
+            .title("Banned")
+            .button("Disconnect", |siv| siv.quit());
+            return Ok(Box::new(dialog));
+        }
+        let allowance = block_on(UsageUtil::new(self.db.clone()).start_call(user.as_ref()))
+            .unwrap_or_else(|err| {
+                error!("Failed to check time allowance: {}", err);
+                CallAllowance::Allowed(None)
+            });
+        let refusal = match allowance {
+            CallAllowance::Allowed(left) => {
+                let limits =
+                    get_settings().limits(user.as_ref().map_or(Role::Member, |user| user.role));
+                self.clock.session_deadline =
+                    limits.session.map(|session| Instant::now() + session);
+                if user.as_ref().and_then(|user| user.id()).is_some() {
+                    self.clock.daily_limit = limits.daily;
+                }
+                self.clock.deadline = left.map(|left| Instant::now() + left);
+                set_session_deadline(session_id, self.clock.deadline);
+                None
+            }
+            CallAllowance::OutOfTime => Some("You've used up your time online for today."),
+            CallAllowance::OutOfCalls => Some("You've called as many times as you can today."),
+        };
+        if let Some(refusal) = refusal {
+            set_session_screen(session_id, "Out of time");
+            let dialog = Dialog::text(format!("{} Come back tomorrow!", refusal))
+                .title("Time's up")
+                .button("Disconnect", |siv| siv.quit());
+            return Ok(Box::new(dialog));
+        }
+        if let Some(user) = &user {
+            let user_util = UserUtil::new(self.db.clone(), pub_key.clone());
+            if let Err(err) = block_on(user_util.record_login(user)) {
+                error!("Failed to record login: {}", err);
+            }
+            let audit_util = AuditUtil::new(self.db.clone());
+            let audit = audit_util.record(
+                AuditKind::Login,
+                user.id(),
+                None,
+                ban_target.fingerprint.clone(),
+                "Logged in",
+            );
+            if let Err(err) = block_on(audit) {
+                error!("Failed to record login: {}", err);
+            }
+        }
+        self.user_id = user.as_ref().and_then(|user| user.id());
+        self.clock.accounted = Some(Instant::now());
+        // Hooked in at the root so that input counts as activity whichever layer it goes to.
+        siv.set_on_pre_event_inner(
+            EventTrigger::from_fn(|event| !matches!(event, Event::Refresh | Event::WindowResize)),
+            move |_| {
+                touch_session(session_id);
+                None
+            },
+        );
+        let mut stack = Stack::new(
+            siv,
+            session_id,
+            force_relayout_sender.clone(),
+            self.db.clone(),
+        );
+
+        // Notifications arrive on their own task and are shown as a banner over the stack.
+        let (notification_sender, mut notification_receiver) = channel(16);
+        subscribe(
+            session_id,
+            user.as_ref().and_then(|user| user.id()),
+            notification_sender,
+        );
+        on_session_end(session_id, move || unsubscribe(session_id));
+        let toasts = stack.toasts();
+        self.toasts = Some(toasts.clone());
+        let relayout_sender = force_relayout_sender.clone();
+        let notification_task = spawn(async move {
+            while let Some(notification) = notification_receiver.recv().await {
+                toasts.push(notification.to_string());
+                if relayout_sender.send(()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        add_session_task(session_id, notification_task.abort_handle());
+
+        stack
+            .push_screen(
+                home_screen(
+                    force_relayout_sender.clone(),
+                    self.db.clone(),
+                    pub_key.clone(),
+                ),
+                "Main menu",
+            )
             .unwrap();
         self.relayout_sender = Some(force_relayout_sender);
         let dialog = Dialog::new()
             .padding(Margins::lrtb(2, 2, 1, 1))
             .content(stack.with_name(STACK_NAME).full_screen())
+            .with_name(SESSION_DIALOG)
             .full_screen();
         Ok(Box::new(dialog))
     }
 
     fn on_tick(
         &mut self,
-        _siv: &mut ssh_ui::cursive::Cursive,
+        siv: &mut ssh_ui::cursive::Cursive,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        self.charge_time(ACCOUNTING_INTERVAL);
+        if let Some(deadline) = shutdown_deadline() {
+            self.check_shutdown(siv, deadline);
+        }
+        if let Some(deadline) = self.clock.deadline {
+            self.check_time_left(siv, deadline);
+        }
+        self.check_idle(siv);
+        if let Some(reason) = self.session_id.and_then(session_kicked) {
+            info!("Session {:?} was kicked: {}", self.session_id, reason);
+            hang_up(siv);
+        }
+        if let (Some(toasts), Some(relayout_sender)) = (&self.toasts, &self.relayout_sender) {
+            if toasts.expire() {
+                let _ = relayout_sender.try_send(());
+            }
+        }
         Ok(())
     }
 }
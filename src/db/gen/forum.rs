@@ -10,6 +10,8 @@ pub struct Model {
     pub name: String,
     pub description: Option<String>,
     pub parent: Option<i32>,
+    pub position: i32,
+    pub question_answer: bool,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -2,8 +2,24 @@
 
 pub mod prelude;
 
+pub mod audit_log;
+pub mod ban;
+pub mod door_state;
+pub mod draft;
 pub mod forum;
+pub mod mention;
+pub mod message;
+pub mod moderation_log;
+pub mod mute;
+pub mod poll;
+pub mod poll_ballot;
+pub mod poll_choice;
+pub mod poll_option;
 pub mod post;
 pub mod public_key;
+pub mod reaction;
+pub mod read_marker;
+pub mod score;
 pub mod thread;
+pub mod time_usage;
 pub mod user;
@@ -11,10 +11,16 @@ pub struct Model {
     pub modified: Option<String>,
     pub author: i32,
     pub thread: i32,
+    #[sea_orm(column_type = "Text")]
+    pub body: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::mention::Entity")]
+    Mention,
+    #[sea_orm(has_many = "super::reaction::Entity")]
+    Reaction,
     #[sea_orm(
         belongs_to = "super::thread::Entity",
         from = "Column::Thread",
@@ -33,6 +39,18 @@ pub enum Relation {
     User,
 }
 
+impl Related<super::mention::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Mention.def()
+    }
+}
+
+impl Related<super::reaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Reaction.def()
+    }
+}
+
 impl Related<super::thread::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Thread.def()
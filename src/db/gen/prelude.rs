@@ -1,7 +1,23 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.10.7
 
+pub use super::audit_log::Entity as AuditLog;
+pub use super::ban::Entity as Ban;
+pub use super::door_state::Entity as DoorState;
+pub use super::draft::Entity as Draft;
 pub use super::forum::Entity as Forum;
+pub use super::mention::Entity as Mention;
+pub use super::message::Entity as Message;
+pub use super::moderation_log::Entity as ModerationLog;
+pub use super::mute::Entity as Mute;
+pub use super::poll::Entity as Poll;
+pub use super::poll_ballot::Entity as PollBallot;
+pub use super::poll_choice::Entity as PollChoice;
+pub use super::poll_option::Entity as PollOption;
 pub use super::post::Entity as Post;
 pub use super::public_key::Entity as PublicKey;
+pub use super::reaction::Entity as Reaction;
+pub use super::read_marker::Entity as ReadMarker;
+pub use super::score::Entity as Score;
 pub use super::thread::Entity as Thread;
+pub use super::time_usage::Entity as TimeUsage;
 pub use super::user::Entity as User;
@@ -25,8 +25,12 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Forum,
+    #[sea_orm(has_one = "super::poll::Entity")]
+    Poll,
     #[sea_orm(has_many = "super::post::Entity")]
     Post,
+    #[sea_orm(has_many = "super::read_marker::Entity")]
+    ReadMarker,
     #[sea_orm(
         belongs_to = "super::user::Entity",
         from = "Column::Author",
@@ -43,12 +47,24 @@ impl Related<super::forum::Entity> for Entity {
     }
 }
 
+impl Related<super::poll::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Poll.def()
+    }
+}
+
 impl Related<super::post::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Post.def()
     }
 }
 
+impl Related<super::read_marker::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReadMarker.def()
+    }
+}
+
 impl Related<super::user::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::User.def()
@@ -10,16 +10,71 @@ pub struct Model {
     pub handle: String,
     pub status: Option<String>,
     pub contact: Option<String>,
+    pub role: i32,
+    pub last_login: Option<String>,
+    pub previous_login: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub bio: Option<String>,
+    pub joined: Option<String>,
+    pub last_seen: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::audit_log::Entity")]
+    AuditLog,
+    #[sea_orm(has_many = "super::door_state::Entity")]
+    DoorState,
+    #[sea_orm(has_many = "super::draft::Entity")]
+    Draft,
+    #[sea_orm(has_many = "super::moderation_log::Entity")]
+    ModerationLog,
+    #[sea_orm(has_many = "super::poll_ballot::Entity")]
+    PollBallot,
     #[sea_orm(has_many = "super::post::Entity")]
     Post,
     #[sea_orm(has_many = "super::public_key::Entity")]
     PublicKey,
+    #[sea_orm(has_many = "super::reaction::Entity")]
+    Reaction,
+    #[sea_orm(has_many = "super::read_marker::Entity")]
+    ReadMarker,
+    #[sea_orm(has_many = "super::score::Entity")]
+    Score,
     #[sea_orm(has_many = "super::thread::Entity")]
     Thread,
+    #[sea_orm(has_many = "super::time_usage::Entity")]
+    TimeUsage,
+}
+
+impl Related<super::audit_log::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::AuditLog.def()
+    }
+}
+
+impl Related<super::door_state::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::DoorState.def()
+    }
+}
+
+impl Related<super::draft::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Draft.def()
+    }
+}
+
+impl Related<super::moderation_log::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ModerationLog.def()
+    }
+}
+
+impl Related<super::poll_ballot::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PollBallot.def()
+    }
 }
 
 impl Related<super::post::Entity> for Entity {
@@ -34,10 +89,34 @@ impl Related<super::public_key::Entity> for Entity {
     }
 }
 
+impl Related<super::reaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Reaction.def()
+    }
+}
+
+impl Related<super::read_marker::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReadMarker.def()
+    }
+}
+
+impl Related<super::score::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Score.def()
+    }
+}
+
 impl Related<super::thread::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Thread.def()
     }
 }
 
+impl Related<super::time_usage::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TimeUsage.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
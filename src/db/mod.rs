@@ -1,9 +1,25 @@
 use std::sync::{Arc, Mutex};
 
+use chrono::{Duration, NaiveDateTime, Utc};
 use sea_orm::DatabaseConnection;
 
 pub mod gen;
 
+/// Timestamps are stored as UTC strings in this format so they sort lexicographically.
+pub static TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub fn now() -> String {
+    format_timestamp(Utc::now().naive_utc())
+}
+
+pub fn from_now(duration: Duration) -> String {
+    format_timestamp(Utc::now().naive_utc() + duration)
+}
+
+pub fn format_timestamp(time: NaiveDateTime) -> String {
+    time.format(TIMESTAMP_FORMAT).to_string()
+}
+
 pub struct DbUtil {
     db: Arc<Mutex<DatabaseConnection>>,
 }
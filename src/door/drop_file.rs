@@ -0,0 +1,272 @@
+use std::fs::{self, DirBuilder, File};
+use std::io::{self, Read};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::Local;
+
+use crate::user::{Role, UserInfo};
+
+const BBS_NAME: &str = "abbs";
+const SYSOP_NAME: &str = "Sysop";
+/// A door can report how a game went by leaving this file in its drop directory: the score on the
+/// first line and, optionally, a few words about the game on the second.
+const SCORE_FILE: &str = "SCORE.TXT";
+/// More than a score file needs, so a door can't make us read much.
+const SCORE_FILE_LIMIT: u64 = 1024;
+const SCORE_METADATA_LENGTH: usize = 60;
+
+/// The drop file layouts doors expect to find their caller's details in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DropFileFormat {
+    /// The 52 line GAP format.
+    DoorSys,
+    /// The 13 line RBBS/QuickBBS format.
+    DorInfo,
+    /// The 11 line format from the DOOR32 spec.
+    Door32,
+}
+
+impl DropFileFormat {
+    /// Parses the `drop_file` setting, which names the file the door reads.
+    pub(crate) fn from_name(name: &str) -> Option<DropFileFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "door.sys" => Some(DropFileFormat::DoorSys),
+            "dorinfo1.def" => Some(DropFileFormat::DorInfo),
+            "door32.sys" => Some(DropFileFormat::Door32),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn file_name(self) -> &'static str {
+        match self {
+            DropFileFormat::DoorSys => "DOOR.SYS",
+            DropFileFormat::DorInfo => "DORINFO1.DEF",
+            DropFileFormat::Door32 => "door32.sys",
+        }
+    }
+}
+
+/// Who is calling the door, and on what.
+#[derive(Debug, Clone)]
+pub(crate) struct DropFileInfo {
+    pub(crate) user: UserInfo,
+    pub(crate) node: u64,
+    pub(crate) time_left: Duration,
+    pub(crate) rows: u16,
+    pub(crate) cols: u16,
+    pub(crate) ansi: bool,
+}
+
+impl DropFileInfo {
+    fn security_level(&self) -> u8 {
+        match self.user.role {
+            Role::Member => 30,
+            Role::Moderator => 100,
+            Role::Sysop => 255,
+        }
+    }
+
+    fn minutes_left(&self) -> u64 {
+        self.time_left.as_secs() / 60
+    }
+
+    /// Doors written for real names want a first and last name. Handles have no spaces to split
+    /// at, so the whole handle is the first name and the last name is left blank.
+    fn names(&self) -> (&str, &str) {
+        (&self.user.handle, "")
+    }
+
+    fn door_sys(&self) -> Vec<String> {
+        let today = Local::now().format("%m/%d/%y").to_string();
+        let time = Local::now().format("%H:%M").to_string();
+        let record = self.user.id().unwrap_or_default().to_string();
+        vec![
+            "COM0:".into(),
+            "0".into(),
+            "8".into(),
+            self.node.to_string(),
+            "0".into(),
+            "Y".into(),
+            "N".into(),
+            "N".into(),
+            "N".into(),
+            self.user.handle.clone(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            self.security_level().to_string(),
+            "1".into(),
+            today.clone(),
+            self.time_left.as_secs().to_string(),
+            self.minutes_left().to_string(),
+            if self.ansi { "GR" } else { "NG" }.into(),
+            self.rows.to_string(),
+            "Y".into(),
+            String::new(),
+            String::new(),
+            "12/31/99".into(),
+            record,
+            "Z".into(),
+            "0".into(),
+            "0".into(),
+            "0".into(),
+            "9999999".into(),
+            "01/01/70".into(),
+            String::new(),
+            String::new(),
+            SYSOP_NAME.into(),
+            self.user.handle.clone(),
+            "00:00".into(),
+            "Y".into(),
+            if self.ansi { "Y" } else { "N" }.into(),
+            "Y".into(),
+            "7".into(),
+            "0".into(),
+            today,
+            time.clone(),
+            time,
+            "32768".into(),
+            "0".into(),
+            "0".into(),
+            "0".into(),
+            String::new(),
+            "0".into(),
+            "0".into(),
+        ]
+    }
+
+    fn dorinfo(&self) -> Vec<String> {
+        let (first, last) = self.names();
+        vec![
+            BBS_NAME.into(),
+            SYSOP_NAME.into(),
+            String::new(),
+            "COM0".into(),
+            "0 BAUD,N,8,1".into(),
+            "0".into(),
+            first.into(),
+            last.into(),
+            String::new(),
+            if self.ansi { "1" } else { "0" }.into(),
+            self.security_level().to_string(),
+            self.minutes_left().to_string(),
+            "-1".into(),
+        ]
+    }
+
+    fn door32(&self) -> Vec<String> {
+        vec![
+            // Local connection: the door talks to its terminal, not a serial port or socket.
+            "0".into(),
+            "0".into(),
+            "0".into(),
+            format!("{} {}", BBS_NAME, env!("CARGO_PKG_VERSION")),
+            self.user.id().unwrap_or_default().to_string(),
+            self.user.handle.clone(),
+            self.user.handle.clone(),
+            self.security_level().to_string(),
+            self.minutes_left().to_string(),
+            if self.ansi { "1" } else { "0" }.into(),
+            self.node.to_string(),
+        ]
+    }
+
+    /// Writes the drop file into `dir`, returning its path.
+    pub(crate) fn write(&self, format: DropFileFormat, dir: &Path) -> io::Result<PathBuf> {
+        let lines = match format {
+            DropFileFormat::DoorSys => self.door_sys(),
+            DropFileFormat::DorInfo => self.dorinfo(),
+            DropFileFormat::Door32 => self.door32(),
+        };
+        let mut contents = lines.join("\r\n");
+        contents.push_str("\r\n");
+        let path = dir.join(format.file_name());
+        fs::write(&path, contents)?;
+        Ok(path)
+    }
+}
+
+/// A score a door reported in its score file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DoorScore {
+    pub(crate) score: i64,
+    pub(crate) metadata: String,
+}
+
+impl DoorScore {
+    /// Reads the score file at `path`, if the door left a sensible one.
+    pub(crate) fn read(path: &Path) -> Option<DoorScore> {
+        let mut text = String::new();
+        File::open(path)
+            .ok()?
+            .take(SCORE_FILE_LIMIT)
+            .read_to_string(&mut text)
+            .ok()?;
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Option<DoorScore> {
+        let mut lines = text.lines();
+        let score = lines.next()?.trim().parse().ok()?;
+        // It ends up on the boards, so nothing that could mess with a caller's terminal.
+        let metadata: String = lines
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(SCORE_METADATA_LENGTH)
+            .collect();
+        Some(DoorScore {
+            score,
+            metadata: metadata.trim().to_string(),
+        })
+    }
+}
+
+/// A private scratch directory for one node's drop files, removed when dropped. It also holds
+/// the empty directory a sandboxed door's root is built on.
+pub(crate) struct DropDirectory {
+    base: PathBuf,
+    path: PathBuf,
+    root: PathBuf,
+}
+
+impl DropDirectory {
+    pub(crate) fn create(node: u64) -> io::Result<DropDirectory> {
+        let base = std::env::temp_dir().join(format!("abbs-node{}", node));
+        // Left over from a session that didn't get to clean up after itself.
+        if base.exists() {
+            fs::remove_dir_all(&base)?;
+        }
+        let mut builder = DirBuilder::new();
+        builder.mode(0o700).create(&base)?;
+        let path = base.join("drop");
+        let root = base.join("root");
+        builder.create(&path)?;
+        builder.create(&root)?;
+        Ok(DropDirectory { base, path, root })
+    }
+
+    /// Where the drop files go.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where the door can leave its score.
+    pub(crate) fn score_path(&self) -> PathBuf {
+        self.path.join(SCORE_FILE)
+    }
+}
+
+impl Drop for DropDirectory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.base);
+    }
+}
@@ -0,0 +1,127 @@
+//! Door games: external programs run for one session at a time in a pseudo-terminal, confined
+//! by a sandbox on Linux, and native games that implement `Door`.
+
+use std::time::Duration;
+
+use config::{Config, Value};
+use log::warn;
+use thiserror::Error;
+
+pub(crate) use self::drop_file::{DropFileFormat, DropFileInfo};
+pub(crate) use self::native::Door;
+pub(crate) use self::pty::DoorProcess;
+pub use self::scores::{ScoreEntry, ScoreUtil};
+pub use self::state::DoorStateUtil;
+
+mod drop_file;
+mod native;
+mod pty;
+mod sandbox;
+mod scores;
+mod state;
+
+/// Doors without a time limit still get no more than this much CPU time, and it's the time left
+/// their drop files report.
+pub(crate) const DEFAULT_TIME_LIMIT: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Error)]
+pub enum DoorError {
+    #[error("Door definitions need a {0}")]
+    MissingField(&'static str),
+    #[error("The door's {0} setting is invalid")]
+    InvalidField(&'static str),
+    #[error("Register a profile to play games")]
+    NotRegistered,
+}
+
+/// A door from the `[[doors]]` tables in the config file.
+#[derive(Debug, Clone)]
+pub(crate) struct DoorDefinition {
+    pub(crate) name: String,
+    pub(crate) command: String,
+    /// Arguments, in which `{node}`, `{dropdir}` and `{dropfile}` are replaced by the caller's
+    /// node number and where their drop file was written.
+    pub(crate) args: Vec<String>,
+    pub(crate) working_dir: Option<String>,
+    /// How long one visit may last. The door is hung up on when it runs out.
+    pub(crate) time_limit: Option<Duration>,
+    /// Which drop file the door reads, if it wants one.
+    pub(crate) drop_file: Option<DropFileFormat>,
+    /// Whether to confine the door. Only worth turning off for doors that are trusted and can't
+    /// run confined, e.g. on systems without unprivileged user namespaces.
+    pub(crate) sandbox: bool,
+}
+
+impl DoorDefinition {
+    /// Reads every door in the config file, skipping ones that don't make sense.
+    pub(crate) fn from_config(config: &Config) -> Vec<DoorDefinition> {
+        config
+            .get_array("doors")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|value| match Self::from_value(value) {
+                Ok(door) => Some(door),
+                Err(err) => {
+                    warn!("Skipping door: {}", err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn from_value(value: Value) -> Result<DoorDefinition, DoorError> {
+        let mut table = value
+            .into_table()
+            .map_err(|_| DoorError::InvalidField("definition"))?;
+        let mut string = |field: &'static str| match table.remove(field) {
+            Some(value) => value
+                .into_string()
+                .map(Some)
+                .map_err(|_| DoorError::InvalidField(field)),
+            None => Ok(None),
+        };
+        let name = string("name")?.ok_or(DoorError::MissingField("name"))?;
+        let command = string("command")?.ok_or(DoorError::MissingField("command"))?;
+        let working_dir = string("working_dir")?;
+        let drop_file = match string("drop_file")? {
+            Some(name) => {
+                Some(DropFileFormat::from_name(&name).ok_or(DoorError::InvalidField("drop_file"))?)
+            }
+            None => None,
+        };
+        let args = match table.remove("args") {
+            Some(args) => args
+                .into_array()
+                .map_err(|_| DoorError::InvalidField("args"))?
+                .into_iter()
+                .map(|arg| {
+                    arg.into_string()
+                        .map_err(|_| DoorError::InvalidField("args"))
+                })
+                .collect::<Result<Vec<String>, DoorError>>()?,
+            None => Vec::new(),
+        };
+        let time_limit = match table.remove("time_limit") {
+            Some(minutes) => match minutes.into_int() {
+                Ok(minutes) if minutes > 0 => Some(Duration::from_secs(minutes as u64 * 60)),
+                _ => return Err(DoorError::InvalidField("time_limit")),
+            },
+            None => None,
+        };
+        let sandbox = match table.remove("sandbox") {
+            Some(sandbox) => sandbox
+                .into_bool()
+                .map_err(|_| DoorError::InvalidField("sandbox"))?,
+            None => true,
+        };
+        Ok(DoorDefinition {
+            name,
+            command,
+            args,
+            working_dir,
+            time_limit,
+            drop_file,
+            sandbox,
+        })
+    }
+}
@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::View;
+use tokio::sync::Mutex;
+
+use crate::user::UserInfo;
+
+/// A game built into the board. Unlike external doors these run in-process as ordinary views,
+/// and keep their saves in the database through `DoorStateUtil`.
+pub(crate) trait Door: Send + Sync {
+    /// Stable key the door's saved games are filed under. Changing it loses every save.
+    fn id(&self) -> &'static str;
+
+    fn name(&self) -> &'static str;
+
+    /// One line for the games menu.
+    fn description(&self) -> &'static str;
+
+    /// Builds the game's screen for `user`, resuming their saved game if they have one.
+    fn open(
+        &self,
+        db: Arc<Mutex<DatabaseConnection>>,
+        user: UserInfo,
+    ) -> Result<Box<dyn View>, anyhow::Error>;
+}
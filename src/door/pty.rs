@@ -0,0 +1,279 @@
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use log::{error, info};
+use tokio::sync::mpsc::Sender;
+
+use super::drop_file::{DoorScore, DropDirectory, DropFileInfo};
+use super::sandbox::Sandbox;
+use super::DoorDefinition;
+
+/// How long a door gets to wrap up after a hang-up before it's killed.
+const HANG_UP_GRACE: Duration = Duration::from_secs(5);
+
+/// A running door, attached to the master side of its own pseudo-terminal.
+///
+/// Output is fed into a terminal emulator on a background thread, so the screen can be drawn
+/// at any time without blocking on the door. Input is written from another thread, since a door
+/// that stops reading would otherwise stall the session once the terminal's buffer fills up.
+pub(crate) struct DoorProcess {
+    master: File,
+    input: mpsc::Sender<Vec<u8>>,
+    pid: libc::pid_t,
+    parser: Arc<Mutex<vt100::Parser>>,
+    updated: Arc<AtomicBool>,
+    exited: Arc<AtomicBool>,
+    score: Arc<Mutex<Option<DoorScore>>>,
+    _drop_directory: DropDirectory,
+}
+
+impl DoorProcess {
+    /// Starts `door` for the caller described by `info`, on a terminal of the size given there,
+    /// nudging `relayout_sender` whenever the screen changes.
+    pub(crate) fn spawn(
+        door: &DoorDefinition,
+        info: &DropFileInfo,
+        relayout_sender: Sender<()>,
+    ) -> io::Result<DoorProcess> {
+        let (rows, cols) = (info.rows, info.cols);
+        let drop_directory = DropDirectory::create(info.node)?;
+        let drop_file = match door.drop_file {
+            Some(format) => info.write(format, drop_directory.path())?,
+            None => drop_directory.path().to_owned(),
+        };
+        let args = door.args.iter().map(|arg| {
+            arg.replace("{node}", &info.node.to_string())
+                .replace("{dropdir}", &drop_directory.path().to_string_lossy())
+                .replace("{dropfile}", &drop_file.to_string_lossy())
+        });
+
+        let (master, slave) = open_pty()?;
+        set_window_size(master.as_raw_fd(), rows, cols)?;
+
+        let mut command = Command::new(&door.command);
+        command
+            .args(args)
+            .env_clear()
+            .env("TERM", "xterm-256color")
+            .env("LINES", rows.to_string())
+            .env("COLUMNS", cols.to_string())
+            .env("PATH", "/usr/local/bin:/usr/bin:/bin")
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        if let Some(working_dir) = &door.working_dir {
+            command.current_dir(working_dir).env("HOME", working_dir);
+        }
+        let sandbox = Sandbox::new(door, &drop_directory)?;
+        // SAFETY: the hook only makes async-signal-safe calls and doesn't allocate.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setsid() < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // Stdin is the slave by now, which makes it the door's controlling terminal.
+                if libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                sandbox.enter()
+            });
+        }
+        let mut child = command.spawn()?;
+        // Our copies of the slave have to go, or reads on the master never see the door hang up.
+        drop(command);
+        let pid = child.id() as libc::pid_t;
+        info!("Started door {} as pid {}", door.name, pid);
+
+        let parser = Arc::new(Mutex::new(vt100::Parser::new(rows, cols, 0)));
+        let updated = Arc::new(AtomicBool::new(true));
+        let exited = Arc::new(AtomicBool::new(false));
+        let score = Arc::new(Mutex::new(None));
+        {
+            let mut reader = master.try_clone()?;
+            let parser = parser.clone();
+            let updated = updated.clone();
+            let relayout_sender = relayout_sender.clone();
+            thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                loop {
+                    // The master reads EIO once nothing has the slave open any more.
+                    let len = match reader.read(&mut buf) {
+                        Ok(0) | Err(_) => break,
+                        Ok(len) => len,
+                    };
+                    parser.lock().unwrap().process(&buf[..len]);
+                    updated.store(true, Ordering::Release);
+                    let _ = relayout_sender.try_send(());
+                }
+            });
+        }
+        let (input, keystrokes) = mpsc::channel::<Vec<u8>>();
+        {
+            let mut writer = master.try_clone()?;
+            thread::spawn(move || {
+                // Ends when the door hangs up or the process handle is dropped.
+                for bytes in keystrokes {
+                    if writer.write_all(&bytes).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        {
+            let exited = exited.clone();
+            let updated = updated.clone();
+            let score = score.clone();
+            let score_path = drop_directory.score_path();
+            let name = door.name.clone();
+            thread::spawn(move || {
+                match child.wait() {
+                    Ok(status) => info!("Door {} exited: {}", name, status),
+                    Err(err) => error!("Failed to wait for door {}: {}", name, err),
+                }
+                // Read before anyone is told the door exited, so it's there when they look.
+                *score.lock().unwrap() = DoorScore::read(&score_path);
+                exited.store(true, Ordering::Release);
+                updated.store(true, Ordering::Release);
+                // Take anything the door left running in the background down with it.
+                signal_group(pid, libc::SIGKILL);
+                let _ = relayout_sender.try_send(());
+            });
+        }
+        if let Some(time_limit) = door.time_limit {
+            let exited = exited.clone();
+            thread::spawn(move || {
+                thread::sleep(time_limit);
+                if !exited.load(Ordering::Acquire) {
+                    hang_up(pid, exited);
+                }
+            });
+        }
+
+        Ok(DoorProcess {
+            master,
+            input,
+            pid,
+            parser,
+            updated,
+            exited,
+            score,
+            _drop_directory: drop_directory,
+        })
+    }
+
+    /// Sends keystrokes to the door without waiting for it to read them.
+    pub(crate) fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.input
+            .send(bytes.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "The door hung up"))
+    }
+
+    pub(crate) fn resize(&self, rows: u16, cols: u16) -> io::Result<()> {
+        self.parser.lock().unwrap().set_size(rows, cols);
+        set_window_size(self.master.as_raw_fd(), rows, cols)
+    }
+
+    /// The door's terminal, as of the last output it wrote.
+    pub(crate) fn screen(&self) -> MutexGuard<'_, vt100::Parser> {
+        self.parser.lock().unwrap()
+    }
+
+    pub(crate) fn has_exited(&self) -> bool {
+        self.exited.load(Ordering::Acquire)
+    }
+
+    /// The score the door reported when it exited, the first time it's asked for.
+    pub(crate) fn take_score(&self) -> Option<DoorScore> {
+        self.score.lock().unwrap().take()
+    }
+
+    /// Whether the screen changed since the last call.
+    pub(crate) fn take_updated(&self) -> bool {
+        self.updated.swap(false, Ordering::AcqRel)
+    }
+
+    /// Asks the door to quit like a modem dropping carrier would, killing it if it doesn't.
+    pub(crate) fn hang_up(&self) {
+        if !self.has_exited() {
+            hang_up(self.pid, self.exited.clone());
+        }
+    }
+}
+
+impl Drop for DoorProcess {
+    fn drop(&mut self) {
+        self.hang_up();
+    }
+}
+
+fn hang_up(pid: libc::pid_t, exited: Arc<AtomicBool>) {
+    signal_group(pid, libc::SIGHUP);
+    thread::spawn(move || {
+        thread::sleep(HANG_UP_GRACE);
+        if !exited.load(Ordering::Acquire) {
+            signal_group(pid, libc::SIGKILL);
+        }
+    });
+}
+
+/// The door leads its own session, so its process group shares its pid.
+fn signal_group(pid: libc::pid_t, signal: libc::c_int) {
+    // SAFETY: kill has no memory safety requirements.
+    unsafe {
+        libc::kill(-pid, signal);
+    }
+}
+
+fn open_pty() -> io::Result<(File, File)> {
+    // SAFETY: posix_openpt returns a fresh descriptor or -1, and we take ownership of it below.
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: fd is open and owned by nobody else.
+    let master = unsafe { File::from_raw_fd(fd) };
+    // SAFETY: these only operate on the descriptor.
+    if unsafe { libc::grantpt(fd) } != 0 || unsafe { libc::unlockpt(fd) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut name = [0 as libc::c_char; 128];
+    // SAFETY: ptsname_r writes a nul-terminated path of at most `name.len()` bytes.
+    let result = unsafe { libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+    // SAFETY: ptsname_r succeeded, so the buffer holds a nul-terminated string.
+    let path = unsafe { CStr::from_ptr(name.as_ptr()) }
+        .to_str()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let slave = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY | libc::O_CLOEXEC)
+        .open(path)?;
+    Ok((master, slave))
+}
+
+fn set_window_size(fd: RawFd, rows: u16, cols: u16) -> io::Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCSWINSZ only reads the struct we pass it.
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,649 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use super::drop_file::DropDirectory;
+use super::{DoorDefinition, DEFAULT_TIME_LIMIT};
+
+const ADDRESS_SPACE_LIMIT: u64 = 512 * 1024 * 1024;
+const FILE_SIZE_LIMIT: u64 = 64 * 1024 * 1024;
+const OPEN_FILES_LIMIT: u64 = 256;
+
+/// What a confined door sees of the system, read-only. Missing ones are skipped, and symlinks
+/// (as on merged-/usr systems) are recreated rather than followed.
+const SYSTEM_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/etc",
+];
+const DEVICES: &[&str] = &[
+    "/dev/null",
+    "/dev/zero",
+    "/dev/full",
+    "/dev/random",
+    "/dev/urandom",
+    "/dev/tty",
+];
+/// Options for the tmpfs a confined door's root is built in, which also holds its `/tmp`.
+const ROOT_OPTIONS: &str = "mode=0755,size=64m";
+
+// Classic BPF opcodes and seccomp return values, from linux/filter.h and linux/seccomp.h.
+// BPF_LD | BPF_W | BPF_ABS
+const BPF_LD_W_ABS: u16 = 0x20;
+// BPF_JMP | BPF_JEQ | BPF_K
+const BPF_JMP_JEQ_K: u16 = 0x15;
+// BPF_JMP | BPF_JGE | BPF_K
+const BPF_JMP_JGE_K: u16 = 0x35;
+// BPF_JMP | BPF_JSET | BPF_K
+const BPF_JMP_JSET_K: u16 = 0x45;
+// BPF_RET | BPF_K
+const BPF_RET_K: u16 = 0x06;
+const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+// Offsets of `nr`, `arch` and the low half of `args[0]` in struct seccomp_data, on the
+// little-endian targets below.
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+const SECCOMP_DATA_ARG0: u32 = 16;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: u32 = 0xC000_00B7;
+/// x32 syscalls share the x86_64 audit arch, so they're told apart by this bit instead.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Syscalls a door has no business making. They fail with EPERM rather than killing the door,
+/// since some runtimes probe for them at startup.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const DENIED_SYSCALLS: &[libc::c_long] = &[
+    libc::SYS_ptrace,
+    libc::SYS_process_vm_readv,
+    libc::SYS_process_vm_writev,
+    libc::SYS_mount,
+    libc::SYS_umount2,
+    libc::SYS_pivot_root,
+    libc::SYS_reboot,
+    libc::SYS_kexec_load,
+    libc::SYS_init_module,
+    libc::SYS_finit_module,
+    libc::SYS_delete_module,
+    libc::SYS_swapon,
+    libc::SYS_swapoff,
+    libc::SYS_setns,
+    libc::SYS_unshare,
+    libc::SYS_bpf,
+    libc::SYS_perf_event_open,
+    libc::SYS_userfaultfd,
+    libc::SYS_io_uring_setup,
+    libc::SYS_io_uring_enter,
+    libc::SYS_io_uring_register,
+    libc::SYS_keyctl,
+    libc::SYS_add_key,
+    libc::SYS_request_key,
+    libc::SYS_acct,
+    libc::SYS_settimeofday,
+    libc::SYS_clock_settime,
+    libc::SYS_sethostname,
+    libc::SYS_setdomainname,
+];
+
+/// clone flags that would put the door's children in namespaces of their own.
+const NAMESPACE_FLAGS: libc::c_int = libc::CLONE_NEWNS
+    | libc::CLONE_NEWCGROUP
+    | libc::CLONE_NEWUTS
+    | libc::CLONE_NEWIPC
+    | libc::CLONE_NEWUSER
+    | libc::CLONE_NEWPID
+    | libc::CLONE_NEWNET;
+
+/// Signals the process left behind outside the door's pid namespace shrugs off, so that hanging
+/// up or ^C on the door's terminal reaches the door instead of orphaning it.
+const IGNORED_SIGNALS: &[libc::c_int] = &[
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGTERM,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SockFilter {
+    code: u16,
+    jt: u8,
+    jf: u8,
+    k: u32,
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: libc::c_ushort,
+    filter: *const SockFilter,
+}
+
+/// One step in building a confined door's root, with every path already under the new root.
+#[derive(Debug)]
+enum Step {
+    /// Creates a directory unless it's already there.
+    Directory {
+        path: CString,
+        mode: libc::mode_t,
+    },
+    /// Creates an empty file for a device to be bound over.
+    File {
+        path: CString,
+    },
+    Symlink {
+        target: CString,
+        path: CString,
+    },
+    /// Binds `source` at `path`, then remounts it with `flags` unless they're empty.
+    Bind {
+        source: CString,
+        path: CString,
+        flags: libc::c_ulong,
+    },
+    Mount {
+        fstype: CString,
+        path: CString,
+        flags: libc::c_ulong,
+        data: Option<CString>,
+    },
+}
+
+/// Limits applied to a door process between fork and exec.
+///
+/// Everything is worked out up front because the forked child may only make async-signal-safe
+/// calls, which rules out allocating.
+#[derive(Debug)]
+pub(super) struct Sandbox {
+    cpu_seconds: u64,
+    confinement: Option<Confinement>,
+}
+
+/// The namespaces, root and syscall filter a door is confined to.
+///
+/// The door gets its own user, mount, pid, network, IPC and UTS namespaces. Its root is a tmpfs
+/// holding read-only binds of `SYSTEM_PATHS`, a few devices, a fresh `/proc` and writable binds
+/// of its working directory and drop directory at their usual paths, so its arguments still
+/// point at the right places. Nothing else of the host's filesystem, such as the board's
+/// database, config or host keys, is reachable.
+#[derive(Debug)]
+struct Confinement {
+    filter: Vec<SockFilter>,
+    uid_map: String,
+    gid_map: String,
+    root: CString,
+    steps: Vec<Step>,
+    working_dir: CString,
+    /// One past the highest descriptor that can be open, for when close_range isn't available.
+    max_fd: libc::c_int,
+}
+
+impl Sandbox {
+    pub(super) fn new(
+        door: &DoorDefinition,
+        drop_directory: &DropDirectory,
+    ) -> io::Result<Sandbox> {
+        let confinement = if door.sandbox {
+            Some(Confinement::new(door, drop_directory)?)
+        } else {
+            None
+        };
+        Ok(Sandbox {
+            cpu_seconds: door
+                .time_limit
+                .unwrap_or(DEFAULT_TIME_LIMIT)
+                .as_secs()
+                .max(1),
+            confinement,
+        })
+    }
+
+    /// Confines the calling process. Only call this in the child, right before exec.
+    ///
+    /// A confined door runs in a child of the calling process, which is the door's init in its
+    /// pid namespace. The calling process stays behind until the door exits and then exits the
+    /// same way, so that it can still be waited for and signalled as the door.
+    pub(super) fn enter(&self) -> io::Result<()> {
+        set_limit(libc::RLIMIT_CPU, self.cpu_seconds)?;
+        set_limit(libc::RLIMIT_AS, ADDRESS_SPACE_LIMIT)?;
+        set_limit(libc::RLIMIT_FSIZE, FILE_SIZE_LIMIT)?;
+        set_limit(libc::RLIMIT_NOFILE, OPEN_FILES_LIMIT)?;
+        set_limit(libc::RLIMIT_CORE, 0)?;
+        // SAFETY: prctl with integer arguments doesn't touch our memory.
+        check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0) })?;
+        match &self.confinement {
+            Some(confinement) => confinement.enter(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Confinement {
+    fn new(door: &DoorDefinition, drop_directory: &DropDirectory) -> io::Result<Confinement> {
+        let filter = syscall_filter();
+        if filter.is_empty() {
+            // There's no filter for this architecture, and running unconfined isn't an option.
+            return Err(io::Error::from_raw_os_error(libc::ENOSYS));
+        }
+        // SAFETY: getuid and getgid can't fail.
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        let root = drop_directory.root();
+        let mut steps = vec![Step::Mount {
+            fstype: c_string("tmpfs")?,
+            path: path_string(root)?,
+            flags: libc::MS_NOSUID | libc::MS_NODEV,
+            data: Some(c_string(ROOT_OPTIONS)?),
+        }];
+        let mut created = Vec::new();
+        for path in SYSTEM_PATHS.iter().map(Path::new) {
+            let metadata = match fs::symlink_metadata(path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+            let inside = under(root, path);
+            if metadata.file_type().is_symlink() {
+                steps.push(Step::Symlink {
+                    target: path_string(&fs::read_link(path)?)?,
+                    path: path_string(&inside)?,
+                });
+            } else if metadata.is_dir() {
+                directories(&mut steps, &mut created, root, path)?;
+                steps.push(Step::Bind {
+                    source: path_string(path)?,
+                    path: path_string(&inside)?,
+                    flags: remount_flags(path, true)?,
+                });
+            }
+        }
+        directories(&mut steps, &mut created, root, Path::new("/dev"))?;
+        for device in DEVICES.iter().map(Path::new) {
+            if device.exists() {
+                let inside = path_string(&under(root, device))?;
+                steps.push(Step::File {
+                    path: inside.clone(),
+                });
+                steps.push(Step::Bind {
+                    source: path_string(device)?,
+                    path: inside,
+                    flags: 0,
+                });
+            }
+        }
+        directories(&mut steps, &mut created, root, Path::new("/proc"))?;
+        steps.push(Step::Mount {
+            fstype: c_string("proc")?,
+            path: path_string(&under(root, Path::new("/proc")))?,
+            flags: libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC,
+            data: None,
+        });
+        steps.push(Step::Directory {
+            path: path_string(&under(root, Path::new("/tmp")))?,
+            mode: 0o1777,
+        });
+        created.push(PathBuf::from("/tmp"));
+        let working_dir = match &door.working_dir {
+            Some(working_dir) => Some(std::env::current_dir()?.join(working_dir)),
+            None => None,
+        };
+        for path in working_dir
+            .iter()
+            .map(PathBuf::as_path)
+            .chain([drop_directory.path()])
+        {
+            directories(&mut steps, &mut created, root, path)?;
+            steps.push(Step::Bind {
+                source: path_string(path)?,
+                path: path_string(&under(root, path))?,
+                flags: remount_flags(path, false)?,
+            });
+        }
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit only writes the struct we pass it.
+        check(unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) })?;
+        Ok(Confinement {
+            filter,
+            uid_map: format!("{} {} 1", uid, uid),
+            gid_map: format!("{} {} 1", gid, gid),
+            root: path_string(root)?,
+            steps,
+            working_dir: path_string(working_dir.as_deref().unwrap_or(Path::new("/")))?,
+            max_fd: limit.rlim_cur.min(libc::c_int::MAX as libc::rlim_t) as libc::c_int,
+        })
+    }
+
+    fn enter(&self) -> io::Result<()> {
+        // SAFETY: unshare only changes which namespaces this process belongs to.
+        check(unsafe {
+            libc::unshare(
+                libc::CLONE_NEWUSER
+                    | libc::CLONE_NEWNS
+                    | libc::CLONE_NEWPID
+                    | libc::CLONE_NEWNET
+                    | libc::CLONE_NEWIPC
+                    | libc::CLONE_NEWUTS,
+            )
+        })?;
+        // Only our own ids are mapped, so the door has no capabilities once it's exec'd.
+        write_file(b"/proc/self/setgroups\0", b"deny")?;
+        write_file(b"/proc/self/uid_map\0", self.uid_map.as_bytes())?;
+        write_file(b"/proc/self/gid_map\0", self.gid_map.as_bytes())?;
+        // SAFETY: we're the only thread in this process, and the child only makes
+        // async-signal-safe calls until it execs.
+        let pid = unsafe { libc::fork() };
+        if pid < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if pid > 0 {
+            self.stand_in_for(pid);
+        }
+        // SAFETY: as in `Sandbox::enter`.
+        check(unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL, 0, 0, 0) })?;
+        self.build_root()?;
+        // SAFETY: as above, prctl with integer arguments.
+        check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+        let program = SockFprog {
+            len: self.filter.len() as libc::c_ushort,
+            filter: self.filter.as_ptr(),
+        };
+        // SAFETY: `program` points at the filter, which outlives the call. The kernel copies it.
+        check(unsafe {
+            libc::prctl(
+                libc::PR_SET_SECCOMP,
+                SECCOMP_MODE_FILTER,
+                &program as *const SockFprog,
+                0,
+                0,
+            )
+        })?;
+        Ok(())
+    }
+
+    /// Builds the door's root from `steps` and makes it the root of this mount namespace.
+    fn build_root(&self) -> io::Result<()> {
+        // SAFETY: mount, pivot_root, umount2 and friends only read the strings we pass them,
+        // which are all nul-terminated.
+        unsafe {
+            // Keep what happens here from propagating back to the host's mounts.
+            check(libc::mount(
+                std::ptr::null(),
+                b"/\0".as_ptr().cast(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+            for step in &self.steps {
+                step.run()?;
+            }
+            check(libc::chdir(self.root.as_ptr()))?;
+            // Stacks the old root under the new one, from where it can be detached.
+            check(
+                libc::syscall(libc::SYS_pivot_root, b".\0".as_ptr(), b".\0".as_ptr())
+                    as libc::c_int,
+            )?;
+            check(libc::umount2(b".\0".as_ptr().cast(), libc::MNT_DETACH))?;
+            check(libc::chdir(self.working_dir.as_ptr()))?;
+        }
+        Ok(())
+    }
+
+    /// Waits in place of the door for it to exit, then exits the same way. Never returns.
+    fn stand_in_for(&self, pid: libc::pid_t) -> ! {
+        // SAFETY: these only operate on descriptors, signal dispositions and our own exit.
+        unsafe {
+            // The spawning thread waits for the exec status pipe to close, and the door's
+            // terminal only hangs up once every copy of it is closed, so nothing may stay open.
+            if libc::syscall(libc::SYS_close_range, 0, libc::c_uint::MAX, 0) < 0 {
+                for fd in 0..self.max_fd {
+                    libc::close(fd);
+                }
+            }
+            for &signal in IGNORED_SIGNALS {
+                libc::signal(signal, libc::SIG_IGN);
+            }
+            let mut status = 0;
+            loop {
+                if libc::waitpid(pid, &mut status, 0) == pid {
+                    break;
+                }
+                if io::Error::last_os_error().raw_os_error() != Some(libc::EINTR) {
+                    libc::_exit(1);
+                }
+            }
+            if libc::WIFSIGNALED(status) {
+                libc::_exit(128 + libc::WTERMSIG(status));
+            }
+            libc::_exit(libc::WEXITSTATUS(status))
+        }
+    }
+}
+
+impl Step {
+    fn run(&self) -> io::Result<()> {
+        // SAFETY: every path is a nul-terminated string that outlives the call.
+        unsafe {
+            match self {
+                Step::Directory { path, mode } => {
+                    if libc::mkdir(path.as_ptr(), *mode) < 0 {
+                        let err = io::Error::last_os_error();
+                        if err.raw_os_error() != Some(libc::EEXIST) {
+                            return Err(err);
+                        }
+                    } else {
+                        // Undo the umask, which would take the sticky /tmp's write bits away.
+                        check(libc::chmod(path.as_ptr(), *mode))?;
+                    }
+                }
+                Step::File { path } => {
+                    let fd = libc::open(
+                        path.as_ptr(),
+                        libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC,
+                        0o644,
+                    );
+                    check(fd)?;
+                    libc::close(fd);
+                }
+                Step::Symlink { target, path } => {
+                    check(libc::symlink(target.as_ptr(), path.as_ptr()))?;
+                }
+                Step::Bind {
+                    source,
+                    path,
+                    flags,
+                } => {
+                    check(libc::mount(
+                        source.as_ptr(),
+                        path.as_ptr(),
+                        std::ptr::null(),
+                        libc::MS_BIND | libc::MS_REC,
+                        std::ptr::null(),
+                    ))?;
+                    if *flags != 0 {
+                        check(libc::mount(
+                            std::ptr::null(),
+                            path.as_ptr(),
+                            std::ptr::null(),
+                            *flags,
+                            std::ptr::null(),
+                        ))?;
+                    }
+                }
+                Step::Mount {
+                    fstype,
+                    path,
+                    flags,
+                    data,
+                } => {
+                    check(libc::mount(
+                        fstype.as_ptr(),
+                        path.as_ptr(),
+                        fstype.as_ptr(),
+                        *flags,
+                        data.as_ref()
+                            .map_or(std::ptr::null(), |data| data.as_ptr().cast()),
+                    ))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Adds steps creating `path` and its parents under `root`, other than those in `created`.
+fn directories(
+    steps: &mut Vec<Step>,
+    created: &mut Vec<PathBuf>,
+    root: &Path,
+    path: &Path,
+) -> io::Result<()> {
+    let mut ancestors: Vec<&Path> = path
+        .ancestors()
+        .filter(|ancestor| ancestor.parent().is_some())
+        .collect();
+    ancestors.reverse();
+    for ancestor in ancestors {
+        if !created.iter().any(|done| done == ancestor) {
+            steps.push(Step::Directory {
+                path: path_string(&under(root, ancestor))?,
+                mode: 0o755,
+            });
+            created.push(ancestor.to_owned());
+        }
+    }
+    Ok(())
+}
+
+/// Where absolute `path` ends up under `root`.
+fn under(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+/// The flags to remount a bind of `path` with. Flags the host's mount has can't be dropped
+/// from inside a user namespace, so they're carried over.
+fn remount_flags(path: &Path, read_only: bool) -> io::Result<libc::c_ulong> {
+    let path = path_string(path)?;
+    // SAFETY: statvfs is plain old data, and statvfs only writes it.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is nul-terminated and `stat` is big enough.
+    check(unsafe { libc::statvfs(path.as_ptr(), &mut stat) })?;
+    let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_NOSUID | libc::MS_NODEV;
+    if read_only || stat.f_flag & libc::ST_RDONLY != 0 {
+        flags |= libc::MS_RDONLY;
+    }
+    if stat.f_flag & libc::ST_NOEXEC != 0 {
+        flags |= libc::MS_NOEXEC;
+    }
+    Ok(flags)
+}
+
+fn path_string(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+fn c_string(string: &str) -> io::Result<CString> {
+    path_string(Path::new(string))
+}
+
+/// Writes `contents` to the file at nul-terminated `path` in one go, as proc files want.
+fn write_file(path: &[u8], contents: &[u8]) -> io::Result<()> {
+    // SAFETY: `path` is nul-terminated, and write only reads `contents`.
+    unsafe {
+        let fd = libc::open(path.as_ptr().cast(), libc::O_WRONLY | libc::O_CLOEXEC);
+        check(fd)?;
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        libc::close(fd);
+        if written < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn set_limit(resource: libc::__rlimit_resource_t, limit: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: limit as libc::rlim_t,
+        rlim_max: limit as libc::rlim_t,
+    };
+    // SAFETY: setrlimit only reads the struct we pass it.
+    check(unsafe { libc::setrlimit(resource, &limit) })
+}
+
+fn check(result: libc::c_int) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn statement(code: u16, k: u32) -> SockFilter {
+    SockFilter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+    SockFilter { code, jt, jf, k }
+}
+
+/// Builds a filter that kills processes using a foreign syscall ABI, denies `DENIED_SYSCALLS`
+/// and clones into new namespaces, and has clone3 report itself unsupported so that libc falls
+/// back to clone, whose flags the filter can see. Empty on architectures we don't have syscall
+/// numbers for.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+fn syscall_filter() -> Vec<SockFilter> {
+    // Where the checks can jump to, counted from the end of the checks, which falls through to
+    // allowing the call.
+    const CHECK_CLONE: usize = 1;
+    const DENY: usize = 4;
+    const UNSUPPORTED: usize = 5;
+    let mut filter = vec![
+        statement(BPF_LD_W_ABS, SECCOMP_DATA_ARCH),
+        jump(BPF_JMP_JEQ_K, AUDIT_ARCH, 1, 0),
+        statement(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+        statement(BPF_LD_W_ABS, SECCOMP_DATA_NR),
+    ];
+    let mut checks = Vec::new();
+    #[cfg(target_arch = "x86_64")]
+    checks.push((BPF_JMP_JGE_K, X32_SYSCALL_BIT, DENY));
+    for &syscall in DENIED_SYSCALLS {
+        checks.push((BPF_JMP_JEQ_K, syscall as u32, DENY));
+    }
+    checks.push((BPF_JMP_JEQ_K, libc::SYS_clone3 as u32, UNSUPPORTED));
+    checks.push((BPF_JMP_JEQ_K, libc::SYS_clone as u32, CHECK_CLONE));
+    // Jumps are relative and only go forwards, over the checks after them and on to the target.
+    let count = checks.len();
+    for (index, (code, k, target)) in checks.into_iter().enumerate() {
+        filter.push(jump(code, k, (count - index - 1 + target) as u8, 0));
+    }
+    filter.push(statement(BPF_RET_K, SECCOMP_RET_ALLOW));
+    // clone's flags are its first argument on both architectures.
+    filter.push(statement(BPF_LD_W_ABS, SECCOMP_DATA_ARG0));
+    filter.push(jump(BPF_JMP_JSET_K, NAMESPACE_FLAGS as u32, 1, 0));
+    filter.push(statement(BPF_RET_K, SECCOMP_RET_ALLOW));
+    filter.push(statement(BPF_RET_K, SECCOMP_RET_ERRNO | libc::EPERM as u32));
+    filter.push(statement(
+        BPF_RET_K,
+        SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+    ));
+    filter
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn syscall_filter() -> Vec<SockFilter> {
+    Vec::new()
+}
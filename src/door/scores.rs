@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult, QueryFilter,
+    QueryOrder, QuerySelect, Set,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{
+        gen::{
+            prelude::{Score, User},
+            score, user,
+        },
+        now,
+    },
+    user::UserInfo,
+};
+
+use super::DoorError;
+
+/// A score with its player's handle resolved.
+#[derive(Debug, Clone)]
+pub struct ScoreEntry {
+    pub score: score::Model,
+    pub handle: String,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct PlayerBest {
+    user_id: i32,
+    best: i64,
+}
+
+/// High scores for every door, native or not. Doors decide what a score means, but bigger is
+/// always better.
+pub struct ScoreUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl ScoreUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> ScoreUtil {
+        ScoreUtil { db }
+    }
+
+    /// Records a finished game at `door`, with `metadata` saying briefly how it went. Returns
+    /// whether it beat the player's previous best there.
+    pub async fn record(
+        &self,
+        door: &str,
+        user: &UserInfo,
+        score: i64,
+        metadata: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let user_id = user.id().ok_or(DoorError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let previous_best = Score::find()
+            .filter(score::Column::Door.eq(door))
+            .filter(score::Column::UserId.eq(user_id))
+            .order_by_desc(score::Column::Score)
+            .one(&db)
+            .await?;
+        let personal_best = previous_best.map_or(true, |best| score > best.score);
+        score::ActiveModel {
+            door: Set(door.to_string()),
+            user_id: Set(user_id),
+            score: Set(score),
+            metadata: Set(metadata.to_string()),
+            personal_best: Set(personal_best),
+            created: Set(now()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?;
+        Ok(personal_best)
+    }
+
+    /// The best `limit` players at `door`, by their best score. With `since`, only scores set
+    /// after that timestamp count.
+    pub async fn leaderboard(
+        &self,
+        door: &str,
+        since: Option<String>,
+        limit: usize,
+    ) -> Result<Vec<ScoreEntry>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let mut query = Score::find().filter(score::Column::Door.eq(door));
+        if let Some(since) = since {
+            query = query.filter(score::Column::Created.gte(since));
+        }
+        let bests: HashMap<i32, i64> = query
+            .clone()
+            .select_only()
+            .column(score::Column::UserId)
+            .column_as(score::Column::Score.max(), "best")
+            .group_by(score::Column::UserId)
+            .order_by_desc(score::Column::Score.max())
+            .limit(limit as u64)
+            .into_model::<PlayerBest>()
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|player| (player.user_id, player.best))
+            .collect();
+        // The game each of them set their best in, the first time if they matched it later.
+        let scores = query
+            .filter(score::Column::UserId.is_in(bests.keys().copied().collect::<Vec<i32>>()))
+            .order_by_asc(score::Column::Created)
+            .order_by_asc(score::Column::Id)
+            .all(&db)
+            .await?;
+        let mut best: Vec<score::Model> = Vec::new();
+        for score in scores {
+            let is_first_best = bests.get(&score.user_id) == Some(&score.score)
+                && !best.iter().any(|best| best.user_id == score.user_id);
+            if is_first_best {
+                best.push(score);
+            }
+        }
+        // Earlier ties rank higher, so whoever got there first keeps the spot.
+        best.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.created.cmp(&b.created))
+        });
+        Self::with_handles(&db, best).await
+    }
+
+    /// The latest personal bests across all doors, newest first.
+    pub async fn recent_achievements(&self, limit: u64) -> Result<Vec<ScoreEntry>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let scores = Score::find()
+            .filter(score::Column::PersonalBest.eq(true))
+            .order_by_desc(score::Column::Created)
+            .order_by_desc(score::Column::Id)
+            .limit(limit)
+            .all(&db)
+            .await?;
+        Self::with_handles(&db, scores).await
+    }
+
+    async fn with_handles(
+        db: &DatabaseConnection,
+        scores: Vec<score::Model>,
+    ) -> Result<Vec<ScoreEntry>, anyhow::Error> {
+        let user_ids: Vec<i32> = scores.iter().map(|score| score.user_id).collect();
+        let users = User::find()
+            .filter(user::Column::Id.is_in(user_ids))
+            .all(db)
+            .await?;
+        Ok(scores
+            .into_iter()
+            .map(|score| ScoreEntry {
+                handle: users
+                    .iter()
+                    .find(|user| user.id == score.user_id)
+                    .map(|user| user.handle.clone())
+                    .unwrap_or_default(),
+                score,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{
+        gen::{door_state, prelude::DoorState},
+        now,
+    },
+    user::UserInfo,
+};
+
+use super::DoorError;
+
+/// Saved games for native doors. Each door keeps one opaque string per player, in whatever
+/// format suits it.
+pub struct DoorStateUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl DoorStateUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> DoorStateUtil {
+        DoorStateUtil { db }
+    }
+
+    async fn find(
+        db: &DatabaseConnection,
+        door: &str,
+        user_id: i32,
+    ) -> Result<Option<door_state::Model>, anyhow::Error> {
+        Ok(DoorState::find()
+            .filter(door_state::Column::Door.eq(door))
+            .filter(door_state::Column::UserId.eq(user_id))
+            .one(db)
+            .await?)
+    }
+
+    pub async fn load(&self, door: &str, user: &UserInfo) -> Result<Option<String>, anyhow::Error> {
+        let user_id = user.id().ok_or(DoorError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        Ok(Self::find(&db, door, user_id)
+            .await?
+            .map(|saved| saved.state))
+    }
+
+    pub async fn save(
+        &self,
+        door: &str,
+        user: &UserInfo,
+        state: &str,
+    ) -> Result<(), anyhow::Error> {
+        let user_id = user.id().ok_or(DoorError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        match Self::find(&db, door, user_id).await? {
+            Some(existing) => {
+                let mut active: door_state::ActiveModel = existing.into();
+                active.state = Set(state.to_string());
+                active.updated = Set(now());
+                active.update(&db).await?;
+            }
+            None => {
+                door_state::ActiveModel {
+                    door: Set(door.to_string()),
+                    user_id: Set(user_id),
+                    state: Set(state.to_string()),
+                    updated: Set(now()),
+                    ..Default::default()
+                }
+                .insert(&db)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
+use tokio::sync::Mutex;
+
+use crate::db::{
+    gen::{draft, prelude::Draft},
+    now,
+};
+
+/// What an unfinished piece of writing was going to become.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DraftTarget {
+    Reply { thread: i32 },
+    NewThread { forum: i32 },
+    Mail { recipient: String },
+}
+
+impl DraftTarget {
+    /// The value stored in `draft.target`.
+    fn key(&self) -> String {
+        match self {
+            DraftTarget::Reply { thread } => format!("reply:{}", thread),
+            DraftTarget::NewThread { forum } => format!("forum:{}", forum),
+            DraftTarget::Mail { recipient } => format!("mail:{}", recipient.to_lowercase()),
+        }
+    }
+}
+
+pub struct DraftUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl DraftUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> DraftUtil {
+        DraftUtil { db }
+    }
+
+    async fn find(
+        db: &DatabaseConnection,
+        user_id: i32,
+        target: &DraftTarget,
+    ) -> Result<Option<draft::Model>, anyhow::Error> {
+        Ok(Draft::find()
+            .filter(draft::Column::UserId.eq(user_id))
+            .filter(draft::Column::Target.eq(target.key()))
+            .one(db)
+            .await?)
+    }
+
+    /// Keeps `body` as `user_id`'s draft for `target`, replacing any earlier one. An empty body
+    /// discards the draft.
+    pub async fn save(
+        &self,
+        user_id: i32,
+        target: &DraftTarget,
+        body: &str,
+    ) -> Result<(), anyhow::Error> {
+        if body.trim().is_empty() {
+            return self.discard(user_id, target).await;
+        }
+        let db = self.db.lock().await.to_owned();
+        match Self::find(&db, user_id, target).await? {
+            Some(existing) => {
+                let mut active: draft::ActiveModel = existing.into();
+                active.body = Set(body.to_string());
+                active.updated = Set(now());
+                active.update(&db).await?;
+            }
+            None => {
+                draft::ActiveModel {
+                    user_id: Set(user_id),
+                    target: Set(target.key()),
+                    body: Set(body.to_string()),
+                    updated: Set(now()),
+                    ..Default::default()
+                }
+                .insert(&db)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn load(
+        &self,
+        user_id: i32,
+        target: &DraftTarget,
+    ) -> Result<Option<draft::Model>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Self::find(&db, user_id, target).await
+    }
+
+    pub async fn discard(&self, user_id: i32, target: &DraftTarget) -> Result<(), anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Draft::delete_many()
+            .filter(draft::Column::UserId.eq(user_id))
+            .filter(draft::Column::Target.eq(target.key()))
+            .exec(&db)
+            .await?;
+        Ok(())
+    }
+}
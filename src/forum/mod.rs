@@ -0,0 +1,532 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{
+        gen::{
+            forum, post,
+            prelude::{Forum, Post, Thread, User},
+            thread, user,
+        },
+        now,
+    },
+    mention::MentionUtil,
+    notification::{notify_user, Notification},
+    user::{Role, UserInfo},
+};
+
+pub use self::polls::{PollError, PollTally};
+pub use self::reactions::{reaction_kinds, PostReactions, DOWNVOTE, UPVOTE};
+pub use self::unread::{unread_generation, NewActivity};
+
+use self::search::{index_posts, search_index, ForumQuery, SearchHit};
+
+mod polls;
+mod reactions;
+pub(crate) mod search;
+mod unread;
+
+static FORUM_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Counter bumped on every forum write. Open views compare it against the value they loaded
+/// with to notice new content.
+pub fn forum_generation() -> u64 {
+    FORUM_GENERATION.load(Ordering::SeqCst)
+}
+
+pub(crate) fn bump_generation() {
+    FORUM_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+#[derive(Debug, Error)]
+pub enum ForumError {
+    #[error("Register a profile to post")]
+    NotRegistered,
+    #[error("Forum not found")]
+    NoSuchForum,
+    #[error("Thread not found")]
+    NoSuchThread,
+    #[error("Post not found")]
+    NoSuchPost,
+    #[error("Threads need a title")]
+    EmptyTitle,
+    #[error("Posts need a body")]
+    EmptyBody,
+    #[error("You can only edit your own posts")]
+    NotYourPost,
+    #[error("No forum is called {0}")]
+    NoSuchForumName(String),
+    #[error("This thread is locked")]
+    Locked,
+    #[error("There's no reaction called {0}")]
+    NoSuchReaction(String),
+    #[error("You can't vote on your own post")]
+    OwnPost,
+}
+
+/// A forum together with its depth in the forum tree, in display order.
+#[derive(Debug, Clone)]
+pub struct ForumNode {
+    pub forum: forum::Model,
+    pub depth: usize,
+}
+
+/// A thread with what a thread list needs to show about it.
+#[derive(Debug, Clone)]
+pub struct ThreadSummary {
+    pub thread: thread::Model,
+    pub author: String,
+    pub posts: usize,
+    pub unread: usize,
+    pub last_post: Option<String>,
+}
+
+impl ThreadSummary {
+    /// When the thread last changed. Timestamps sort lexically, so these compare in time order.
+    pub fn activity(&self) -> Option<&String> {
+        self.last_post.as_ref().or(self.thread.created.as_ref())
+    }
+}
+
+/// A post with its author's handle resolved.
+#[derive(Debug, Clone)]
+pub struct PostEntry {
+    pub post: post::Model,
+    pub author: String,
+}
+
+pub struct ForumUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl ForumUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> ForumUtil {
+        ForumUtil { db }
+    }
+
+    /// Returns every forum depth-first, with sub-forums following their parent.
+    pub async fn forum_tree(&self) -> Result<Vec<ForumNode>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let forums = Forum::find()
+            .order_by_asc(forum::Column::Position)
+            .order_by_asc(forum::Column::Id)
+            .all(&db)
+            .await?;
+        let mut tree = Vec::new();
+        Self::collect_children(&forums, None, 0, &mut tree);
+        Ok(tree)
+    }
+
+    fn collect_children(
+        forums: &[forum::Model],
+        parent: Option<i32>,
+        depth: usize,
+        tree: &mut Vec<ForumNode>,
+    ) {
+        for forum in forums.iter().filter(|forum| forum.parent == parent) {
+            tree.push(ForumNode {
+                forum: forum.clone(),
+                depth,
+            });
+            Self::collect_children(forums, Some(forum.id), depth + 1, tree);
+        }
+    }
+
+    async fn handles(
+        db: &DatabaseConnection,
+        mut ids: Vec<i32>,
+    ) -> Result<HashMap<i32, String>, anyhow::Error> {
+        ids.sort_unstable();
+        ids.dedup();
+        Ok(User::find()
+            .filter(user::Column::Id.is_in(ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.handle))
+            .collect())
+    }
+
+    /// Returns the threads in a forum, sticky threads first and then by latest activity. Unread
+    /// counts are filled in for `reader` if given.
+    pub async fn threads(
+        &self,
+        reader: Option<&UserInfo>,
+        forum_id: i32,
+    ) -> Result<Vec<ThreadSummary>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let threads = Thread::find()
+            .filter(thread::Column::Forum.eq(forum_id))
+            .all(&db)
+            .await?;
+        let mut summaries = Self::summarize(&db, reader, threads).await?;
+        summaries.sort_by(|a, b| {
+            b.thread
+                .sticky
+                .cmp(&a.thread.sticky)
+                .then_with(|| b.activity().cmp(&a.activity()))
+        });
+        Ok(summaries)
+    }
+
+    async fn summarize(
+        db: &DatabaseConnection,
+        reader: Option<&UserInfo>,
+        threads: Vec<thread::Model>,
+    ) -> Result<Vec<ThreadSummary>, anyhow::Error> {
+        let thread_ids: Vec<i32> = threads.iter().map(|thread| thread.id).collect();
+        let posts = Self::post_refs(db, thread_ids.clone()).await?;
+        let markers = match reader.and_then(|reader| reader.id()) {
+            Some(reader_id) => Self::read_markers(db, reader_id, thread_ids).await?,
+            None => HashMap::new(),
+        };
+        let handles =
+            Self::handles(db, threads.iter().map(|thread| thread.author).collect()).await?;
+        Ok(threads
+            .into_iter()
+            .map(|thread| {
+                let thread_posts = posts.iter().filter(|post| post.thread == thread.id);
+                let last_read = markers.get(&thread.id).copied().unwrap_or(0);
+                ThreadSummary {
+                    author: handles.get(&thread.author).cloned().unwrap_or_default(),
+                    posts: thread_posts.clone().count(),
+                    unread: match reader {
+                        Some(_) => thread_posts
+                            .clone()
+                            .filter(|post| post.id > last_read)
+                            .count(),
+                        None => 0,
+                    },
+                    last_post: thread_posts.filter_map(|post| post.created.clone()).max(),
+                    thread,
+                }
+            })
+            .collect())
+    }
+
+    pub async fn forum(&self, forum_id: i32) -> Result<forum::Model, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(Forum::find_by_id(forum_id)
+            .one(&db)
+            .await?
+            .ok_or(ForumError::NoSuchForum)?)
+    }
+
+    pub async fn thread(&self, thread_id: i32) -> Result<thread::Model, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(Thread::find_by_id(thread_id)
+            .one(&db)
+            .await?
+            .ok_or(ForumError::NoSuchThread)?)
+    }
+
+    pub async fn post(&self, post_id: i32) -> Result<post::Model, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(Post::find_by_id(post_id)
+            .one(&db)
+            .await?
+            .ok_or(ForumError::NoSuchPost)?)
+    }
+
+    /// Returns the posts of a thread, oldest first.
+    pub async fn posts(&self, thread_id: i32) -> Result<Vec<PostEntry>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let posts = Post::find()
+            .filter(post::Column::Thread.eq(thread_id))
+            .order_by_asc(post::Column::Created)
+            .order_by_asc(post::Column::Id)
+            .all(&db)
+            .await?;
+        let handles = Self::handles(&db, posts.iter().map(|post| post.author).collect()).await?;
+        Ok(posts
+            .into_iter()
+            .map(|post| PostEntry {
+                author: handles.get(&post.author).cloned().unwrap_or_default(),
+                post,
+            })
+            .collect())
+    }
+
+    /// Starts a thread with `body` as its first post.
+    pub async fn create_thread(
+        &self,
+        author: &UserInfo,
+        forum_id: i32,
+        title: &str,
+        body: &str,
+    ) -> Result<thread::Model, anyhow::Error> {
+        let author_id = author.id().ok_or(ForumError::NotRegistered)?;
+        if title.trim().is_empty() {
+            return Err(ForumError::EmptyTitle.into());
+        }
+        if body.trim().is_empty() {
+            return Err(ForumError::EmptyBody.into());
+        }
+        // Together, so that there's never a thread without a first post.
+        let (thread, post) = {
+            let db = self.db.lock().await.to_owned();
+            let txn = db.begin().await?;
+            Forum::find_by_id(forum_id)
+                .one(&txn)
+                .await?
+                .ok_or(ForumError::NoSuchForum)?;
+            let thread = thread::ActiveModel {
+                name: Set(title.trim().to_string()),
+                created: Set(Some(now())),
+                locked: Set(None),
+                sticky: Set(false),
+                forum: Set(forum_id),
+                author: Set(author_id),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            let post = Self::new_post(&txn, author_id, thread.id, body).await?;
+            txn.commit().await?;
+            (thread, post)
+        };
+        self.posted(author, &thread, &post).await?;
+        Ok(thread)
+    }
+
+    /// Adds a post to a thread and tells the thread's author about it.
+    pub async fn reply(
+        &self,
+        author: &UserInfo,
+        thread_id: i32,
+        body: &str,
+    ) -> Result<post::Model, anyhow::Error> {
+        let author_id = author.id().ok_or(ForumError::NotRegistered)?;
+        if body.trim().is_empty() {
+            return Err(ForumError::EmptyBody.into());
+        }
+        let thread = self.thread(thread_id).await?;
+        // Moderators can still post in locked threads, e.g. to explain why they were locked.
+        if thread.locked.is_some() && !author.has_role(Role::Moderator) {
+            return Err(ForumError::Locked.into());
+        }
+        let post = self.insert_post(author, &thread, body).await?;
+        if thread.author != author_id {
+            notify_user(
+                thread.author,
+                Notification::Reply {
+                    from: author.handle.clone(),
+                    thread: thread.name.clone(),
+                },
+            );
+        }
+        Ok(post)
+    }
+
+    async fn insert_post(
+        &self,
+        author: &UserInfo,
+        thread: &thread::Model,
+        body: &str,
+    ) -> Result<post::Model, anyhow::Error> {
+        let author_id = author.id().ok_or(ForumError::NotRegistered)?;
+        let post = {
+            let db = self.db.lock().await.to_owned();
+            Self::new_post(&db, author_id, thread.id, body).await?
+        };
+        self.posted(author, thread, &post).await?;
+        Ok(post)
+    }
+
+    async fn new_post<C: ConnectionTrait>(
+        db: &C,
+        author_id: i32,
+        thread_id: i32,
+        body: &str,
+    ) -> Result<post::Model, anyhow::Error> {
+        Ok(post::ActiveModel {
+            created: Set(Some(now())),
+            modified: Set(None),
+            author: Set(author_id),
+            thread: Set(thread_id),
+            body: Set(body.to_string()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+
+    /// Catches the author's read marker, the search index and mentions up with a new post.
+    async fn posted(
+        &self,
+        author: &UserInfo,
+        thread: &thread::Model,
+        post: &post::Model,
+    ) -> Result<(), anyhow::Error> {
+        self.mark_read(author, thread.id, post.id).await?;
+        bump_generation();
+        Self::index(post, thread, &author.handle);
+        MentionUtil::new(self.db.clone())
+            .record(
+                author,
+                &post.body,
+                Some(post.id),
+                &format!("\"{}\"", thread.name),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Search is secondary to posting, so a failure to index is logged rather than returned.
+    fn index(post: &post::Model, thread: &thread::Model, author: &str) {
+        let handles = HashMap::from([(post.author, author.to_string())]);
+        if let Err(err) = index_posts(&[post.clone()], thread, &handles) {
+            error!("Failed to index post {}: {}", post.id, err);
+        }
+    }
+
+    /// Rewrites the search index entries for every post in the given threads, e.g. after posts
+    /// moved between threads or a thread moved between forums.
+    pub(crate) async fn reindex_threads(&self, thread_ids: Vec<i32>) -> Result<(), anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let threads = Thread::find()
+            .filter(thread::Column::Id.is_in(thread_ids.clone()))
+            .all(&db)
+            .await?;
+        let posts = Post::find()
+            .filter(post::Column::Thread.is_in(thread_ids))
+            .all(&db)
+            .await?;
+        let handles = Self::handles(&db, posts.iter().map(|post| post.author).collect()).await?;
+        for thread in threads.iter() {
+            let thread_posts: Vec<post::Model> = posts
+                .iter()
+                .filter(|post| post.thread == thread.id)
+                .cloned()
+                .collect();
+            index_posts(&thread_posts, thread, &handles)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the body of a post. Authors can edit their own posts and moderators anyone's.
+    /// Only moderators can edit posts in locked threads.
+    pub async fn edit_post(
+        &self,
+        editor: &UserInfo,
+        post_id: i32,
+        body: &str,
+    ) -> Result<post::Model, anyhow::Error> {
+        let editor_id = editor.id().ok_or(ForumError::NotRegistered)?;
+        if body.trim().is_empty() {
+            return Err(ForumError::EmptyBody.into());
+        }
+        let post = self.post(post_id).await?;
+        if post.author != editor_id && !editor.has_role(Role::Moderator) {
+            return Err(ForumError::NotYourPost.into());
+        }
+        let thread = self.thread(post.thread).await?;
+        // Same rule as replying: locking a thread freezes it for everyone but moderators.
+        if thread.locked.is_some() && !editor.has_role(Role::Moderator) {
+            return Err(ForumError::Locked.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        let author = User::find_by_id(post.author)
+            .one(&db)
+            .await?
+            .map(|author| author.handle)
+            .unwrap_or_default();
+        let mut active: post::ActiveModel = post.into();
+        active.body = Set(body.to_string());
+        active.modified = Set(Some(now()));
+        let post = active.update(&db).await?;
+        bump_generation();
+        Self::index(&post, &thread, &author);
+        MentionUtil::new(self.db.clone())
+            .record_edit(
+                post.author,
+                &author,
+                body,
+                post.id,
+                &format!("\"{}\"", thread.name),
+            )
+            .await?;
+        Ok(post)
+    }
+
+    /// Searches posts. Besides plain words, `author:handle` and `forum:name` narrow the results.
+    /// Hits are checked against the database so deleted or moved content isn't shown stale.
+    pub async fn search(
+        &self,
+        text: &str,
+        forum: Option<i32>,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, anyhow::Error> {
+        let mut query = ForumQuery {
+            forum,
+            ..Default::default()
+        };
+        let mut words = Vec::new();
+        let mut forum_name = None;
+        for word in text.split_whitespace() {
+            if let Some(author) = word.strip_prefix("author:") {
+                query.author = Some(author.trim_start_matches('@').to_string());
+            } else if let Some(forum) = word.strip_prefix("forum:") {
+                forum_name = Some(forum.to_string());
+            } else {
+                words.push(word);
+            }
+        }
+        query.text = words.join(" ");
+        if let Some(forum_name) = forum_name {
+            let forum = self
+                .forum_tree()
+                .await?
+                .into_iter()
+                .find(|node| node.forum.name.eq_ignore_ascii_case(&forum_name))
+                .ok_or(ForumError::NoSuchForumName(forum_name))?;
+            query.forum = Some(forum.forum.id);
+        }
+        let hits = search_index(&query, limit)?;
+        let db = self.db.lock().await.to_owned();
+        let post_ids: Vec<i32> = hits.iter().map(|hit| hit.post_id).collect();
+        let posts: HashMap<i32, post::Model> = Post::find()
+            .filter(post::Column::Id.is_in(post_ids))
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|post| (post.id, post))
+            .collect();
+        let thread_ids: Vec<i32> = posts.values().map(|post| post.thread).collect();
+        let threads: HashMap<i32, thread::Model> = Thread::find()
+            .filter(thread::Column::Id.is_in(thread_ids))
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|thread| (thread.id, thread))
+            .collect();
+        let handles = Self::handles(&db, posts.values().map(|post| post.author).collect()).await?;
+        Ok(hits
+            .into_iter()
+            .filter_map(|mut hit| {
+                let post = posts.get(&hit.post_id)?;
+                let thread = threads.get(&post.thread)?;
+                if query.forum.map_or(false, |forum| forum != thread.forum) {
+                    return None;
+                }
+                hit.thread_id = thread.id;
+                hit.forum_id = thread.forum;
+                hit.title = thread.name.clone();
+                // The index only keeps a lowercased handle for matching.
+                if let Some(author) = handles.get(&post.author) {
+                    hit.author = author.clone();
+                }
+                Some(hit)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use chrono::Duration;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, ModelTrait, QueryFilter, QueryOrder, Set,
+    TransactionTrait,
+};
+use thiserror::Error;
+
+use crate::{
+    db::{
+        from_now,
+        gen::{
+            poll, poll_ballot, poll_choice, poll_option,
+            prelude::{Poll, PollBallot, PollChoice, PollOption},
+        },
+        now,
+    },
+    user::UserInfo,
+};
+
+use super::{bump_generation, ForumError, ForumUtil};
+
+static MAX_OPTIONS: usize = 20;
+
+#[derive(Debug, Error)]
+pub enum PollError {
+    #[error("Only the thread's author can add a poll")]
+    NotThreadAuthor,
+    #[error("This thread already has a poll")]
+    AlreadyHasPoll,
+    #[error("Polls need a question")]
+    EmptyQuestion,
+    #[error("Polls need at least two options")]
+    TooFewOptions,
+    #[error("Polls can have at most {0} options")]
+    TooManyOptions(usize),
+    #[error("Poll not found")]
+    NoSuchPoll,
+    #[error("This poll has closed")]
+    Closed,
+    #[error("Pick one option")]
+    SingleChoice,
+    #[error("That option isn't part of this poll")]
+    NoSuchOption,
+}
+
+/// A poll with the votes cast so far.
+#[derive(Debug, Clone)]
+pub struct PollTally {
+    pub poll: poll::Model,
+    /// Each option in display order, with how many voters picked it.
+    pub options: Vec<(poll_option::Model, usize)>,
+    pub voters: usize,
+    /// The options the reader picked.
+    pub own: HashSet<i32>,
+}
+
+impl PollTally {
+    pub fn is_closed(&self) -> bool {
+        matches!(&self.poll.closes, Some(closes) if *closes <= now())
+    }
+}
+
+impl ForumUtil {
+    /// Attaches a poll to a thread started by `author`. Blank options are dropped, and a poll
+    /// without a `duration` stays open.
+    pub async fn attach_poll(
+        &self,
+        author: &UserInfo,
+        thread_id: i32,
+        question: &str,
+        options: &[String],
+        multiple: bool,
+        duration: Option<Duration>,
+    ) -> Result<poll::Model, anyhow::Error> {
+        let author_id = author.id().ok_or(ForumError::NotRegistered)?;
+        let question = question.trim();
+        if question.is_empty() {
+            return Err(PollError::EmptyQuestion.into());
+        }
+        let options: Vec<&str> = options
+            .iter()
+            .map(|option| option.trim())
+            .filter(|option| !option.is_empty())
+            .collect();
+        if options.len() < 2 {
+            return Err(PollError::TooFewOptions.into());
+        }
+        if options.len() > MAX_OPTIONS {
+            return Err(PollError::TooManyOptions(MAX_OPTIONS).into());
+        }
+        let thread = self.thread(thread_id).await?;
+        if thread.author != author_id {
+            return Err(PollError::NotThreadAuthor.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        let txn = db.begin().await?;
+        if thread.find_related(Poll).one(&txn).await?.is_some() {
+            return Err(PollError::AlreadyHasPoll.into());
+        }
+        let poll = poll::ActiveModel {
+            thread_id: Set(thread_id),
+            question: Set(question.to_string()),
+            multiple: Set(multiple),
+            closes: Set(duration.map(from_now)),
+            created: Set(now()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        for (position, label) in options.into_iter().enumerate() {
+            poll_option::ActiveModel {
+                poll_id: Set(poll.id),
+                label: Set(label.to_string()),
+                position: Set(position as i32),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        bump_generation();
+        Ok(poll)
+    }
+
+    /// Returns the poll attached to a thread, if there is one, tallied for `reader`.
+    pub async fn poll(
+        &self,
+        reader: Option<&UserInfo>,
+        thread_id: i32,
+    ) -> Result<Option<PollTally>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let poll = match Poll::find()
+            .filter(poll::Column::ThreadId.eq(thread_id))
+            .one(&db)
+            .await?
+        {
+            Some(poll) => poll,
+            None => return Ok(None),
+        };
+        let options = poll
+            .find_related(PollOption)
+            .order_by_asc(poll_option::Column::Position)
+            .all(&db)
+            .await?;
+        let ballots = poll.find_related(PollBallot).all(&db).await?;
+        let choices = PollChoice::find()
+            .filter(poll_choice::Column::BallotId.is_in(ballots.iter().map(|ballot| ballot.id)))
+            .all(&db)
+            .await?;
+        let reader_id = reader.and_then(|reader| reader.id());
+        let own_ballot = ballots
+            .iter()
+            .find(|ballot| Some(ballot.user_id) == reader_id)
+            .map(|ballot| ballot.id);
+        let own = choices
+            .iter()
+            .filter(|choice| Some(choice.ballot_id) == own_ballot)
+            .map(|choice| choice.option_id)
+            .collect();
+        let options = options
+            .into_iter()
+            .map(|option| {
+                let votes = choices
+                    .iter()
+                    .filter(|choice| choice.option_id == option.id)
+                    .count();
+                (option, votes)
+            })
+            .collect();
+        Ok(Some(PollTally {
+            poll,
+            options,
+            voters: ballots.len(),
+            own,
+        }))
+    }
+
+    /// Replaces `user`'s ballot in a poll with `option_ids`. No options withdraws the vote.
+    pub async fn vote(
+        &self,
+        user: &UserInfo,
+        poll_id: i32,
+        option_ids: &[i32],
+    ) -> Result<(), anyhow::Error> {
+        let user_id = user.id().ok_or(ForumError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let poll = Poll::find_by_id(poll_id)
+            .one(&db)
+            .await?
+            .ok_or(PollError::NoSuchPoll)?;
+        if matches!(&poll.closes, Some(closes) if *closes <= now()) {
+            return Err(PollError::Closed.into());
+        }
+        if !poll.multiple && option_ids.len() > 1 {
+            return Err(PollError::SingleChoice.into());
+        }
+        let options: HashSet<i32> = poll
+            .find_related(PollOption)
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|option| option.id)
+            .collect();
+        if option_ids
+            .iter()
+            .any(|option_id| !options.contains(option_id))
+        {
+            return Err(PollError::NoSuchOption.into());
+        }
+        // Replacing a ballot takes several statements, and a failure halfway would lose the vote.
+        let txn = db.begin().await?;
+        let ballot = PollBallot::find()
+            .filter(poll_ballot::Column::PollId.eq(poll_id))
+            .filter(poll_ballot::Column::UserId.eq(user_id))
+            .one(&txn)
+            .await?;
+        if let Some(ballot) = ballot {
+            PollChoice::delete_many()
+                .filter(poll_choice::Column::BallotId.eq(ballot.id))
+                .exec(&txn)
+                .await?;
+            ballot.delete(&txn).await?;
+        }
+        if !option_ids.is_empty() {
+            // The unique index on (poll, user) turns a concurrent second ballot into an error.
+            let ballot = poll_ballot::ActiveModel {
+                poll_id: Set(poll_id),
+                user_id: Set(user_id),
+                created: Set(now()),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            let chosen: HashSet<&i32> = option_ids.iter().collect();
+            for option_id in chosen {
+                poll_choice::ActiveModel {
+                    ballot_id: Set(ballot.id),
+                    option_id: Set(*option_id),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+            }
+        }
+        txn.commit().await?;
+        bump_generation();
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, ModelTrait, QueryFilter, Set, TransactionTrait,
+};
+
+use crate::{
+    db::{
+        gen::{
+            prelude::{Post, Reaction},
+            reaction,
+        },
+        now,
+    },
+    settings::get_settings,
+    user::UserInfo,
+};
+
+use super::{bump_generation, ForumError, ForumUtil};
+
+pub static UPVOTE: &str = "+1";
+pub static DOWNVOTE: &str = "-1";
+
+/// How readers have reacted to one post.
+#[derive(Debug, Clone, Default)]
+pub struct PostReactions {
+    /// Counts per reaction kind, in the order they're offered. Kinds nobody used are left out.
+    pub counts: Vec<(String, usize)>,
+    /// The kinds the reader has left on the post themselves.
+    pub own: HashSet<String>,
+}
+
+impl PostReactions {
+    fn count(&self, kind: &str) -> usize {
+        self.counts
+            .iter()
+            .find(|(counted, _)| counted == kind)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    /// Up votes minus down votes, used to rank answers in Q&A forums.
+    pub fn score(&self) -> i64 {
+        self.count(UPVOTE) as i64 - self.count(DOWNVOTE) as i64
+    }
+}
+
+/// Every kind of reaction, votes first and then the configured ones.
+pub fn reaction_kinds() -> Vec<String> {
+    let mut kinds = vec![UPVOTE.to_string(), DOWNVOTE.to_string()];
+    for kind in get_settings().reactions {
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    kinds
+}
+
+impl ForumUtil {
+    /// Adds `user`'s `kind` reaction to a post, or takes it back if it's already there. A vote
+    /// replaces an opposite one. Returns whether the reaction is now present.
+    pub async fn toggle_reaction(
+        &self,
+        user: &UserInfo,
+        post_id: i32,
+        kind: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let user_id = user.id().ok_or(ForumError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let txn = db.begin().await?;
+        let existing = Reaction::find()
+            .filter(reaction::Column::UserId.eq(user_id))
+            .filter(reaction::Column::PostId.eq(post_id))
+            .filter(reaction::Column::Kind.eq(kind))
+            .one(&txn)
+            .await?;
+        let present = match existing {
+            Some(existing) => {
+                existing.delete(&txn).await?;
+                false
+            }
+            None => {
+                if !reaction_kinds().iter().any(|known| known == kind) {
+                    return Err(ForumError::NoSuchReaction(kind.to_string()).into());
+                }
+                let post = Post::find_by_id(post_id)
+                    .one(&txn)
+                    .await?
+                    .ok_or(ForumError::NoSuchPost)?;
+                let opposite = if kind == UPVOTE {
+                    Some(DOWNVOTE)
+                } else if kind == DOWNVOTE {
+                    Some(UPVOTE)
+                } else {
+                    None
+                };
+                if let Some(opposite) = opposite {
+                    if post.author == user_id {
+                        return Err(ForumError::OwnPost.into());
+                    }
+                    Reaction::delete_many()
+                        .filter(reaction::Column::UserId.eq(user_id))
+                        .filter(reaction::Column::PostId.eq(post_id))
+                        .filter(reaction::Column::Kind.eq(opposite))
+                        .exec(&txn)
+                        .await?;
+                }
+                reaction::ActiveModel {
+                    user_id: Set(user_id),
+                    post_id: Set(post_id),
+                    kind: Set(kind.to_string()),
+                    created: Set(now()),
+                    ..Default::default()
+                }
+                .insert(&txn)
+                .await?;
+                true
+            }
+        };
+        txn.commit().await?;
+        bump_generation();
+        Ok(present)
+    }
+
+    /// Tallies the reactions on `post_ids`. Posts nobody reacted to are left out.
+    pub async fn reactions(
+        &self,
+        reader: Option<&UserInfo>,
+        post_ids: Vec<i32>,
+    ) -> Result<HashMap<i32, PostReactions>, anyhow::Error> {
+        let reader_id = reader.and_then(|reader| reader.id());
+        let db = self.db.lock().await.to_owned();
+        let rows = Reaction::find()
+            .filter(reaction::Column::PostId.is_in(post_ids))
+            .all(&db)
+            .await?;
+        let mut counts: HashMap<i32, HashMap<String, usize>> = HashMap::new();
+        let mut own: HashMap<i32, HashSet<String>> = HashMap::new();
+        for row in rows {
+            if Some(row.user_id) == reader_id {
+                own.entry(row.post_id).or_default().insert(row.kind.clone());
+            }
+            *counts
+                .entry(row.post_id)
+                .or_default()
+                .entry(row.kind)
+                .or_default() += 1;
+        }
+        // Kinds dropped from the config since are still counted, after the current ones.
+        let kinds = reaction_kinds();
+        Ok(counts
+            .into_iter()
+            .map(|(post_id, mut by_kind)| {
+                let mut ordered: Vec<(String, usize)> = kinds
+                    .iter()
+                    .filter_map(|kind| by_kind.remove(kind).map(|count| (kind.clone(), count)))
+                    .collect();
+                let mut retired: Vec<(String, usize)> = by_kind.into_iter().collect();
+                retired.sort();
+                ordered.extend(retired);
+                let reactions = PostReactions {
+                    counts: ordered,
+                    own: own.remove(&post_id).unwrap_or_default(),
+                };
+                (post_id, reactions)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::fs::create_dir_all;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+
+use log::info;
+use sea_orm::{DatabaseConnection, EntityTrait};
+use tantivy::collector::TopDocs;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, INDEXED, STORED, STRING, TEXT};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, SnippetGenerator, Term};
+
+use crate::db::gen::{
+    post,
+    prelude::{Post, Thread, User},
+    thread,
+};
+
+lazy_static! {
+    static ref FORUM_INDEX: RwLock<Option<Arc<ForumIndex>>> = RwLock::new(None);
+}
+
+static POST_ID_FIELD: &str = "post_id";
+static THREAD_ID_FIELD: &str = "thread_id";
+static FORUM_ID_FIELD: &str = "forum_id";
+static AUTHOR_FIELD: &str = "author";
+static TITLE_FIELD: &str = "title";
+static BODY_FIELD: &str = "body";
+
+/// What to look for. Every part that is set must match.
+#[derive(Debug, Clone, Default)]
+pub struct ForumQuery {
+    pub text: String,
+    pub author: Option<String>,
+    pub forum: Option<i32>,
+}
+
+/// A matching post. `highlighted` holds byte ranges of `fragment` that matched the query.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub post_id: i32,
+    pub thread_id: i32,
+    pub forum_id: i32,
+    pub author: String,
+    pub title: String,
+    pub fragment: String,
+    pub highlighted: Vec<Range<usize>>,
+}
+
+struct ForumIndex {
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    post_id: Field,
+    thread_id: Field,
+    forum_id: Field,
+    author: Field,
+    title: Field,
+    body: Field,
+}
+
+impl ForumIndex {
+    fn schema() -> Schema {
+        let mut schema_builder = Schema::builder();
+        schema_builder.add_u64_field(POST_ID_FIELD, INDEXED | STORED);
+        schema_builder.add_u64_field(THREAD_ID_FIELD, INDEXED | STORED);
+        schema_builder.add_u64_field(FORUM_ID_FIELD, INDEXED | STORED);
+        schema_builder.add_text_field(AUTHOR_FIELD, STRING | STORED);
+        schema_builder.add_text_field(TITLE_FIELD, TEXT | STORED);
+        schema_builder.add_text_field(BODY_FIELD, TEXT | STORED);
+        schema_builder.build()
+    }
+
+    fn open(index: Index) -> Result<Self, anyhow::Error> {
+        let schema = index.schema();
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::OnCommit)
+            .try_into()?;
+        let writer = index.writer(15_000_000)?;
+        Ok(Self {
+            post_id: schema.get_field(POST_ID_FIELD).unwrap(),
+            thread_id: schema.get_field(THREAD_ID_FIELD).unwrap(),
+            forum_id: schema.get_field(FORUM_ID_FIELD).unwrap(),
+            author: schema.get_field(AUTHOR_FIELD).unwrap(),
+            title: schema.get_field(TITLE_FIELD).unwrap(),
+            body: schema.get_field(BODY_FIELD).unwrap(),
+            writer: Mutex::new(writer),
+            reader,
+        })
+    }
+
+    fn add(
+        &self,
+        writer: &mut IndexWriter,
+        post: &post::Model,
+        thread: &thread::Model,
+        author: &str,
+    ) -> Result<(), anyhow::Error> {
+        writer.delete_term(Term::from_field_u64(self.post_id, post.id as u64));
+        writer.add_document(doc!(
+            self.post_id => post.id as u64,
+            self.thread_id => thread.id as u64,
+            self.forum_id => thread.forum as u64,
+            self.author => author.to_lowercase(),
+            self.title => thread.name.clone(),
+            self.body => post.body.clone(),
+        ))?;
+        Ok(())
+    }
+
+    fn search(&self, query: &ForumQuery, limit: usize) -> Result<Vec<SearchHit>, anyhow::Error> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        // Split the way the default tokenizer does so every word has to appear somewhere.
+        for token in query
+            .text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+        {
+            let token = token.to_lowercase();
+            let either: Vec<(Occur, Box<dyn Query>)> = [self.title, self.body]
+                .into_iter()
+                .map(|field| {
+                    let term = Term::from_field_text(field, &token);
+                    (
+                        Occur::Should,
+                        Box::new(TermQuery::new(term, IndexRecordOption::WithFreqs))
+                            as Box<dyn Query>,
+                    )
+                })
+                .collect();
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(either))));
+        }
+        if let Some(author) = &query.author {
+            let term = Term::from_field_text(self.author, &author.to_lowercase());
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+        if let Some(forum) = query.forum {
+            let term = Term::from_field_u64(self.forum_id, forum as u64);
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(term, IndexRecordOption::Basic)),
+            ));
+        }
+        if clauses.is_empty() {
+            return Ok(Vec::new());
+        }
+        let query = BooleanQuery::new(clauses);
+        let searcher = self.reader.searcher();
+        let snippets = SnippetGenerator::create(&searcher, &query, self.body)?;
+        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        let mut hits = Vec::new();
+        for (_score, address) in top_docs {
+            let doc = searcher.doc(address)?;
+            let id = |field| {
+                doc.get_first(field)
+                    .and_then(|value| value.as_u64())
+                    .unwrap_or(0) as i32
+            };
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|value| value.as_text())
+                    .unwrap_or("")
+                    .to_string()
+            };
+            let snippet = snippets.snippet_from_doc(&doc);
+            let (fragment, highlighted) = if snippet.fragment().is_empty() {
+                // Nothing matched in the body, e.g. a title or author only search.
+                (text(self.body).chars().take(80).collect(), Vec::new())
+            } else {
+                (
+                    snippet.fragment().to_string(),
+                    snippet.highlighted().to_vec(),
+                )
+            };
+            hits.push(SearchHit {
+                post_id: id(self.post_id),
+                thread_id: id(self.thread_id),
+                forum_id: id(self.forum_id),
+                author: text(self.author),
+                title: text(self.title),
+                fragment,
+                highlighted,
+            });
+        }
+        Ok(hits)
+    }
+}
+
+/// Opens the forum search index, building it from the database if it doesn't exist yet.
+pub(crate) async fn open_forum_index<P: AsRef<Path>>(
+    index_path: P,
+    db: &DatabaseConnection,
+) -> Result<(), anyhow::Error> {
+    let index_path = index_path.as_ref();
+    let fresh = !index_path.join("meta.json").exists();
+    let index = if fresh {
+        create_dir_all(index_path)?;
+        Index::create_in_dir(index_path, ForumIndex::schema())?
+    } else {
+        Index::open_in_dir(index_path)?
+    };
+    let forum_index = ForumIndex::open(index)?;
+    if fresh {
+        let threads: HashMap<i32, thread::Model> = Thread::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|thread| (thread.id, thread))
+            .collect();
+        let handles: HashMap<i32, String> = User::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.handle))
+            .collect();
+        let posts = Post::find().all(db).await?;
+        let mut writer = forum_index.writer.lock().unwrap();
+        for post in posts.iter() {
+            if let (Some(thread), Some(author)) =
+                (threads.get(&post.thread), handles.get(&post.author))
+            {
+                forum_index.add(&mut writer, post, thread, author)?;
+            }
+        }
+        writer.commit()?;
+        info!("Indexed {} forum posts", posts.len());
+    }
+    *FORUM_INDEX.write().unwrap() = Some(Arc::new(forum_index));
+    Ok(())
+}
+
+fn get_index() -> Option<Arc<ForumIndex>> {
+    FORUM_INDEX.read().unwrap().clone()
+}
+
+/// Commits anything still pending and closes the index, so nothing is half written when the
+/// server exits. Posts written after this aren't indexed.
+pub(crate) fn close_forum_index() -> Result<(), anyhow::Error> {
+    let forum_index = FORUM_INDEX.write().unwrap().take();
+    if let Some(forum_index) = forum_index {
+        forum_index.writer.lock().unwrap().commit()?;
+    }
+    Ok(())
+}
+
+/// Adds posts from one thread to the index, replacing any earlier versions of them. Does nothing
+/// until the index has been opened.
+pub(crate) fn index_posts(
+    posts: &[post::Model],
+    thread: &thread::Model,
+    handles: &HashMap<i32, String>,
+) -> Result<(), anyhow::Error> {
+    let forum_index = match get_index() {
+        Some(forum_index) => forum_index,
+        None => return Ok(()),
+    };
+    let mut writer = forum_index.writer.lock().unwrap();
+    for post in posts {
+        let author = handles.get(&post.author).map(String::as_str).unwrap_or("");
+        forum_index.add(&mut writer, post, thread, author)?;
+    }
+    writer.commit()?;
+    Ok(())
+}
+
+pub(crate) fn search_index(
+    query: &ForumQuery,
+    limit: usize,
+) -> Result<Vec<SearchHit>, anyhow::Error> {
+    match get_index() {
+        Some(forum_index) => forum_index.search(query, limit),
+        None => Err(anyhow::anyhow!("Forum search isn't available yet")),
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait,
+    FromQueryResult, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+
+use crate::{
+    db::{
+        gen::{
+            post,
+            prelude::{Forum, Post, ReadMarker, Thread, User},
+            read_marker, thread,
+        },
+        now,
+    },
+    user::UserInfo,
+};
+
+use super::{forum_generation, ForumError, ForumUtil, ThreadSummary};
+
+lazy_static! {
+    static ref READ_GENERATIONS: Mutex<HashMap<i32, u64>> = Mutex::new(HashMap::new());
+}
+
+/// Like `forum_generation`, but also changes whenever `reader_id` marks something read. Only
+/// views showing that reader's unread counts need to watch it.
+pub fn unread_generation(reader_id: Option<i32>) -> u64 {
+    let read = reader_id
+        .and_then(|reader_id| READ_GENERATIONS.lock().unwrap().get(&reader_id).copied())
+        .unwrap_or(0);
+    forum_generation() + read
+}
+
+fn bump_read_generation(reader_id: i32) {
+    *READ_GENERATIONS
+        .lock()
+        .unwrap()
+        .entry(reader_id)
+        .or_insert(0) += 1;
+}
+
+/// Just enough of a post to count it, without loading its body.
+#[derive(Debug, FromQueryResult)]
+pub(super) struct PostRef {
+    pub(super) id: i32,
+    pub(super) thread: i32,
+    pub(super) created: Option<String>,
+}
+
+/// A thread that changed since the reader's last visit, with the name of its forum.
+#[derive(Debug, Clone)]
+pub struct NewActivity {
+    pub forum: String,
+    pub summary: ThreadSummary,
+}
+
+impl ForumUtil {
+    pub(super) async fn post_refs(
+        db: &DatabaseConnection,
+        thread_ids: Vec<i32>,
+    ) -> Result<Vec<PostRef>, anyhow::Error> {
+        Ok(Post::find()
+            .select_only()
+            .column(post::Column::Id)
+            .column(post::Column::Thread)
+            .column(post::Column::Created)
+            .filter(post::Column::Thread.is_in(thread_ids))
+            .into_model::<PostRef>()
+            .all(db)
+            .await?)
+    }
+
+    /// Maps thread ids to the last post `user_id` has read in them.
+    pub(super) async fn read_markers(
+        db: &DatabaseConnection,
+        user_id: i32,
+        thread_ids: Vec<i32>,
+    ) -> Result<HashMap<i32, i32>, anyhow::Error> {
+        Ok(ReadMarker::find()
+            .filter(read_marker::Column::UserId.eq(user_id))
+            .filter(read_marker::Column::ThreadId.is_in(thread_ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|marker| (marker.thread_id, marker.last_read_post))
+            .collect())
+    }
+
+    /// Records that `reader` has read `thread_id` up to `post_id`. Markers only move forward.
+    pub async fn mark_read(
+        &self,
+        reader: &UserInfo,
+        thread_id: i32,
+        post_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        let reader_id = reader.id().ok_or(ForumError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let marker = ReadMarker::find()
+            .filter(read_marker::Column::UserId.eq(reader_id))
+            .filter(read_marker::Column::ThreadId.eq(thread_id))
+            .one(&db)
+            .await?;
+        match marker {
+            Some(marker) if marker.last_read_post >= post_id => return Ok(()),
+            Some(marker) => {
+                let mut active: read_marker::ActiveModel = marker.into();
+                active.last_read_post = Set(post_id);
+                active.updated = Set(now());
+                active.update(&db).await?;
+            }
+            None => {
+                read_marker::ActiveModel {
+                    user_id: Set(reader_id),
+                    thread_id: Set(thread_id),
+                    last_read_post: Set(post_id),
+                    updated: Set(now()),
+                    ..Default::default()
+                }
+                .insert(&db)
+                .await?;
+            }
+        }
+        // This reader's unread counts are now stale, but nobody else's are.
+        bump_read_generation(reader_id);
+        Ok(())
+    }
+
+    /// Returns the oldest post in a thread that `reader` hasn't read yet.
+    pub async fn first_unread(
+        &self,
+        reader: &UserInfo,
+        thread_id: i32,
+    ) -> Result<Option<i32>, anyhow::Error> {
+        let reader_id = match reader.id() {
+            Some(reader_id) => reader_id,
+            None => return Ok(None),
+        };
+        let db = self.db.lock().await.to_owned();
+        let last_read = Self::read_markers(&db, reader_id, vec![thread_id])
+            .await?
+            .get(&thread_id)
+            .copied()
+            .unwrap_or(0);
+        Ok(Post::find()
+            .filter(post::Column::Thread.eq(thread_id))
+            .filter(post::Column::Id.gt(last_read))
+            .order_by_asc(post::Column::Id)
+            .one(&db)
+            .await?
+            .map(|post| post.id))
+    }
+
+    /// Counts unread posts per forum for `reader`. Forums without unread posts are left out.
+    pub async fn unread_by_forum(
+        &self,
+        reader: &UserInfo,
+    ) -> Result<HashMap<i32, usize>, anyhow::Error> {
+        let reader_id = match reader.id() {
+            Some(reader_id) => reader_id,
+            None => return Ok(HashMap::new()),
+        };
+        let db = self.db.lock().await.to_owned();
+        Ok(Post::find()
+            .select_only()
+            .column_as(thread::Column::Forum, "forum")
+            .column_as(post::Column::Id.count(), "unread")
+            .inner_join(Thread)
+            .filter(Expr::cust_with_values(
+                "post.id > COALESCE((SELECT last_read_post FROM read_marker \
+                 WHERE read_marker.user_id = ? AND read_marker.thread_id = post.thread), 0)",
+                [reader_id],
+            ))
+            .group_by(thread::Column::Forum)
+            .into_model::<ForumUnread>()
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|count| (count.forum, count.unread as usize))
+            .collect())
+    }
+
+    /// Returns threads with posts since `reader`'s previous login that they haven't read yet,
+    /// most recently active first. On a first visit everything unread counts as new.
+    pub async fn new_activity(&self, reader: &UserInfo) -> Result<Vec<NewActivity>, anyhow::Error> {
+        let reader_id = reader.id().ok_or(ForumError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let since = User::find_by_id(reader_id)
+            .one(&db)
+            .await?
+            .and_then(|user| user.previous_login);
+        let mut active_threads = Post::find().select_only().column(post::Column::Thread);
+        if let Some(since) = &since {
+            active_threads = active_threads.filter(post::Column::Created.gt(since.clone()));
+        }
+        let mut thread_ids: Vec<i32> = active_threads
+            .into_model::<ThreadRef>()
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|thread| thread.thread)
+            .collect();
+        thread_ids.sort_unstable();
+        thread_ids.dedup();
+        let threads = Thread::find()
+            .filter(thread::Column::Id.is_in(thread_ids))
+            .all(&db)
+            .await?;
+        let forums: HashMap<i32, String> = Forum::find()
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|forum| (forum.id, forum.name))
+            .collect();
+        let mut activity: Vec<NewActivity> = Self::summarize(&db, Some(reader), threads)
+            .await?
+            .into_iter()
+            .filter(|summary| summary.unread > 0)
+            .map(|summary| NewActivity {
+                forum: forums
+                    .get(&summary.thread.forum)
+                    .cloned()
+                    .unwrap_or_default(),
+                summary,
+            })
+            .collect();
+        activity.sort_by(|a, b| b.summary.activity().cmp(&a.summary.activity()));
+        Ok(activity)
+    }
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ForumUnread {
+    forum: i32,
+    unread: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct ThreadRef {
+    thread: i32,
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{
+        gen::{
+            message,
+            prelude::{Message, User},
+            user,
+        },
+        now,
+    },
+    notification::{notify_user, Notification},
+    user::UserInfo,
+};
+
+#[derive(Debug, Error)]
+pub enum MailError {
+    #[error("Register a profile to use mail")]
+    NotRegistered,
+    #[error("Nobody is called {0}")]
+    NoSuchRecipient(String),
+    #[error("Message not found")]
+    NoSuchMessage,
+    #[error("Messages need a body")]
+    EmptyBody,
+}
+
+/// A message with the handles of both parties resolved.
+#[derive(Debug, Clone)]
+pub struct MailEntry {
+    pub message: message::Model,
+    pub sender: String,
+    pub recipient: String,
+}
+
+pub struct MailUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl MailUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> MailUtil {
+        MailUtil { db }
+    }
+
+    async fn resolve(
+        db: &DatabaseConnection,
+        messages: Vec<message::Model>,
+    ) -> Result<Vec<MailEntry>, anyhow::Error> {
+        let mut ids: Vec<i32> = messages
+            .iter()
+            .flat_map(|message| [message.sender, message.recipient])
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let handles: HashMap<i32, String> = User::find()
+            .filter(user::Column::Id.is_in(ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.handle))
+            .collect();
+        Ok(messages
+            .into_iter()
+            .map(|message| MailEntry {
+                sender: handles.get(&message.sender).cloned().unwrap_or_default(),
+                recipient: handles.get(&message.recipient).cloned().unwrap_or_default(),
+                message,
+            })
+            .collect())
+    }
+
+    /// Sends a message. Replies are attached to the root of the conversation they answer.
+    pub async fn send(
+        &self,
+        sender: &UserInfo,
+        recipient: &str,
+        subject: &str,
+        body: &str,
+        reply_to: Option<i32>,
+    ) -> Result<message::Model, anyhow::Error> {
+        let sender_id = sender.id().ok_or(MailError::NotRegistered)?;
+        if body.trim().is_empty() {
+            return Err(MailError::EmptyBody.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        let recipient = User::find()
+            .filter(user::Column::Handle.eq(recipient.trim()))
+            .one(&db)
+            .await?
+            .ok_or_else(|| MailError::NoSuchRecipient(recipient.trim().to_string()))?;
+        let parent = match reply_to {
+            Some(reply_to) => {
+                // Other people's mail looks the same as mail that doesn't exist.
+                let original = Message::find_by_id(reply_to)
+                    .one(&db)
+                    .await?
+                    .filter(|original| {
+                        original.sender == sender_id || original.recipient == sender_id
+                    })
+                    .ok_or(MailError::NoSuchMessage)?;
+                Some(original.parent.unwrap_or(original.id))
+            }
+            None => None,
+        };
+        let subject = subject.trim();
+        let message = message::ActiveModel {
+            sender: Set(sender_id),
+            recipient: Set(recipient.id),
+            subject: Set(if subject.is_empty() {
+                "(no subject)".to_string()
+            } else {
+                subject.to_string()
+            }),
+            body: Set(body.to_string()),
+            created: Set(now()),
+            read: Set(false),
+            parent: Set(parent),
+            sender_deleted: Set(false),
+            recipient_deleted: Set(false),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?;
+        notify_user(
+            recipient.id,
+            Notification::Mail {
+                from: sender.handle.clone(),
+                subject: message.subject.clone(),
+            },
+        );
+        Ok(message)
+    }
+
+    pub async fn inbox(&self, user: &UserInfo) -> Result<Vec<MailEntry>, anyhow::Error> {
+        let user_id = user.id().ok_or(MailError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let messages = Message::find()
+            .filter(message::Column::Recipient.eq(user_id))
+            .filter(message::Column::RecipientDeleted.eq(false))
+            .order_by_desc(message::Column::Created)
+            .all(&db)
+            .await?;
+        Self::resolve(&db, messages).await
+    }
+
+    pub async fn outbox(&self, user: &UserInfo) -> Result<Vec<MailEntry>, anyhow::Error> {
+        let user_id = user.id().ok_or(MailError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let messages = Message::find()
+            .filter(message::Column::Sender.eq(user_id))
+            .filter(message::Column::SenderDeleted.eq(false))
+            .order_by_desc(message::Column::Created)
+            .all(&db)
+            .await?;
+        Self::resolve(&db, messages).await
+    }
+
+    pub async fn unread_count(&self, user: &UserInfo) -> Result<u64, anyhow::Error> {
+        let user_id = user.id().ok_or(MailError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        Ok(Message::find()
+            .filter(message::Column::Recipient.eq(user_id))
+            .filter(message::Column::RecipientDeleted.eq(false))
+            .filter(message::Column::Read.eq(false))
+            .count(&db)
+            .await?)
+    }
+
+    /// Returns every message in the conversation containing `message_id` that `user` can still
+    /// see, oldest first, and marks the ones addressed to them as read.
+    pub async fn conversation(
+        &self,
+        user: &UserInfo,
+        message_id: i32,
+    ) -> Result<Vec<MailEntry>, anyhow::Error> {
+        let user_id = user.id().ok_or(MailError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let message = Message::find_by_id(message_id)
+            .one(&db)
+            .await?
+            .ok_or(MailError::NoSuchMessage)?;
+        let root = message.parent.unwrap_or(message.id);
+        let visible = Condition::any()
+            .add(
+                Condition::all()
+                    .add(message::Column::Sender.eq(user_id))
+                    .add(message::Column::SenderDeleted.eq(false)),
+            )
+            .add(
+                Condition::all()
+                    .add(message::Column::Recipient.eq(user_id))
+                    .add(message::Column::RecipientDeleted.eq(false)),
+            );
+        let messages = Message::find()
+            .filter(
+                Condition::any()
+                    .add(message::Column::Id.eq(root))
+                    .add(message::Column::Parent.eq(root)),
+            )
+            .filter(visible)
+            .order_by_asc(message::Column::Created)
+            .order_by_asc(message::Column::Id)
+            .all(&db)
+            .await?;
+        for message in messages.iter() {
+            if message.recipient == user_id && !message.read {
+                let mut active: message::ActiveModel = message.clone().into();
+                active.read = Set(true);
+                active.update(&db).await?;
+            }
+        }
+        Self::resolve(&db, messages).await
+    }
+
+    /// Deletes a message from `user`'s side only. The other party keeps their copy.
+    pub async fn delete(&self, user: &UserInfo, message_id: i32) -> Result<(), anyhow::Error> {
+        let user_id = user.id().ok_or(MailError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let message = Message::find_by_id(message_id)
+            .one(&db)
+            .await?
+            .ok_or(MailError::NoSuchMessage)?;
+        let is_sender = message.sender == user_id;
+        let is_recipient = message.recipient == user_id;
+        if !is_sender && !is_recipient {
+            return Err(MailError::NoSuchMessage.into());
+        }
+        let mut active: message::ActiveModel = message.into();
+        if is_sender {
+            active.sender_deleted = Set(true);
+        }
+        if is_recipient {
+            active.recipient_deleted = Set(true);
+        }
+        active.update(&db).await?;
+        Ok(())
+    }
+}
@@ -1,22 +1,46 @@
 use std::{
-    fs::{read_dir, File},
+    fs::{read_dir, remove_dir_all, File},
     io::Read,
     sync::Arc,
+    time::Duration,
 };
 
 use bbs::BbsApp;
 use config::Config;
-use log::info;
+use forum::search::{close_forum_index, open_forum_index};
+use log::{error, info, warn};
 use migrator::Migrator;
 use sea_orm::{Database, DatabaseConnection};
 use sea_orm_migration::MigratorTrait;
+use session::{begin_shutdown, wait_for_sessions};
+use settings::{get_settings, set_settings, Settings};
 use ssh_ui::{russh_keys::decode_secret_key, AppServer};
-use tokio::spawn;
+use tokio::{
+    pin, select,
+    signal::{
+        ctrl_c,
+        unix::{signal, SignalKind},
+    },
+    spawn,
+};
 use ui::library::{push_library, Library};
 
+pub(crate) mod admin;
+pub(crate) mod audit;
 pub(crate) mod bbs;
 pub(crate) mod db;
+pub(crate) mod door;
+pub(crate) mod draft;
+pub(crate) mod forum;
+pub(crate) mod mail;
+pub(crate) mod mention;
 pub(crate) mod migrator;
+pub(crate) mod moderation;
+pub(crate) mod notification;
+pub(crate) mod profile;
+pub(crate) mod session;
+pub(crate) mod settings;
+pub(crate) mod throttle;
 pub(crate) mod ui;
 pub(crate) mod user;
 
@@ -25,6 +49,9 @@ extern crate tantivy;
 #[macro_use]
 extern crate lazy_static;
 
+/// How long past the shutdown deadline to wait for sessions to write what they have.
+const SHUTDOWN_FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
 async fn setup_db(db_url: &str, reset: bool) -> Result<DatabaseConnection, anyhow::Error> {
     let db = Database::connect(db_url).await?;
     if reset {
@@ -35,6 +62,14 @@ async fn setup_db(db_url: &str, reset: bool) -> Result<DatabaseConnection, anyho
     Ok(db)
 }
 
+async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM.");
+    select! {
+        _ = ctrl_c() => info!("Received SIGINT"),
+        _ = terminate.recv() => info!("Received SIGTERM"),
+    }
+}
+
 #[tokio::main]
 async fn main() {
     env_logger::init();
@@ -56,18 +91,28 @@ async fn main() {
         |port| port as u16,
     );
     let reset = settings.get_bool("unsafe_db_reset").unwrap_or(false);
+    set_settings(Settings::from_config(&settings));
     info!("Loading database from URL '{}'", &db_url);
     let db = setup_db(&db_url, reset)
         .await
         .expect("Failed to load database.");
+    if reset {
+        // Post ids start over after a reset, so the old index would point at the wrong posts.
+        let _ = remove_dir_all(get_settings().forum_index_path);
+    }
+    open_forum_index(get_settings().forum_index_path, &db)
+        .await
+        .expect("Failed to open forum search index.");
 
-    let library_path = settings
-        .get_string("library_path")
-        .unwrap_or("./library.zim".into());
+    let library_settings = get_settings();
     spawn(async {
-        let lib = Library::open::<String>("library", library_path, "_search_index".into())
-            .await
-            .expect("Failed to open library");
+        let lib = Library::open::<String>(
+            "library",
+            library_settings.library_path,
+            library_settings.library_index_path,
+        )
+        .await
+        .expect("Failed to open library");
         println!("Opened library");
         push_library(lib);
     });
@@ -95,5 +140,29 @@ async fn main() {
         .filter_map(Result::ok)
         .collect::<Vec<_>>();
     info!("Starting server...");
-    server.run(&keys, Arc::new(bbs_app)).await.unwrap();
+    let run = server.run(&keys, Arc::new(bbs_app));
+    pin!(run);
+    select! {
+        result = &mut run => {
+            result.unwrap();
+            return;
+        }
+        _ = shutdown_signal() => {}
+    }
+
+    // Keep serving while callers wrap up. New ones are turned away by the app.
+    let grace = get_settings().shutdown_grace;
+    info!("Shutting down in {} seconds", grace.as_secs());
+    let deadline = begin_shutdown(grace);
+    select! {
+        result = &mut run => result.unwrap(),
+        _ = wait_for_sessions(deadline + SHUTDOWN_FLUSH_TIMEOUT) => {}
+    }
+    if let Err(err) = close_forum_index() {
+        error!("Failed to commit forum search index: {}", err);
+    }
+    if Library::is_reindexing() {
+        warn!("Library reindex was interrupted, it will start over next time");
+    }
+    info!("Shut down");
 }
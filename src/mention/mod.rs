@@ -0,0 +1,346 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use regex::Regex;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait, PaginatorTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::{
+        gen::{
+            mention,
+            prelude::{Mention, User},
+            user,
+        },
+        now,
+    },
+    notification::{notify_user, Notification},
+    user::UserInfo,
+};
+
+lazy_static! {
+    /// Matches the characters `is_valid_handle` allows, so every handle can be mentioned.
+    static ref MENTION_REGEX: Regex = Regex::new(r"@([A-Za-z0-9_\-]+)").unwrap();
+}
+
+static EXCERPT_LENGTH: usize = 80;
+
+/// A `@handle` token found in a piece of text, as a byte range including the `@`.
+#[derive(Debug, Clone)]
+pub struct MentionToken {
+    pub start: usize,
+    pub end: usize,
+    pub handle: String,
+}
+
+/// Finds `@handle` tokens, skipping ones glued to a preceding word such as email addresses.
+pub fn find_mentions(text: &str) -> Vec<MentionToken> {
+    MENTION_REGEX
+        .captures_iter(text)
+        .filter_map(|capture| {
+            let whole = capture.get(0)?;
+            let preceding = text[..whole.start()].chars().last();
+            if preceding.map_or(false, |c| c.is_alphanumeric() || c == '@') {
+                return None;
+            }
+            Some(MentionToken {
+                start: whole.start(),
+                end: whole.end(),
+                handle: capture.get(1)?.as_str().to_string(),
+            })
+        })
+        .collect()
+}
+
+fn excerpt(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() > EXCERPT_LENGTH {
+        let truncated: String = line.chars().take(EXCERPT_LENGTH - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        line
+    }
+}
+
+/// A mention of the current user with the author's handle resolved.
+#[derive(Debug, Clone)]
+pub struct MentionEntry {
+    pub mention: mention::Model,
+    pub author: String,
+}
+
+pub struct MentionUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl MentionUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> MentionUtil {
+        MentionUtil { db }
+    }
+
+    /// Returns which of `handles` belong to registered users.
+    pub async fn resolve_handles(
+        &self,
+        handles: impl IntoIterator<Item = String>,
+    ) -> Result<HashSet<String>, anyhow::Error> {
+        let handles: Vec<String> = handles.into_iter().collect();
+        if handles.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let db = self.db.lock().await.to_owned();
+        Ok(User::find()
+            .filter(user::Column::Handle.is_in(handles))
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|user| user.handle)
+            .collect())
+    }
+
+    /// Looks up the registered users mentioned in `text`.
+    async fn mentioned_users(
+        db: &DatabaseConnection,
+        text: &str,
+    ) -> Result<Vec<user::Model>, anyhow::Error> {
+        let mut handles: Vec<String> = find_mentions(text)
+            .into_iter()
+            .map(|token| token.handle)
+            .collect();
+        handles.sort();
+        handles.dedup();
+        if handles.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(User::find()
+            .filter(user::Column::Handle.is_in(handles))
+            .all(db)
+            .await?)
+    }
+
+    async fn insert(
+        db: &DatabaseConnection,
+        user_id: i32,
+        author_id: i32,
+        author_handle: &str,
+        post: Option<i32>,
+        context: &str,
+        excerpt: &str,
+    ) -> Result<(), anyhow::Error> {
+        mention::ActiveModel {
+            user_id: Set(user_id),
+            author: Set(author_id),
+            post: Set(post),
+            context: Set(context.to_string()),
+            excerpt: Set(excerpt.to_string()),
+            created: Set(now()),
+            seen: Set(false),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        notify_user(
+            user_id,
+            Notification::Mention {
+                from: author_handle.to_string(),
+                context: context.to_string(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Records and announces every resolvable mention in `text`. `context` describes where the
+    /// text was written, e.g. `chat` or a thread title. Returns the handles that resolved.
+    pub async fn record(
+        &self,
+        author: &UserInfo,
+        text: &str,
+        post: Option<i32>,
+        context: &str,
+    ) -> Result<HashSet<String>, anyhow::Error> {
+        let author_id = match author.id() {
+            Some(author_id) => author_id,
+            None => return Ok(HashSet::new()),
+        };
+        let db = self.db.lock().await.to_owned();
+        let users = Self::mentioned_users(&db, text).await?;
+        let excerpt = excerpt(text);
+        for user in users.iter().filter(|user| user.id != author_id) {
+            Self::insert(
+                &db,
+                user.id,
+                author_id,
+                &author.handle,
+                post,
+                context,
+                &excerpt,
+            )
+            .await?;
+        }
+        Ok(users.into_iter().map(|user| user.handle).collect())
+    }
+
+    /// Brings the mentions recorded for an edited post in line with its new `text`. Users who are
+    /// still mentioned keep their entry with a fresh excerpt, users no longer mentioned lose it,
+    /// and only newly mentioned users are notified.
+    pub async fn record_edit(
+        &self,
+        author_id: i32,
+        author_handle: &str,
+        text: &str,
+        post_id: i32,
+        context: &str,
+    ) -> Result<(), anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let previous = Mention::find()
+            .filter(mention::Column::Post.eq(post_id))
+            .all(&db)
+            .await?;
+        let users: Vec<user::Model> = Self::mentioned_users(&db, text)
+            .await?
+            .into_iter()
+            .filter(|user| user.id != author_id)
+            .collect();
+        let excerpt = excerpt(text);
+        for mention in previous.iter() {
+            if users.iter().any(|user| user.id == mention.user_id) {
+                let mut active: mention::ActiveModel = mention.clone().into();
+                active.excerpt = Set(excerpt.clone());
+                active.update(&db).await?;
+            } else {
+                mention.clone().delete(&db).await?;
+            }
+        }
+        for user in users
+            .iter()
+            .filter(|user| !previous.iter().any(|mention| mention.user_id == user.id))
+        {
+            Self::insert(
+                &db,
+                user.id,
+                author_id,
+                author_handle,
+                Some(post_id),
+                context,
+                &excerpt,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn mentions(&self, user: &UserInfo) -> Result<Vec<MentionEntry>, anyhow::Error> {
+        let user_id = match user.id() {
+            Some(user_id) => user_id,
+            None => return Ok(Vec::new()),
+        };
+        let db = self.db.lock().await.to_owned();
+        let mentions = Mention::find()
+            .filter(mention::Column::UserId.eq(user_id))
+            .order_by_desc(mention::Column::Created)
+            .order_by_desc(mention::Column::Id)
+            .all(&db)
+            .await?;
+        let author_ids: Vec<i32> = mentions.iter().map(|mention| mention.author).collect();
+        let authors = User::find()
+            .filter(user::Column::Id.is_in(author_ids))
+            .all(&db)
+            .await?;
+        Ok(mentions
+            .into_iter()
+            .map(|mention| MentionEntry {
+                author: authors
+                    .iter()
+                    .find(|author| author.id == mention.author)
+                    .map(|author| author.handle.clone())
+                    .unwrap_or_default(),
+                mention,
+            })
+            .collect())
+    }
+
+    pub async fn unseen_count(&self, user: &UserInfo) -> Result<u64, anyhow::Error> {
+        let user_id = match user.id() {
+            Some(user_id) => user_id,
+            None => return Ok(0),
+        };
+        let db = self.db.lock().await.to_owned();
+        Ok(Mention::find()
+            .filter(mention::Column::UserId.eq(user_id))
+            .filter(mention::Column::Seen.eq(false))
+            .count(&db)
+            .await?)
+    }
+
+    pub async fn mark_seen(&self, user: &UserInfo) -> Result<(), anyhow::Error> {
+        let user_id = match user.id() {
+            Some(user_id) => user_id,
+            None => return Ok(()),
+        };
+        let db = self.db.lock().await.to_owned();
+        let unseen = Mention::find()
+            .filter(mention::Column::UserId.eq(user_id))
+            .filter(mention::Column::Seen.eq(false))
+            .all(&db)
+            .await?;
+        for mention in unseen {
+            let mut active: mention::ActiveModel = mention.into();
+            active.seen = Set(true);
+            active.update(&db).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_mentions;
+    use crate::user::is_valid_handle;
+
+    fn handles(text: &str) -> Vec<String> {
+        find_mentions(text)
+            .into_iter()
+            .map(|token| token.handle)
+            .collect()
+    }
+
+    #[test]
+    fn finds_mentions() {
+        let cases: &[(&str, &[&str])] = &[
+            ("@alice", &["alice"]),
+            ("hi @alice and @bob", &["alice", "bob"]),
+            ("(@alice), @bob. @carol!", &["alice", "bob", "carol"]),
+            ("@a-b_c9", &["a-b_c9"]),
+            ("@alice's post", &["alice"]),
+            ("mail bob@example.com", &[]),
+            ("café@bob", &[]),
+            ("@@alice", &[]),
+            ("@ alice", &[]),
+            ("no mentions here", &[]),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(handles(text), *expected, "in {:?}", text);
+        }
+    }
+
+    #[test]
+    fn token_ranges_cover_the_at_sign() {
+        let text = "ping @alice now";
+        let tokens = find_mentions(text);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(&text[tokens[0].start..tokens[0].end], "@alice");
+    }
+
+    #[test]
+    fn every_valid_handle_can_be_mentioned() {
+        for handle in ["alice", "Bob", "x", "a_b", "a-b", "007", "-_-"] {
+            assert!(is_valid_handle(handle), "{:?}", handle);
+            assert_eq!(handles(&format!("hey @{}", handle)), [handle]);
+        }
+        for handle in ["", "two words", "dot.ted", "émile", "at@sign"] {
+            assert!(!is_valid_handle(handle), "{:?}", handle);
+        }
+    }
+}
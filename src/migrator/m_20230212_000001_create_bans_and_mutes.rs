@@ -0,0 +1,116 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230212_000001_create_bans_and_mutes"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Ban::Table)
+                    .col(
+                        ColumnDef::new(Ban::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Ban::UserId).integer())
+                    .col(ColumnDef::new(Ban::Fingerprint).string())
+                    .col(ColumnDef::new(Ban::Address).string())
+                    .col(ColumnDef::new(Ban::Reason).string().not_null())
+                    .col(ColumnDef::new(Ban::Created).date_time().not_null())
+                    .col(ColumnDef::new(Ban::Expires).date_time())
+                    .col(ColumnDef::new(Ban::CreatedBy).integer())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Ban::Table, Ban::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Ban::Table, Ban::CreatedBy)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(Mute::Table)
+                    .col(
+                        ColumnDef::new(Mute::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Mute::UserId).integer().not_null())
+                    .col(ColumnDef::new(Mute::Reason).string().not_null())
+                    .col(ColumnDef::new(Mute::Created).date_time().not_null())
+                    .col(ColumnDef::new(Mute::Expires).date_time())
+                    .col(ColumnDef::new(Mute::CreatedBy).integer())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Mute::Table, Mute::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Mute::Table, Mute::CreatedBy)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Mute::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Ban::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Ban {
+    Table,
+    Id,
+    UserId,
+    Fingerprint,
+    Address,
+    Reason,
+    Created,
+    Expires,
+    CreatedBy,
+}
+
+#[derive(Iden)]
+pub enum Mute {
+    Table,
+    Id,
+    UserId,
+    Reason,
+    Created,
+    Expires,
+    CreatedBy,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,97 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230219_000001_create_messages"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Message::Table)
+                    .col(
+                        ColumnDef::new(Message::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Message::Sender).integer().not_null())
+                    .col(ColumnDef::new(Message::Recipient).integer().not_null())
+                    .col(ColumnDef::new(Message::Subject).string().not_null())
+                    .col(ColumnDef::new(Message::Body).text().not_null())
+                    .col(ColumnDef::new(Message::Created).date_time().not_null())
+                    .col(
+                        ColumnDef::new(Message::Read)
+                            .boolean()
+                            .default(false)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Message::Parent).integer())
+                    .col(
+                        ColumnDef::new(Message::SenderDeleted)
+                            .boolean()
+                            .default(false)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Message::RecipientDeleted)
+                            .boolean()
+                            .default(false)
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Message::Table, Message::Sender)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Message::Table, Message::Recipient)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Message::Table, Message::Parent)
+                            .to(Message::Table, Message::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Message::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Message {
+    Table,
+    Id,
+    Sender,
+    Recipient,
+    Subject,
+    Body,
+    Created,
+    Read,
+    Parent,
+    SenderDeleted,
+    RecipientDeleted,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
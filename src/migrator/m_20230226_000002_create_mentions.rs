@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230226_000002_create_mentions"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Mention::Table)
+                    .col(
+                        ColumnDef::new(Mention::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Mention::UserId).integer().not_null())
+                    .col(ColumnDef::new(Mention::Author).integer().not_null())
+                    .col(ColumnDef::new(Mention::Post).integer())
+                    .col(ColumnDef::new(Mention::Context).string().not_null())
+                    .col(ColumnDef::new(Mention::Excerpt).string().not_null())
+                    .col(ColumnDef::new(Mention::Created).date_time().not_null())
+                    .col(
+                        ColumnDef::new(Mention::Seen)
+                            .boolean()
+                            .default(false)
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Mention::Table, Mention::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Mention::Table, Mention::Author)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Mention::Table, Mention::Post)
+                            .to(Post::Table, Post::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Mention::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Mention {
+    Table,
+    Id,
+    UserId,
+    Author,
+    Post,
+    Context,
+    Excerpt,
+    Created,
+    Seen,
+}
+
+#[derive(Iden)]
+pub enum Post {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,122 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230305_000001_create_read_markers"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ReadMarker::Table)
+                    .col(
+                        ColumnDef::new(ReadMarker::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ReadMarker::UserId).integer().not_null())
+                    .col(ColumnDef::new(ReadMarker::ThreadId).integer().not_null())
+                    .col(
+                        ColumnDef::new(ReadMarker::LastReadPost)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ReadMarker::Updated).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(ReadMarker::Table, ReadMarker::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(ReadMarker::Table, ReadMarker::ThreadId)
+                            .to(Thread::Table, Thread::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-read_marker-user_id-thread_id")
+                    .table(ReadMarker::Table)
+                    .col(ReadMarker::UserId)
+                    .col(ReadMarker::ThreadId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(User::LastLogin).date_time())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .add_column(ColumnDef::new(User::PreviousLogin).date_time())
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::PreviousLogin)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(User::Table)
+                    .drop_column(User::LastLogin)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ReadMarker::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum ReadMarker {
+    Table,
+    Id,
+    UserId,
+    ThreadId,
+    LastReadPost,
+    Updated,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+    LastLogin,
+    PreviousLogin,
+}
+
+#[derive(Iden)]
+pub enum Thread {
+    Table,
+    Id,
+}
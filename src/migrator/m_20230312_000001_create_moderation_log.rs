@@ -0,0 +1,68 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230312_000001_create_moderation_log"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // `thread` is deliberately not a foreign key: merged threads are deleted, but their log
+        // entries should stay.
+        manager
+            .create_table(
+                Table::create()
+                    .table(ModerationLog::Table)
+                    .col(
+                        ColumnDef::new(ModerationLog::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ModerationLog::Actor).integer().not_null())
+                    .col(ColumnDef::new(ModerationLog::Action).string().not_null())
+                    .col(ColumnDef::new(ModerationLog::Thread).integer())
+                    .col(ColumnDef::new(ModerationLog::Detail).string().not_null())
+                    .col(
+                        ColumnDef::new(ModerationLog::Created)
+                            .date_time()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(ModerationLog::Table, ModerationLog::Actor)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ModerationLog::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum ModerationLog {
+    Table,
+    Id,
+    Actor,
+    Action,
+    Thread,
+    Detail,
+    Created,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230319_000001_create_drafts"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Draft::Table)
+                    .col(
+                        ColumnDef::new(Draft::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Draft::UserId).integer().not_null())
+                    .col(ColumnDef::new(Draft::Target).string().not_null())
+                    .col(ColumnDef::new(Draft::Body).text().not_null())
+                    .col(ColumnDef::new(Draft::Updated).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Draft::Table, Draft::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-draft-user_id-target")
+                    .table(Draft::Table)
+                    .col(Draft::UserId)
+                    .col(Draft::Target)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Draft::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Draft {
+    Table,
+    Id,
+    UserId,
+    Target,
+    Body,
+    Updated,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,112 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230326_000001_create_reactions"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Reaction::Table)
+                    .col(
+                        ColumnDef::new(Reaction::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Reaction::UserId).integer().not_null())
+                    .col(ColumnDef::new(Reaction::PostId).integer().not_null())
+                    .col(ColumnDef::new(Reaction::Kind).string().not_null())
+                    .col(ColumnDef::new(Reaction::Created).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Reaction::Table, Reaction::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Reaction::Table, Reaction::PostId)
+                            .to(Post::Table, Post::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-reaction-user_id-post_id-kind")
+                    .table(Reaction::Table)
+                    .col(Reaction::UserId)
+                    .col(Reaction::PostId)
+                    .col(Reaction::Kind)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Forum::Table)
+                    .add_column(
+                        ColumnDef::new(Forum::QuestionAnswer)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Forum::Table)
+                    .drop_column(Forum::QuestionAnswer)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Reaction::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Reaction {
+    Table,
+    Id,
+    UserId,
+    PostId,
+    Kind,
+    Created,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+pub enum Post {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+pub enum Forum {
+    Table,
+    QuestionAnswer,
+}
@@ -0,0 +1,209 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230402_000001_create_polls"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Poll::Table)
+                    .col(
+                        ColumnDef::new(Poll::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Poll::ThreadId)
+                            .integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Poll::Question).string().not_null())
+                    .col(ColumnDef::new(Poll::Multiple).boolean().not_null())
+                    .col(ColumnDef::new(Poll::Closes).date_time())
+                    .col(ColumnDef::new(Poll::Created).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Poll::Table, Poll::ThreadId)
+                            .to(Thread::Table, Thread::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PollOption::Table)
+                    .col(
+                        ColumnDef::new(PollOption::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PollOption::PollId).integer().not_null())
+                    .col(ColumnDef::new(PollOption::Label).string().not_null())
+                    .col(ColumnDef::new(PollOption::Position).integer().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(PollOption::Table, PollOption::PollId)
+                            .to(Poll::Table, Poll::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PollBallot::Table)
+                    .col(
+                        ColumnDef::new(PollBallot::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PollBallot::PollId).integer().not_null())
+                    .col(ColumnDef::new(PollBallot::UserId).integer().not_null())
+                    .col(ColumnDef::new(PollBallot::Created).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(PollBallot::Table, PollBallot::PollId)
+                            .to(Poll::Table, Poll::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(PollBallot::Table, PollBallot::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // One ballot per user and poll. A ballot holds one choice, or several in
+        // multiple-choice polls.
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-poll_ballot-poll_id-user_id")
+                    .table(PollBallot::Table)
+                    .col(PollBallot::PollId)
+                    .col(PollBallot::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(PollChoice::Table)
+                    .col(
+                        ColumnDef::new(PollChoice::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PollChoice::BallotId).integer().not_null())
+                    .col(ColumnDef::new(PollChoice::OptionId).integer().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(PollChoice::Table, PollChoice::BallotId)
+                            .to(PollBallot::Table, PollBallot::Id),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(PollChoice::Table, PollChoice::OptionId)
+                            .to(PollOption::Table, PollOption::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-poll_choice-ballot_id-option_id")
+                    .table(PollChoice::Table)
+                    .col(PollChoice::BallotId)
+                    .col(PollChoice::OptionId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PollChoice::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PollBallot::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PollOption::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Poll::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Poll {
+    Table,
+    Id,
+    ThreadId,
+    Question,
+    Multiple,
+    Closes,
+    Created,
+}
+
+#[derive(Iden)]
+pub enum PollOption {
+    Table,
+    Id,
+    PollId,
+    Label,
+    Position,
+}
+
+#[derive(Iden)]
+pub enum PollBallot {
+    Table,
+    Id,
+    PollId,
+    UserId,
+    Created,
+}
+
+#[derive(Iden)]
+pub enum PollChoice {
+    Table,
+    Id,
+    BallotId,
+    OptionId,
+}
+
+#[derive(Iden)]
+pub enum Thread {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230416_000001_create_door_state"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DoorState::Table)
+                    .col(
+                        ColumnDef::new(DoorState::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(DoorState::Door).string().not_null())
+                    .col(ColumnDef::new(DoorState::UserId).integer().not_null())
+                    .col(ColumnDef::new(DoorState::State).text().not_null())
+                    .col(ColumnDef::new(DoorState::Updated).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(DoorState::Table, DoorState::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-door_state-door-user_id")
+                    .table(DoorState::Table)
+                    .col(DoorState::Door)
+                    .col(DoorState::UserId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DoorState::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum DoorState {
+    Table,
+    Id,
+    Door,
+    UserId,
+    State,
+    Updated,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
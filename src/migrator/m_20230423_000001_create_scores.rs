@@ -0,0 +1,81 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230423_000001_create_scores"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Score::Table)
+                    .col(
+                        ColumnDef::new(Score::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Score::Door).string().not_null())
+                    .col(ColumnDef::new(Score::UserId).integer().not_null())
+                    .col(ColumnDef::new(Score::Score).big_integer().not_null())
+                    .col(ColumnDef::new(Score::Metadata).text().not_null())
+                    .col(
+                        ColumnDef::new(Score::PersonalBest)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(Score::Created).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(Score::Table, Score::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-score-door-score")
+                    .table(Score::Table)
+                    .col(Score::Door)
+                    .col(Score::Score)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Score::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Score {
+    Table,
+    Id,
+    Door,
+    UserId,
+    Score,
+    Metadata,
+    PersonalBest,
+    Created,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -0,0 +1,83 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230430_000001_create_time_usage"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TimeUsage::Table)
+                    .col(
+                        ColumnDef::new(TimeUsage::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TimeUsage::UserId).integer().not_null())
+                    .col(ColumnDef::new(TimeUsage::Day).string().not_null())
+                    .col(
+                        ColumnDef::new(TimeUsage::Seconds)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(TimeUsage::Calls)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(TimeUsage::Table, TimeUsage::UserId)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-time_usage-user_id-day")
+                    .table(TimeUsage::Table)
+                    .col(TimeUsage::UserId)
+                    .col(TimeUsage::Day)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TimeUsage::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum TimeUsage {
+    Table,
+    Id,
+    UserId,
+    Day,
+    Seconds,
+    Calls,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
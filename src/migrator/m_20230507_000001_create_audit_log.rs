@@ -0,0 +1,86 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20230507_000001_create_audit_log"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // `actor` is empty for events without a registered user, like a new key's first call.
+        // `target` is the user whose account the event changed, if it's someone else's.
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .col(
+                        ColumnDef::new(AuditLog::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AuditLog::Kind).string().not_null())
+                    .col(ColumnDef::new(AuditLog::Actor).integer())
+                    .col(ColumnDef::new(AuditLog::Target).integer())
+                    .col(ColumnDef::new(AuditLog::Fingerprint).string())
+                    .col(ColumnDef::new(AuditLog::Detail).string().not_null())
+                    .col(ColumnDef::new(AuditLog::Created).date_time().not_null())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from(AuditLog::Table, AuditLog::Actor)
+                            .to(User::Table, User::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-audit_log-kind")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::Kind)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-audit_log-target")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::Target)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum AuditLog {
+    Table,
+    Id,
+    Kind,
+    Actor,
+    Target,
+    Fingerprint,
+    Detail,
+    Created,
+}
+
+#[derive(Iden)]
+pub enum User {
+    Table,
+    Id,
+}
@@ -1,12 +1,44 @@
 use sea_orm_migration::{async_trait, MigrationTrait, MigratorTrait};
 
 mod m_20220127_000001_create_initial_tables;
+mod m_20230205_000001_add_roles_and_forum_order;
+mod m_20230212_000001_create_bans_and_mutes;
+mod m_20230219_000001_create_messages;
+mod m_20230226_000001_add_post_bodies;
+mod m_20230226_000002_create_mentions;
+mod m_20230305_000001_create_read_markers;
+mod m_20230312_000001_create_moderation_log;
+mod m_20230319_000001_create_drafts;
+mod m_20230326_000001_create_reactions;
+mod m_20230402_000001_create_polls;
+mod m_20230409_000001_add_profile_fields;
+mod m_20230416_000001_create_door_state;
+mod m_20230423_000001_create_scores;
+mod m_20230430_000001_create_time_usage;
+mod m_20230507_000001_create_audit_log;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m_20220127_000001_create_initial_tables::Migration)]
+        vec![
+            Box::new(m_20220127_000001_create_initial_tables::Migration),
+            Box::new(m_20230205_000001_add_roles_and_forum_order::Migration),
+            Box::new(m_20230212_000001_create_bans_and_mutes::Migration),
+            Box::new(m_20230219_000001_create_messages::Migration),
+            Box::new(m_20230226_000001_add_post_bodies::Migration),
+            Box::new(m_20230226_000002_create_mentions::Migration),
+            Box::new(m_20230305_000001_create_read_markers::Migration),
+            Box::new(m_20230312_000001_create_moderation_log::Migration),
+            Box::new(m_20230319_000001_create_drafts::Migration),
+            Box::new(m_20230326_000001_create_reactions::Migration),
+            Box::new(m_20230402_000001_create_polls::Migration),
+            Box::new(m_20230409_000001_add_profile_fields::Migration),
+            Box::new(m_20230416_000001_create_door_state::Migration),
+            Box::new(m_20230423_000001_create_scores::Migration),
+            Box::new(m_20230430_000001_create_time_usage::Migration),
+            Box::new(m_20230507_000001_create_audit_log::Migration),
+        ]
     }
 }
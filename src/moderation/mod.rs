@@ -0,0 +1,389 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use chrono::Duration;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, QueryOrder, Set,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    audit::{AuditKind, AuditUtil},
+    db::{
+        from_now,
+        gen::{
+            ban, mute,
+            prelude::{Ban, Mute, PublicKey, User},
+            public_key,
+        },
+        now,
+    },
+    settings::get_settings,
+    user::{Role, UserInfo},
+};
+
+pub use self::threads::{LogEntry, ThreadModerationError};
+
+mod threads;
+
+#[derive(Debug, Error)]
+pub enum ModerationError {
+    #[error("Only moderators can do that")]
+    NotPermitted,
+    #[error("A ban needs a user, key fingerprint or address")]
+    EmptyBanTarget,
+    #[error("That isn't an IP address")]
+    InvalidAddress,
+    #[error("Please give a reason")]
+    EmptyReason,
+    #[error("You can't do that to yourself")]
+    TargetIsSelf,
+    #[error("You can only do that to users below your own role")]
+    TargetOutranks,
+}
+
+/// What a ban applies to. Any populated field matching a connecting session triggers the ban.
+#[derive(Debug, Clone, Default)]
+pub struct BanTarget {
+    pub user_id: Option<i32>,
+    pub fingerprint: Option<String>,
+    pub address: Option<String>,
+}
+
+impl BanTarget {
+    fn is_empty(&self) -> bool {
+        self.user_id.is_none() && self.fingerprint.is_none() && self.address.is_none()
+    }
+
+    /// Lists what the ban covers, for the audit log.
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(user_id) = self.user_id {
+            parts.push(format!("user #{}", user_id));
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            parts.push(format!("key {}", fingerprint));
+        }
+        if let Some(address) = &self.address {
+            parts.push(format!("address {}", address));
+        }
+        parts.join(", ")
+    }
+
+    fn condition(&self) -> Condition {
+        let mut condition = Condition::any();
+        if let Some(user_id) = self.user_id {
+            condition = condition.add(ban::Column::UserId.eq(user_id));
+        }
+        if let Some(fingerprint) = &self.fingerprint {
+            condition = condition.add(ban::Column::Fingerprint.eq(fingerprint.clone()));
+        }
+        if let Some(address) = &self.address {
+            condition = condition.add(ban::Column::Address.eq(address.clone()));
+        }
+        condition
+    }
+}
+
+pub struct ModerationUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl ModerationUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> ModerationUtil {
+        ModerationUtil { db }
+    }
+
+    fn check_permitted(actor: &UserInfo) -> Result<(), ModerationError> {
+        if actor.has_role(Role::Moderator) {
+            Ok(())
+        } else {
+            Err(ModerationError::NotPermitted)
+        }
+    }
+
+    /// Refuses when `user_id` is the actor, or has a role as high as theirs.
+    async fn check_outranks<C: ConnectionTrait>(
+        db: &C,
+        actor: &UserInfo,
+        user_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        if actor.id() == Some(user_id) {
+            return Err(ModerationError::TargetIsSelf.into());
+        }
+        let user = match User::find_by_id(user_id).one(db).await? {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+        // The same way a login works out the role, keys listed in the config included.
+        let sysop_fingerprints = &get_settings().sysop_fingerprints;
+        let keys = PublicKey::find()
+            .filter(public_key::Column::UserId.eq(user_id))
+            .all(db)
+            .await?;
+        let role = if keys
+            .iter()
+            .any(|key| sysop_fingerprints.contains(&key.fingerprint))
+        {
+            Role::Sysop
+        } else {
+            Role::from_i32(user.role)
+        };
+        if role >= actor.role {
+            return Err(ModerationError::TargetOutranks.into());
+        }
+        Ok(())
+    }
+
+    fn ban_unexpired() -> Condition {
+        Condition::any()
+            .add(ban::Column::Expires.is_null())
+            .add(ban::Column::Expires.gt(now()))
+    }
+
+    fn mute_unexpired() -> Condition {
+        Condition::any()
+            .add(mute::Column::Expires.is_null())
+            .add(mute::Column::Expires.gt(now()))
+    }
+
+    /// Returns the first unexpired ban matching any part of `target`.
+    pub async fn active_ban(
+        &self,
+        target: &BanTarget,
+    ) -> Result<Option<ban::Model>, anyhow::Error> {
+        if target.is_empty() {
+            return Ok(None);
+        }
+        let db = self.db.lock().await.to_owned();
+        Ok(Ban::find()
+            .filter(target.condition())
+            .filter(Self::ban_unexpired())
+            .one(&db)
+            .await?)
+    }
+
+    pub async fn active_bans(&self) -> Result<Vec<ban::Model>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(Ban::find()
+            .filter(Self::ban_unexpired())
+            .order_by_desc(ban::Column::Created)
+            .all(&db)
+            .await?)
+    }
+
+    /// Bans `target`, permanently if `duration` is `None`.
+    pub async fn ban(
+        &self,
+        actor: &UserInfo,
+        mut target: BanTarget,
+        reason: &str,
+        duration: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        if target.is_empty() {
+            return Err(ModerationError::EmptyBanTarget.into());
+        }
+        // Written the way sessions report addresses, so that the ban matches them.
+        if let Some(address) = &target.address {
+            let address: IpAddr = address
+                .trim()
+                .parse()
+                .map_err(|_| ModerationError::InvalidAddress)?;
+            target.address = Some(address.to_string());
+        }
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(ModerationError::EmptyReason.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        if let Some(user_id) = target.user_id {
+            Self::check_outranks(&db, actor, user_id).await?;
+        }
+        // A key ban locks out whoever the key belongs to just the same.
+        if let Some(fingerprint) = &target.fingerprint {
+            let owner = PublicKey::find()
+                .filter(public_key::Column::Fingerprint.eq(fingerprint.clone()))
+                .one(&db)
+                .await?;
+            if let Some(owner) = owner {
+                Self::check_outranks(&db, actor, owner.user_id).await?;
+            }
+        }
+        let expires = duration.map(from_now);
+        let detail = format!(
+            "Banned {} {}: {}",
+            target.describe(),
+            describe_expiry(&expires),
+            reason
+        );
+        let target_user = target.user_id;
+        ban::ActiveModel {
+            user_id: Set(target.user_id),
+            fingerprint: Set(target.fingerprint),
+            address: Set(target.address),
+            reason: Set(reason.to_string()),
+            created: Set(now()),
+            expires: Set(expires),
+            created_by: Set(actor.id()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?;
+        AuditUtil::log_committed(&db, AuditKind::Ban, actor.id(), target_user, None, &detail).await;
+        Ok(())
+    }
+
+    /// Lifts a ban by making it expire now, keeping the record for reference.
+    pub async fn lift_ban(&self, actor: &UserInfo, ban_id: i32) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        let db = self.db.lock().await.to_owned();
+        if let Some(ban) = Ban::find_by_id(ban_id).one(&db).await? {
+            let detail = format!("Lifted ban #{} ({})", ban.id, ban.reason);
+            let target_user = ban.user_id;
+            let mut active: ban::ActiveModel = ban.into();
+            active.expires = Set(Some(now()));
+            active.update(&db).await?;
+            AuditUtil::log_committed(&db, AuditKind::Ban, actor.id(), target_user, None, &detail)
+                .await;
+        }
+        Ok(())
+    }
+
+    pub async fn lift_user_bans(
+        &self,
+        actor: &UserInfo,
+        user_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        let target = BanTarget {
+            user_id: Some(user_id),
+            ..Default::default()
+        };
+        let db = self.db.lock().await.to_owned();
+        let bans = Ban::find()
+            .filter(target.condition())
+            .filter(Self::ban_unexpired())
+            .all(&db)
+            .await?;
+        let lifted = bans.len();
+        for ban in bans {
+            let mut active: ban::ActiveModel = ban.into();
+            active.expires = Set(Some(now()));
+            active.update(&db).await?;
+        }
+        if lifted > 0 {
+            let detail = format!("Lifted {} ban(s) on user #{}", lifted, user_id);
+            AuditUtil::log_committed(
+                &db,
+                AuditKind::Ban,
+                actor.id(),
+                Some(user_id),
+                None,
+                &detail,
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    pub async fn active_mute(&self, user_id: i32) -> Result<Option<mute::Model>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(Mute::find()
+            .filter(mute::Column::UserId.eq(user_id))
+            .filter(Self::mute_unexpired())
+            .one(&db)
+            .await?)
+    }
+
+    pub async fn active_mutes(&self) -> Result<Vec<mute::Model>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(Mute::find()
+            .filter(Self::mute_unexpired())
+            .order_by_desc(mute::Column::Created)
+            .all(&db)
+            .await?)
+    }
+
+    pub async fn mute(
+        &self,
+        actor: &UserInfo,
+        user_id: i32,
+        reason: &str,
+        duration: Option<Duration>,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        let reason = reason.trim();
+        if reason.is_empty() {
+            return Err(ModerationError::EmptyReason.into());
+        }
+        let db = self.db.lock().await.to_owned();
+        Self::check_outranks(&db, actor, user_id).await?;
+        let expires = duration.map(from_now);
+        let detail = format!(
+            "Muted user #{} {}: {}",
+            user_id,
+            describe_expiry(&expires),
+            reason
+        );
+        mute::ActiveModel {
+            user_id: Set(user_id),
+            reason: Set(reason.to_string()),
+            created: Set(now()),
+            expires: Set(expires),
+            created_by: Set(actor.id()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await?;
+        AuditUtil::log_committed(
+            &db,
+            AuditKind::Moderation,
+            actor.id(),
+            Some(user_id),
+            None,
+            &detail,
+        )
+        .await;
+        Ok(())
+    }
+
+    pub async fn unmute(&self, actor: &UserInfo, user_id: i32) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        let db = self.db.lock().await.to_owned();
+        let mutes = Mute::find()
+            .filter(mute::Column::UserId.eq(user_id))
+            .filter(Self::mute_unexpired())
+            .all(&db)
+            .await?;
+        let lifted = !mutes.is_empty();
+        for mute in mutes {
+            let mut active: mute::ActiveModel = mute.into();
+            active.expires = Set(Some(now()));
+            active.update(&db).await?;
+        }
+        if lifted {
+            let detail = format!("Unmuted user #{}", user_id);
+            AuditUtil::log_committed(
+                &db,
+                AuditKind::Moderation,
+                actor.id(),
+                Some(user_id),
+                None,
+                &detail,
+            )
+            .await;
+        }
+        Ok(())
+    }
+}
+
+/// Describes when a ban or mute ends, for showing to users.
+pub fn describe_expiry(expires: &Option<String>) -> String {
+    match expires {
+        Some(expires) => format!("until {} UTC", expires),
+        None => "permanently".to_string(),
+    }
+}
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, ModelTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use thiserror::Error;
+
+use crate::{
+    audit::{AuditKind, AuditUtil},
+    db::{
+        gen::{
+            moderation_log, poll, post,
+            prelude::{Forum, ModerationLog, Poll, Post, ReadMarker, Thread, User},
+            read_marker, thread, user,
+        },
+        now,
+    },
+    forum::{bump_generation, ForumError, ForumUtil},
+    user::UserInfo,
+};
+
+use super::ModerationUtil;
+
+#[derive(Debug, Error)]
+pub enum ThreadModerationError {
+    #[error("Can't merge a thread into itself")]
+    MergeIntoSelf,
+    #[error("Select the posts to split off first")]
+    NothingSelected,
+    #[error("At least one post has to stay behind")]
+    SplitEverything,
+    #[error("The thread is already in that forum")]
+    SameForum,
+    #[error("Both threads have polls, and a thread can only hold one")]
+    BothHavePolls,
+}
+
+/// A moderation log entry with the actor's handle resolved.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub entry: moderation_log::Model,
+    pub actor: String,
+}
+
+impl ModerationUtil {
+    async fn log<C: ConnectionTrait>(
+        db: &C,
+        actor: &UserInfo,
+        action: &str,
+        thread: Option<i32>,
+        detail: String,
+    ) -> Result<(), anyhow::Error> {
+        let audit_detail = match thread {
+            Some(thread) => format!("{} thread #{}: {}", action, thread, detail),
+            None => format!("{}: {}", action, detail),
+        };
+        moderation_log::ActiveModel {
+            actor: Set(actor.id().ok_or(ForumError::NotRegistered)?),
+            action: Set(action.to_string()),
+            thread: Set(thread),
+            detail: Set(detail),
+            created: Set(now()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+        AuditUtil::log(
+            db,
+            AuditKind::Moderation,
+            actor.id(),
+            None,
+            None,
+            &audit_detail,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn find_thread<C: ConnectionTrait>(
+        db: &C,
+        thread_id: i32,
+    ) -> Result<thread::Model, anyhow::Error> {
+        Ok(Thread::find_by_id(thread_id)
+            .one(db)
+            .await?
+            .ok_or(ForumError::NoSuchThread)?)
+    }
+
+    /// Locks or unlocks a thread. Only moderators can reply to locked threads.
+    pub async fn set_locked(
+        &self,
+        actor: &UserInfo,
+        thread_id: i32,
+        locked: bool,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        let db = self.db.lock().await.to_owned();
+        let thread = Self::find_thread(&db, thread_id).await?;
+        let name = thread.name.clone();
+        let mut active: thread::ActiveModel = thread.into();
+        active.locked = Set(if locked { Some(now()) } else { None });
+        active.update(&db).await?;
+        let action = if locked { "lock" } else { "unlock" };
+        Self::log(&db, actor, action, Some(thread_id), format!("\"{}\"", name)).await?;
+        bump_generation();
+        Ok(())
+    }
+
+    pub async fn set_sticky(
+        &self,
+        actor: &UserInfo,
+        thread_id: i32,
+        sticky: bool,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        let db = self.db.lock().await.to_owned();
+        let thread = Self::find_thread(&db, thread_id).await?;
+        let name = thread.name.clone();
+        let mut active: thread::ActiveModel = thread.into();
+        active.sticky = Set(sticky);
+        active.update(&db).await?;
+        let action = if sticky { "sticky" } else { "unsticky" };
+        Self::log(&db, actor, action, Some(thread_id), format!("\"{}\"", name)).await?;
+        bump_generation();
+        Ok(())
+    }
+
+    pub async fn move_thread(
+        &self,
+        actor: &UserInfo,
+        thread_id: i32,
+        forum_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        {
+            let db = self.db.lock().await.to_owned();
+            let txn = db.begin().await?;
+            let thread = Self::find_thread(&txn, thread_id).await?;
+            if thread.forum == forum_id {
+                return Err(ThreadModerationError::SameForum.into());
+            }
+            let forums: HashMap<i32, String> = Forum::find()
+                .all(&txn)
+                .await?
+                .into_iter()
+                .map(|forum| (forum.id, forum.name))
+                .collect();
+            let to = forums.get(&forum_id).ok_or(ForumError::NoSuchForum)?;
+            let detail = format!(
+                "\"{}\" from {} to {}",
+                thread.name,
+                forums.get(&thread.forum).cloned().unwrap_or_default(),
+                to
+            );
+            let mut active: thread::ActiveModel = thread.into();
+            active.forum = Set(forum_id);
+            active.update(&txn).await?;
+            Self::log(&txn, actor, "move", Some(thread_id), detail).await?;
+            txn.commit().await?;
+        }
+        bump_generation();
+        ForumUtil::new(self.db.clone())
+            .reindex_threads(vec![thread_id])
+            .await
+    }
+
+    /// Moves every post of `source_id` into `target_id` and deletes `source_id`.
+    pub async fn merge_threads(
+        &self,
+        actor: &UserInfo,
+        source_id: i32,
+        target_id: i32,
+    ) -> Result<(), anyhow::Error> {
+        Self::check_permitted(actor)?;
+        if source_id == target_id {
+            return Err(ThreadModerationError::MergeIntoSelf.into());
+        }
+        {
+            let db = self.db.lock().await.to_owned();
+            let txn = db.begin().await?;
+            let source = Self::find_thread(&txn, source_id).await?;
+            let target = Self::find_thread(&txn, target_id).await?;
+            if let Some(poll) = source.find_related(Poll).one(&txn).await? {
+                if target.find_related(Poll).one(&txn).await?.is_some() {
+                    return Err(ThreadModerationError::BothHavePolls.into());
+                }
+                let mut active: poll::ActiveModel = poll.into();
+                active.thread_id = Set(target_id);
+                active.update(&txn).await?;
+            }
+            let posts = source.find_related(Post).all(&txn).await?;
+            let first_moved = posts.iter().map(|post| post.id).min();
+            for post in posts {
+                let mut active: post::ActiveModel = post.into();
+                active.thread = Set(target_id);
+                active.update(&txn).await?;
+            }
+            // Read positions are post ids, so they can't be carried over meaningfully. Anyone
+            // who'd read the target past where the moved posts start is put back to just before
+            // them, or the moved posts would count as read.
+            ReadMarker::delete_many()
+                .filter(read_marker::Column::ThreadId.eq(source_id))
+                .exec(&txn)
+                .await?;
+            if let Some(first_moved) = first_moved {
+                ReadMarker::update_many()
+                    .col_expr(
+                        read_marker::Column::LastReadPost,
+                        Expr::value(first_moved - 1),
+                    )
+                    .filter(read_marker::Column::ThreadId.eq(target_id))
+                    .filter(read_marker::Column::LastReadPost.gte(first_moved))
+                    .exec(&txn)
+                    .await?;
+            }
+            let detail = format!("\"{}\" into \"{}\"", source.name, target.name);
+            source.delete(&txn).await?;
+            Self::log(&txn, actor, "merge", Some(target_id), detail).await?;
+            txn.commit().await?;
+        }
+        bump_generation();
+        ForumUtil::new(self.db.clone())
+            .reindex_threads(vec![target_id])
+            .await
+    }
+
+    /// Moves the given posts of a thread into a new thread in the same forum. The new thread is
+    /// credited to the author of the earliest moved post.
+    pub async fn split_thread(
+        &self,
+        actor: &UserInfo,
+        thread_id: i32,
+        post_ids: Vec<i32>,
+        title: &str,
+    ) -> Result<thread::Model, anyhow::Error> {
+        Self::check_permitted(actor)?;
+        if title.trim().is_empty() {
+            return Err(ForumError::EmptyTitle.into());
+        }
+        if post_ids.is_empty() {
+            return Err(ThreadModerationError::NothingSelected.into());
+        }
+        let new_thread = {
+            let db = self.db.lock().await.to_owned();
+            let txn = db.begin().await?;
+            let thread = Self::find_thread(&txn, thread_id).await?;
+            let posts = Post::find()
+                .filter(post::Column::Thread.eq(thread_id))
+                .order_by_asc(post::Column::Id)
+                .all(&txn)
+                .await?;
+            let (moved, kept): (Vec<post::Model>, Vec<post::Model>) = posts
+                .into_iter()
+                .partition(|post| post_ids.contains(&post.id));
+            if moved.is_empty() {
+                return Err(ThreadModerationError::NothingSelected.into());
+            }
+            if kept.is_empty() {
+                return Err(ThreadModerationError::SplitEverything.into());
+            }
+            let first = &moved[0];
+            let new_thread = thread::ActiveModel {
+                name: Set(title.trim().to_string()),
+                created: Set(first.created.clone()),
+                locked: Set(None),
+                sticky: Set(false),
+                forum: Set(thread.forum),
+                author: Set(first.author),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            let count = moved.len();
+            for post in moved {
+                let mut active: post::ActiveModel = post.into();
+                active.thread = Set(new_thread.id);
+                active.update(&txn).await?;
+            }
+            let detail = format!(
+                "{} post{} from \"{}\" into \"{}\"",
+                count,
+                if count == 1 { "" } else { "s" },
+                thread.name,
+                new_thread.name
+            );
+            Self::log(&txn, actor, "split", Some(new_thread.id), detail).await?;
+            txn.commit().await?;
+            new_thread
+        };
+        bump_generation();
+        ForumUtil::new(self.db.clone())
+            .reindex_threads(vec![new_thread.id])
+            .await?;
+        Ok(new_thread)
+    }
+
+    /// Returns the most recent moderation log entries, newest first.
+    pub async fn log_entries(&self, limit: u64) -> Result<Vec<LogEntry>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let entries = ModerationLog::find()
+            .order_by_desc(moderation_log::Column::Id)
+            .limit(limit)
+            .all(&db)
+            .await?;
+        let actor_ids: Vec<i32> = entries.iter().map(|entry| entry.actor).collect();
+        let actors: HashMap<i32, String> = User::find()
+            .filter(user::Column::Id.is_in(actor_ids))
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|user| (user.id, user.handle))
+            .collect();
+        Ok(entries
+            .into_iter()
+            .map(|entry| LogEntry {
+                actor: actors.get(&entry.actor).cloned().unwrap_or_default(),
+                entry,
+            })
+            .collect())
+    }
+}
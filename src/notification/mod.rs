@@ -0,0 +1,68 @@
+use std::fmt::Display;
+use std::sync::Mutex;
+
+use tokio::sync::mpsc::Sender;
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<Vec<Subscriber>> = Mutex::new(Vec::new());
+}
+
+/// Something a user should hear about no matter which screen they're on.
+#[derive(Debug, Clone)]
+pub enum Notification {
+    Mail { from: String, subject: String },
+    Reply { from: String, thread: String },
+    Mention { from: String, context: String },
+}
+
+impl Display for Notification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Notification::Mail { from, subject } => {
+                write!(f, "New mail from {}: {}", from, subject)
+            }
+            Notification::Reply { from, thread } => {
+                write!(f, "{} replied to your thread \"{}\"", from, thread)
+            }
+            Notification::Mention { from, context } => {
+                write!(f, "{} mentioned you in {}", from, context)
+            }
+        }
+    }
+}
+
+struct Subscriber {
+    session_id: u64,
+    user_id: Option<i32>,
+    sender: Sender<Notification>,
+}
+
+/// Registers a session's notification channel. Notifications are addressed to users, so sessions
+/// of anonymous users never get any.
+pub(crate) fn subscribe(session_id: u64, user_id: Option<i32>, sender: Sender<Notification>) {
+    SUBSCRIBERS.lock().unwrap().push(Subscriber {
+        session_id,
+        user_id,
+        sender,
+    });
+}
+
+pub(crate) fn unsubscribe(session_id: u64) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|subscriber| subscriber.session_id != session_id);
+}
+
+/// Delivers a notification to every session `user_id` has open. Never blocks: if a session's
+/// queue is full the notification is dropped for that session.
+pub(crate) fn notify_user(user_id: i32, notification: Notification) {
+    let mut subscribers = SUBSCRIBERS.lock().unwrap();
+    subscribers.retain(|subscriber| !subscriber.sender.is_closed());
+    for subscriber in subscribers
+        .iter()
+        .filter(|subscriber| subscriber.user_id == Some(user_id))
+    {
+        let _ = subscriber.sender.try_send(notification.clone());
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, FromQueryResult,
+    IntoActiveModel, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    audit::{AuditKind, AuditUtil},
+    db::{
+        gen::{
+            post,
+            prelude::{Post, Thread, User},
+            thread, user,
+        },
+        now,
+    },
+    user::{Role, UserInfo},
+};
+
+static MAX_STATUS_LENGTH: usize = 80;
+static MAX_BIO_LENGTH: usize = 2000;
+static RECENT_THREADS: usize = 5;
+/// How far back to look through a user's posts for the threads they were active in.
+static RECENT_POSTS: u64 = 100;
+
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("Register a profile first")]
+    NotRegistered,
+    #[error("User not found")]
+    NoSuchUser,
+    #[error("Status messages can be at most {0} characters")]
+    StatusTooLong(usize),
+    #[error("Bios can be at most {0} characters")]
+    BioTooLong(usize),
+}
+
+/// What a profile page shows about a user.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub user: user::Model,
+    pub role: Role,
+    pub posts: u64,
+    /// Threads the user posted in most recently, newest first.
+    pub recent_threads: Vec<thread::Model>,
+}
+
+/// How the member list is ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberOrder {
+    Handle,
+    /// Newest members first.
+    Joined,
+    /// Most active posters first.
+    Posts,
+}
+
+/// A row in the member list.
+#[derive(Debug, Clone)]
+pub struct MemberSummary {
+    pub user: user::Model,
+    pub role: Role,
+    pub posts: i64,
+}
+
+#[derive(Debug, FromQueryResult)]
+struct PostCount {
+    author: i32,
+    posts: i64,
+}
+
+pub struct ProfileUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+impl ProfileUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> ProfileUtil {
+        ProfileUtil { db }
+    }
+
+    pub async fn find_by_handle(&self, handle: &str) -> Result<Option<i32>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        Ok(User::find()
+            .filter(user::Column::Handle.eq(handle.trim().trim_start_matches('@')))
+            .one(&db)
+            .await?
+            .map(|user| user.id))
+    }
+
+    /// Lists members whose handle contains `search`, ignoring case.
+    pub async fn members(
+        &self,
+        search: &str,
+        order: MemberOrder,
+    ) -> Result<Vec<MemberSummary>, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let search = search.trim().to_lowercase();
+        let users = User::find().all(&db).await?;
+        let counts: HashMap<i32, i64> = Post::find()
+            .select_only()
+            .column(post::Column::Author)
+            .column_as(post::Column::Id.count(), "posts")
+            .group_by(post::Column::Author)
+            .into_model::<PostCount>()
+            .all(&db)
+            .await?
+            .into_iter()
+            .map(|count| (count.author, count.posts))
+            .collect();
+        let mut members: Vec<MemberSummary> = users
+            .into_iter()
+            .filter(|user| user.handle.to_lowercase().contains(&search))
+            .map(|user| MemberSummary {
+                role: Role::from_i32(user.role),
+                posts: counts.get(&user.id).copied().unwrap_or(0),
+                user,
+            })
+            .collect();
+        members.sort_by(|a, b| {
+            let by_handle = a
+                .user
+                .handle
+                .to_lowercase()
+                .cmp(&b.user.handle.to_lowercase());
+            match order {
+                MemberOrder::Handle => by_handle,
+                MemberOrder::Joined => b.user.joined.cmp(&a.user.joined).then(by_handle),
+                MemberOrder::Posts => b.posts.cmp(&a.posts).then(by_handle),
+            }
+        });
+        Ok(members)
+    }
+
+    pub async fn profile(&self, user_id: i32) -> Result<Profile, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let user = User::find_by_id(user_id)
+            .one(&db)
+            .await?
+            .ok_or(ProfileError::NoSuchUser)?;
+        let posts = Post::find()
+            .filter(post::Column::Author.eq(user_id))
+            .count(&db)
+            .await?;
+        let mut thread_ids: Vec<i32> = Vec::new();
+        let recent_posts = Post::find()
+            .filter(post::Column::Author.eq(user_id))
+            .order_by_desc(post::Column::Id)
+            .limit(RECENT_POSTS)
+            .all(&db)
+            .await?;
+        for post in recent_posts {
+            if !thread_ids.contains(&post.thread) {
+                thread_ids.push(post.thread);
+            }
+            if thread_ids.len() == RECENT_THREADS {
+                break;
+            }
+        }
+        let threads = Thread::find()
+            .filter(thread::Column::Id.is_in(thread_ids.clone()))
+            .all(&db)
+            .await?;
+        let recent_threads = thread_ids
+            .iter()
+            .filter_map(|id| threads.iter().find(|thread| thread.id == *id).cloned())
+            .collect();
+        Ok(Profile {
+            role: Role::from_i32(user.role),
+            user,
+            posts,
+            recent_threads,
+        })
+    }
+
+    /// Applies a change to `user`'s profile, noting `change` in the audit log.
+    async fn update(
+        &self,
+        user: &UserInfo,
+        change: &str,
+        apply: impl FnOnce(&mut user::ActiveModel),
+    ) -> Result<(), anyhow::Error> {
+        let user_id = user.id().ok_or(ProfileError::NotRegistered)?;
+        let db = self.db.lock().await.to_owned();
+        let model = User::find_by_id(user_id)
+            .one(&db)
+            .await?
+            .ok_or(ProfileError::NoSuchUser)?;
+        let mut active = model.into_active_model();
+        apply(&mut active);
+        active.update(&db).await?;
+        AuditUtil::log_committed(
+            &db,
+            AuditKind::Profile,
+            Some(user_id),
+            Some(user_id),
+            None,
+            change,
+        )
+        .await;
+        Ok(())
+    }
+
+    /// Sets the one-line status shown next to `user`'s handle. A blank status clears it.
+    pub async fn set_status(&self, user: &UserInfo, status: &str) -> Result<(), anyhow::Error> {
+        let status = status.split_whitespace().collect::<Vec<_>>().join(" ");
+        if status.chars().count() > MAX_STATUS_LENGTH {
+            return Err(ProfileError::StatusTooLong(MAX_STATUS_LENGTH).into());
+        }
+        self.update(user, "Changed status", |active| {
+            active.status = Set(if status.is_empty() {
+                None
+            } else {
+                Some(status)
+            });
+        })
+        .await
+    }
+
+    pub async fn set_bio(&self, user: &UserInfo, bio: &str) -> Result<(), anyhow::Error> {
+        let bio = bio.trim();
+        if bio.chars().count() > MAX_BIO_LENGTH {
+            return Err(ProfileError::BioTooLong(MAX_BIO_LENGTH).into());
+        }
+        let bio = if bio.is_empty() {
+            None
+        } else {
+            Some(bio.to_string())
+        };
+        self.update(user, "Changed bio", |active| active.bio = Set(bio))
+            .await
+    }
+
+    /// Stamps the moment `user_id` was last around, for "last seen" on their profile.
+    pub async fn record_seen(&self, user_id: i32) -> Result<(), anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        if let Some(model) = User::find_by_id(user_id).one(&db).await? {
+            let mut active = model.into_active_model();
+            active.last_seen = Set(Some(now()));
+            active.update(&db).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use std::time::{Duration, Instant};
+
+use tokio::{task::AbortHandle, time::sleep};
+
+pub(crate) use self::usage::{CallAllowance, UsageUtil};
+
+mod usage;
+
+lazy_static! {
+    static ref SESSIONS: Mutex<Vec<SessionInfo>> = Mutex::new(Vec::new());
+    static ref CLEANUP: Mutex<HashMap<u64, SessionCleanup>> = Mutex::new(HashMap::new());
+    static ref SHUTDOWN: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+/// What has to be undone when a session ends. Kept out of `SessionInfo` so that can be cloned.
+#[derive(Default)]
+struct SessionCleanup {
+    tasks: Vec<AbortHandle>,
+    hooks: Vec<Box<dyn FnOnce() + Send>>,
+}
+
+static NEXT_SESSION_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone)]
+pub(crate) struct SessionInfo {
+    pub(crate) id: u64,
+    pub(crate) user_id: Option<i32>,
+    pub(crate) handle: Option<String>,
+    /// Fingerprint of the key the session connected with.
+    pub(crate) fingerprint: Option<String>,
+    /// The IP address the session connected from, if ssh_ui knew it.
+    pub(crate) address: Option<String>,
+    pub(crate) started: Instant,
+    /// The part of the board the session is in, as named by the main menu.
+    pub(crate) screen: String,
+    pub(crate) last_activity: Instant,
+    /// When the session runs out of time, if its user has a limit.
+    pub(crate) deadline: Option<Instant>,
+    /// Why a sysop asked for the session to be closed, until the session notices.
+    pub(crate) kicked: Option<String>,
+}
+
+pub(crate) fn register_session() -> u64 {
+    let id = NEXT_SESSION_ID.fetch_add(1, Ordering::Relaxed);
+    SESSIONS.lock().unwrap().push(SessionInfo {
+        id,
+        user_id: None,
+        handle: None,
+        fingerprint: None,
+        address: None,
+        started: Instant::now(),
+        screen: "Connecting".into(),
+        last_activity: Instant::now(),
+        deadline: None,
+        kicked: None,
+    });
+    id
+}
+
+/// Cancels `task` when the session ends, if it hasn't finished by then.
+pub(crate) fn add_session_task(id: u64, task: AbortHandle) {
+    CLEANUP
+        .lock()
+        .unwrap()
+        .entry(id)
+        .or_default()
+        .tasks
+        .push(task);
+}
+
+/// Runs `hook` when the session ends.
+pub(crate) fn on_session_end(id: u64, hook: impl FnOnce() + Send + 'static) {
+    CLEANUP
+        .lock()
+        .unwrap()
+        .entry(id)
+        .or_default()
+        .hooks
+        .push(Box::new(hook));
+}
+
+/// Forgets a session, cancels its tasks and then runs its end hooks. Only the first call for a
+/// session does anything.
+pub(crate) fn end_session(id: u64) {
+    SESSIONS.lock().unwrap().retain(|session| session.id != id);
+    let cleanup = CLEANUP.lock().unwrap().remove(&id);
+    if let Some(cleanup) = cleanup {
+        for task in cleanup.tasks {
+            task.abort();
+        }
+        for hook in cleanup.hooks {
+            hook();
+        }
+    }
+}
+
+pub(crate) fn list_sessions() -> Vec<SessionInfo> {
+    SESSIONS.lock().unwrap().clone()
+}
+
+fn update_session(id: u64, update: impl FnOnce(&mut SessionInfo)) {
+    if let Some(session) = SESSIONS
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|session| session.id == id)
+    {
+        update(session);
+    }
+}
+
+pub(crate) fn set_session_user(id: u64, user_id: Option<i32>, handle: Option<String>) {
+    update_session(id, |session| {
+        session.user_id = user_id;
+        session.handle = handle;
+    });
+}
+
+pub(crate) fn set_session_fingerprint(id: u64, fingerprint: Option<String>) {
+    update_session(id, |session| session.fingerprint = fingerprint);
+}
+
+pub(crate) fn set_session_address(id: u64, address: Option<String>) {
+    update_session(id, |session| session.address = address);
+}
+
+/// The fingerprint of the key the session connected with and the address it connected from, as
+/// far as they're known.
+pub(crate) fn session_identity(id: u64) -> (Option<String>, Option<String>) {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|session| session.id == id)
+        .map(|session| (session.fingerprint.clone(), session.address.clone()))
+        .unwrap_or_default()
+}
+
+pub(crate) fn set_session_screen(id: u64, screen: &str) {
+    update_session(id, |session| session.screen = screen.to_string());
+}
+
+/// How long since the session's user last did anything.
+pub(crate) fn session_idle_time(id: u64) -> Option<Duration> {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|session| session.id == id)
+        .map(|session| session.last_activity.elapsed())
+}
+
+/// Notes that the session's user just did something, for idle times.
+pub(crate) fn touch_session(id: u64) {
+    update_session(id, |session| session.last_activity = Instant::now());
+}
+
+pub(crate) fn set_session_deadline(id: u64, deadline: Option<Instant>) {
+    update_session(id, |session| session.deadline = deadline);
+}
+
+/// How long the session has before it's disconnected, or `None` if it can stay all day.
+pub(crate) fn session_time_left(id: u64) -> Option<Duration> {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|session| session.id == id)
+        .and_then(|session| session.deadline)
+        .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+/// Asks a session to disconnect. It notices on its next tick. Returns whether the session exists.
+pub(crate) fn kick_session(id: u64, reason: &str) -> bool {
+    let mut found = false;
+    update_session(id, |session| {
+        session.kicked = Some(reason.to_string());
+        found = true;
+    });
+    found
+}
+
+/// Why the session was kicked, if it was.
+pub(crate) fn session_kicked(id: u64) -> Option<String> {
+    SESSIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|session| session.id == id)
+        .and_then(|session| session.kicked.clone())
+}
+
+/// Tells every session that the server is going down after `grace`, and turns away new callers.
+/// Returns when sessions will be disconnected.
+pub(crate) fn begin_shutdown(grace: Duration) -> Instant {
+    *SHUTDOWN
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| Instant::now() + grace)
+}
+
+/// When the server is going down, if it's shutting down.
+pub(crate) fn shutdown_deadline() -> Option<Instant> {
+    *SHUTDOWN.lock().unwrap()
+}
+
+/// Waits until every session has ended, or until `give_up`.
+pub(crate) async fn wait_for_sessions(give_up: Instant) {
+    while !SESSIONS.lock().unwrap().is_empty() && Instant::now() < give_up {
+        sleep(Duration::from_millis(250)).await;
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    Set,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::gen::{prelude::TimeUsage, time_usage},
+    settings::get_settings,
+    user::{Role, UserInfo},
+};
+
+/// Whether a caller may stay, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CallAllowance {
+    /// Let them in. They'll be disconnected after the given time, if there is one.
+    Allowed(Option<Duration>),
+    OutOfTime,
+    OutOfCalls,
+}
+
+/// Keeps track of how long and how often each user calls, per UTC day.
+pub struct UsageUtil {
+    db: Arc<Mutex<DatabaseConnection>>,
+}
+
+fn today() -> String {
+    Utc::now().date_naive().format("%Y-%m-%d").to_string()
+}
+
+impl UsageUtil {
+    pub fn new(db: Arc<Mutex<DatabaseConnection>>) -> UsageUtil {
+        UsageUtil { db }
+    }
+
+    async fn find(
+        db: &DatabaseConnection,
+        user_id: i32,
+        day: &str,
+    ) -> Result<Option<time_usage::Model>, anyhow::Error> {
+        Ok(TimeUsage::find()
+            .filter(time_usage::Column::UserId.eq(user_id))
+            .filter(time_usage::Column::Day.eq(day))
+            .one(db)
+            .await?)
+    }
+
+    /// Returns today's row for `user_id`, creating it if needed. Two sessions may try to create it
+    /// at once; the unique index on (user_id, day) lets one through and the other reads its row.
+    async fn find_or_create(
+        db: &DatabaseConnection,
+        user_id: i32,
+    ) -> Result<time_usage::Model, anyhow::Error> {
+        let day = today();
+        if let Some(usage) = Self::find(db, user_id, &day).await? {
+            return Ok(usage);
+        }
+        let inserted = time_usage::ActiveModel {
+            user_id: Set(user_id),
+            day: Set(day.clone()),
+            seconds: Set(0),
+            calls: Set(0),
+            ..Default::default()
+        }
+        .insert(db)
+        .await;
+        match inserted {
+            Ok(usage) => Ok(usage),
+            Err(err) => Self::find(db, user_id, &day)
+                .await?
+                .ok_or_else(|| err.into()),
+        }
+    }
+
+    /// Counts a new call by `user` against their quota and works out how long they may stay, as
+    /// of now. The daily allowance is shared with the user's other sessions, so that can shrink
+    /// as `add_time` reports the day's total.
+    /// Callers without a profile only get their role's per-session limit, since there's nothing
+    /// to count their calls against.
+    pub(crate) async fn start_call(
+        &self,
+        user: Option<&UserInfo>,
+    ) -> Result<CallAllowance, anyhow::Error> {
+        let role = user.map_or(Role::Member, |user| user.role);
+        let limits = get_settings().limits(role);
+        let user_id = match user.and_then(|user| user.id()) {
+            Some(user_id) => user_id,
+            None => return Ok(CallAllowance::Allowed(limits.session)),
+        };
+        let db = self.db.lock().await.to_owned();
+        let usage = Self::find_or_create(&db, user_id).await?;
+        let daily_left = limits
+            .daily
+            .map(|daily| daily.saturating_sub(Duration::from_secs(usage.seconds.max(0) as u64)));
+        if daily_left == Some(Duration::ZERO) {
+            return Ok(CallAllowance::OutOfTime);
+        }
+        // Checking the quota in the update itself keeps two sessions from both taking the last call.
+        let mut count_call = TimeUsage::update_many()
+            .col_expr(
+                time_usage::Column::Calls,
+                Expr::col(time_usage::Column::Calls).add(1),
+            )
+            .filter(time_usage::Column::Id.eq(usage.id));
+        if let Some(calls) = limits.daily_calls {
+            count_call = count_call.filter(time_usage::Column::Calls.lt(calls as i32));
+        }
+        if count_call.exec(&db).await?.rows_affected == 0 {
+            return Ok(CallAllowance::OutOfCalls);
+        }
+        let left = match (limits.session, daily_left) {
+            (Some(session), Some(daily)) => Some(session.min(daily)),
+            (session, daily) => session.or(daily),
+        };
+        Ok(CallAllowance::Allowed(left))
+    }
+
+    /// Adds `elapsed` to the time `user_id` has spent online today, and returns the total so far
+    /// across all of their sessions.
+    pub async fn add_time(
+        &self,
+        user_id: i32,
+        elapsed: Duration,
+    ) -> Result<Duration, anyhow::Error> {
+        let db = self.db.lock().await.to_owned();
+        let usage = Self::find_or_create(&db, user_id).await?;
+        TimeUsage::update_many()
+            .col_expr(
+                time_usage::Column::Seconds,
+                Expr::col(time_usage::Column::Seconds).add(elapsed.as_secs() as i64),
+            )
+            .filter(time_usage::Column::Id.eq(usage.id))
+            .exec(&db)
+            .await?;
+        let seconds = TimeUsage::find_by_id(usage.id)
+            .one(&db)
+            .await?
+            .map_or(0, |usage| usage.seconds);
+        Ok(Duration::from_secs(seconds.max(0) as u64))
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use config::Config;
+
+use crate::{door::DoorDefinition, throttle::Action, user::Role};
+
+static DEFAULT_IDLE_MINUTES: u64 = 15;
+static DEFAULT_SHUTDOWN_GRACE_SECONDS: u64 = 30;
+
+lazy_static! {
+    static ref SETTINGS: RwLock<Settings> = RwLock::new(Settings::default());
+}
+
+/// Runtime settings that screens need after startup. Populated once from the config file in `main`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Settings {
+    pub(crate) library_path: String,
+    pub(crate) library_index_path: String,
+    pub(crate) forum_index_path: String,
+    pub(crate) sysop_fingerprints: Vec<String>,
+    /// Reactions readers can leave on posts, besides the up and down votes.
+    pub(crate) reactions: Vec<String>,
+    pub(crate) doors: Vec<DoorDefinition>,
+    /// How long a session may sit without input before it's disconnected.
+    pub(crate) idle_timeout: Option<Duration>,
+    /// A file to append audit events to as JSON lines, besides the `audit_log` table.
+    pub(crate) audit_log_path: Option<String>,
+    /// How long callers are warned before the server shuts down.
+    pub(crate) shutdown_grace: Duration,
+    /// Limits for each role, indexed by `Role::as_i32`.
+    role_limits: Vec<RoleLimits>,
+    /// Rate limits for each action, in `Action::ALL` order.
+    rate_limits: Vec<Option<RateLimit>>,
+}
+
+/// How long and how often users of one role may call. `None` means no limit.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct RoleLimits {
+    pub(crate) session: Option<Duration>,
+    pub(crate) daily: Option<Duration>,
+    pub(crate) daily_calls: Option<u32>,
+}
+
+impl RoleLimits {
+    /// Reads the `[limits.<role>]` table.
+    fn from_config(config: &Config, role: Role) -> RoleLimits {
+        let get = |key: &str| {
+            config
+                .get_int(&format!("limits.{}.{}", role, key))
+                .ok()
+                .filter(|value| *value > 0)
+        };
+        let minutes = |key: &str| get(key).map(|minutes| Duration::from_secs(minutes as u64 * 60));
+        RoleLimits {
+            session: minutes("session_minutes"),
+            daily: minutes("daily_minutes"),
+            daily_calls: get("daily_calls").map(|calls| calls as u32),
+        }
+    }
+}
+
+impl Settings {
+    pub(crate) fn from_config(config: &Config) -> Settings {
+        let sysop_fingerprints = config
+            .get_array("sysop_fingerprints")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|value| value.into_string().ok())
+            .collect();
+        let reactions = config
+            .get_array("reactions")
+            .map(|values| {
+                values
+                    .into_iter()
+                    .filter_map(|value| value.into_string().ok())
+                    .collect()
+            })
+            .unwrap_or_else(|_| {
+                ["thanks", "agree", "funny", "insightful"]
+                    .map(String::from)
+                    .to_vec()
+            });
+        Settings {
+            library_path: config
+                .get_string("library_path")
+                .unwrap_or("./library.zim".into()),
+            library_index_path: "_search_index".into(),
+            forum_index_path: "_forum_index".into(),
+            sysop_fingerprints,
+            reactions,
+            doors: DoorDefinition::from_config(config),
+            idle_timeout: match config.get_int("idle_timeout_minutes") {
+                Ok(minutes) if minutes <= 0 => None,
+                Ok(minutes) => Some(Duration::from_secs(minutes as u64 * 60)),
+                Err(_) => Some(Duration::from_secs(DEFAULT_IDLE_MINUTES * 60)),
+            },
+            audit_log_path: config.get_string("audit_log_path").ok(),
+            shutdown_grace: Duration::from_secs(
+                config
+                    .get_int("shutdown_grace_seconds")
+                    .map_or(DEFAULT_SHUTDOWN_GRACE_SECONDS, |seconds| {
+                        seconds.max(0) as u64
+                    }),
+            ),
+            role_limits: Role::ALL
+                .iter()
+                .map(|role| RoleLimits::from_config(config, *role))
+                .collect(),
+            rate_limits: Action::ALL
+                .iter()
+                .map(|action| RateLimit::from_config(config, *action))
+                .collect(),
+        }
+    }
+
+    pub(crate) fn limits(&self, role: Role) -> RoleLimits {
+        self.role_limits
+            .get(role.as_i32() as usize)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn rate_limit(&self, action: Action) -> Option<RateLimit> {
+        self.rate_limits.get(action as usize).copied().flatten()
+    }
+}
+
+/// A token bucket: `burst` actions back to back, refilled at `per_minute`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct RateLimit {
+    pub(crate) per_minute: f64,
+    pub(crate) burst: f64,
+}
+
+impl RateLimit {
+    /// Reads the `[rate_limits.<action>]` table. A `per_minute` of 0 turns the limit off.
+    fn from_config(config: &Config, action: Action) -> Option<RateLimit> {
+        let (per_minute, burst) = action.default_limit();
+        let get = |key: &str, default: u32| {
+            config
+                .get_int(&format!("rate_limits.{}.{}", action, key))
+                .map_or(default as f64, |value| value.max(0) as f64)
+        };
+        let per_minute = get("per_minute", per_minute);
+        if per_minute == 0.0 {
+            return None;
+        }
+        Some(RateLimit {
+            per_minute,
+            burst: get("burst", burst).max(1.0),
+        })
+    }
+}
+
+pub(crate) fn set_settings(settings: Settings) {
+    *SETTINGS.write().unwrap() = settings;
+}
+
+pub(crate) fn get_settings() -> Settings {
+    SETTINGS.read().unwrap().clone()
+}
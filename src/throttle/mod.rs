@@ -0,0 +1,192 @@
+//! Token-bucket rate limits on connecting, chatting, posting and searching. Buckets belong to the
+//! caller's address or key fingerprint rather than the session, so reconnecting doesn't refill
+//! them.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::{
+    session::session_identity,
+    settings::{get_settings, RateLimit},
+};
+
+/// Once there are this many buckets, ones nobody has used for `PRUNE_AGE` are dropped.
+const PRUNE_THRESHOLD: usize = 4096;
+const PRUNE_AGE: Duration = Duration::from_secs(10 * 60);
+
+lazy_static! {
+    static ref BUCKETS: Mutex<HashMap<(Action, String), Bucket>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Error, Debug)]
+pub enum ThrottleError {
+    #[error("You're going a bit fast. Please wait {0} seconds and try again.")]
+    Throttled(u64),
+}
+
+/// Something callers are limited in how often they can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Action {
+    Connect,
+    Chat,
+    Post,
+    Search,
+}
+
+impl Action {
+    pub(crate) const ALL: [Action; 4] =
+        [Action::Connect, Action::Chat, Action::Post, Action::Search];
+
+    /// Per minute and burst when the config doesn't say.
+    pub(crate) fn default_limit(self) -> (u32, u32) {
+        match self {
+            Action::Connect => (6, 3),
+            Action::Chat => (20, 5),
+            Action::Post => (4, 2),
+            Action::Search => (120, 40),
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Action::Connect => "connect",
+            Action::Chat => "chat",
+            Action::Post => "post",
+            Action::Search => "search",
+        };
+        f.write_str(name)
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_minute / 60.0).min(limit.burst);
+        self.updated = now;
+    }
+
+    /// How long until there's a whole token.
+    fn wait(&self, limit: RateLimit) -> Duration {
+        if self.tokens >= 1.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) * 60.0 / limit.per_minute)
+        }
+    }
+}
+
+/// Takes a token for `action` from the bucket of the session's caller.
+pub(crate) fn check(session_id: u64, action: Action) -> Result<(), ThrottleError> {
+    let limit = match get_settings().rate_limit(action) {
+        Some(limit) => limit,
+        None => return Ok(()),
+    };
+    let (fingerprint, address) = session_identity(session_id);
+    let owner = match action {
+        // A new key costs nothing to make, so connects are held against the address, or the key
+        // when the address isn't known. With neither there's nothing that outlives the session.
+        Action::Connect => match (address, fingerprint) {
+            (Some(address), _) => format!("address:{}", address),
+            (None, Some(fingerprint)) => format!("key:{}", fingerprint),
+            (None, None) => return Ok(()),
+        },
+        // Everyone behind a shared address would take from one bucket, so once they're connected
+        // callers with a key are limited by it instead.
+        _ => match (fingerprint, address) {
+            (Some(fingerprint), _) => format!("key:{}", fingerprint),
+            (None, Some(address)) => format!("address:{}", address),
+            (None, None) => format!("session:{}", session_id),
+        },
+    };
+    take(
+        &mut BUCKETS.lock().unwrap(),
+        action,
+        owner,
+        limit,
+        Instant::now(),
+    )
+}
+
+/// Takes a token for `action` from `owner`'s bucket.
+fn take(
+    buckets: &mut HashMap<(Action, String), Bucket>,
+    action: Action,
+    owner: String,
+    limit: RateLimit,
+    now: Instant,
+) -> Result<(), ThrottleError> {
+    if buckets.len() > PRUNE_THRESHOLD {
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated) < PRUNE_AGE);
+    }
+    let bucket = buckets.entry((action, owner)).or_insert(Bucket {
+        tokens: limit.burst,
+        updated: now,
+    });
+    bucket.refill(limit, now);
+    let wait = bucket.wait(limit);
+    if !wait.is_zero() {
+        return Err(ThrottleError::Throttled(wait.as_secs_f64().ceil() as u64));
+    }
+    bucket.tokens -= 1.0;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        per_minute: 60.0,
+        burst: 3.0,
+    };
+
+    #[test]
+    fn refill_adds_tokens_up_to_the_burst() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 0.0,
+            updated: start,
+        };
+        bucket.refill(LIMIT, start + Duration::from_secs(2));
+        assert_eq!(bucket.tokens, 2.0);
+        assert_eq!(bucket.updated, start + Duration::from_secs(2));
+        bucket.refill(LIMIT, start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, LIMIT.burst);
+    }
+
+    #[test]
+    fn wait_is_the_time_to_a_whole_token() {
+        let now = Instant::now();
+        let bucket = |tokens| Bucket {
+            tokens,
+            updated: now,
+        };
+        assert_eq!(bucket(1.0).wait(LIMIT), Duration::ZERO);
+        assert_eq!(bucket(2.5).wait(LIMIT), Duration::ZERO);
+        assert_eq!(bucket(0.5).wait(LIMIT), Duration::from_millis(500));
+        assert_eq!(bucket(0.0).wait(LIMIT), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn take_keeps_actions_apart() {
+        let now = Instant::now();
+        let owner = || "key:a".to_string();
+        let mut buckets = HashMap::new();
+        for _ in 0..3 {
+            take(&mut buckets, Action::Chat, owner(), LIMIT, now).unwrap();
+        }
+        assert!(take(&mut buckets, Action::Chat, owner(), LIMIT, now).is_err());
+        take(&mut buckets, Action::Search, owner(), LIMIT, now).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::{Arc, Mutex as StdMutex};
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    view::{Nameable, Resizable, Scrollable},
+    views::{Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView},
+    Cursive, View,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    audit::{AuditEntry, AuditFilter, AuditKind, AuditUtil},
+    ui::block_on,
+};
+
+static AUDIT_LIST: &str = "sysop_audit_list";
+static AUDIT_STATUS: &str = "sysop_audit_status";
+static AUDIT_KIND_SELECT: &str = "sysop_audit_kind";
+static AUDIT_ACTOR_EDIT: &str = "sysop_audit_actor";
+
+/// What the audit screen is showing.
+#[derive(Default)]
+struct AuditState {
+    filter: AuditFilter,
+    page: u64,
+    pages: u64,
+}
+
+#[derive(Clone)]
+struct AuditScreen {
+    audit: Arc<AuditUtil>,
+    state: Arc<StdMutex<AuditState>>,
+}
+
+fn entry_label(entry: &AuditEntry) -> String {
+    format!(
+        "{} {:12} {:16} {:16} {}",
+        entry.entry.created,
+        entry.entry.kind,
+        entry.actor.as_deref().unwrap_or("-"),
+        entry.target.as_deref().unwrap_or("-"),
+        entry.entry.detail
+    )
+}
+
+fn show_entry(siv: &mut Cursive, entry: &AuditEntry) {
+    let mut text = format!(
+        "#{} at {} UTC\nKind: {}\nBy: {}\nTo: {}\n",
+        entry.entry.id,
+        entry.entry.created,
+        entry.entry.kind,
+        entry.actor.as_deref().unwrap_or("-"),
+        entry.target.as_deref().unwrap_or("-")
+    );
+    if let Some(fingerprint) = &entry.entry.fingerprint {
+        text.push_str(&format!("Key: {}\n", fingerprint));
+    }
+    text.push_str(&format!("\n{}", entry.entry.detail));
+    siv.add_layer(Dialog::info(text).title("Audit log entry"));
+}
+
+/// Fetches the page the screen is on, returning its entries and a line describing it.
+fn load(screen: &AuditScreen) -> Result<(Vec<AuditEntry>, String), anyhow::Error> {
+    let (filter, page) = {
+        let state = screen.state.lock().unwrap();
+        (state.filter.clone(), state.page)
+    };
+    let result = block_on(screen.audit.entries(&filter, page))?;
+    screen.state.lock().unwrap().pages = result.pages;
+    let status = format!(
+        "Page {} of {}. Showing {} events by {}.",
+        page + 1,
+        result.pages.max(1),
+        filter
+            .kind
+            .map_or("all".to_string(), |kind| kind.to_string()),
+        filter.actor.as_deref().unwrap_or("anyone")
+    );
+    Ok((result.entries, status))
+}
+
+fn refresh(siv: &mut Cursive, screen: &AuditScreen) {
+    match load(screen) {
+        Ok((entries, status)) => {
+            siv.call_on_name(AUDIT_LIST, |list: &mut SelectView<AuditEntry>| {
+                list.clear();
+                for entry in entries {
+                    list.add_item(entry_label(&entry), entry);
+                }
+            });
+            siv.call_on_name(AUDIT_STATUS, |text: &mut TextView| text.set_content(status));
+        }
+        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+    }
+}
+
+fn turn_page(siv: &mut Cursive, screen: &AuditScreen, forward: bool) {
+    {
+        let mut state = screen.state.lock().unwrap();
+        if forward && state.page + 1 < state.pages {
+            state.page += 1;
+        } else if !forward && state.page > 0 {
+            state.page -= 1;
+        } else {
+            return;
+        }
+    }
+    refresh(siv, screen);
+}
+
+fn filter_dialog(siv: &mut Cursive, screen: AuditScreen) {
+    let (kind, actor) = {
+        let state = screen.state.lock().unwrap();
+        (state.filter.kind, state.filter.actor.clone())
+    };
+    let mut kinds = SelectView::new().item("All events", None);
+    for option in AuditKind::ALL {
+        kinds.add_item(option.to_string(), Some(option));
+    }
+    let selected = AuditKind::ALL
+        .iter()
+        .position(|option| Some(*option) == kind)
+        .map_or(0, |index| index + 1);
+    kinds.set_selection(selected);
+    let layout = LinearLayout::vertical()
+        .child(kinds.popup().with_name(AUDIT_KIND_SELECT))
+        .child(DummyView)
+        .child(TextView::new("By handle (blank for anyone):"))
+        .child(
+            EditView::new()
+                .content(actor.unwrap_or_default())
+                .with_name(AUDIT_ACTOR_EDIT)
+                .min_width(24),
+        );
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("Filter audit log")
+            .button("Ok", move |siv| {
+                let kind = siv
+                    .call_on_name(
+                        AUDIT_KIND_SELECT,
+                        |kinds: &mut SelectView<Option<AuditKind>>| {
+                            kinds.selection().and_then(|kind| *kind)
+                        },
+                    )
+                    .unwrap();
+                let actor = siv
+                    .call_on_name(AUDIT_ACTOR_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                siv.pop_layer();
+                {
+                    let mut state = screen.state.lock().unwrap();
+                    state.filter = AuditFilter {
+                        kind,
+                        actor: Some(actor.trim().to_string()).filter(|actor| !actor.is_empty()),
+                    };
+                    state.page = 0;
+                }
+                refresh(siv, &screen);
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// Pages through the audit log, newest first, filtered by kind of event and who did it.
+pub(super) fn audit_screen(db: Arc<Mutex<DatabaseConnection>>) -> Box<dyn View> {
+    let screen = AuditScreen {
+        audit: Arc::new(AuditUtil::new(db)),
+        state: Arc::new(StdMutex::new(AuditState::default())),
+    };
+    let mut list = SelectView::<AuditEntry>::new();
+    let mut status = TextView::new("");
+    match load(&screen) {
+        Ok((entries, text)) => {
+            for entry in entries {
+                list.add_item(entry_label(&entry), entry);
+            }
+            status.set_content(text);
+        }
+        Err(err) => error!("Failed to load the audit log: {}", err),
+    }
+    let list = list.on_submit(show_entry);
+    let (next, previous, filter) = (screen.clone(), screen.clone(), screen);
+    let list = OnEventView::new(list.with_name(AUDIT_LIST))
+        .on_event('n', move |siv| turn_page(siv, &next, true))
+        .on_event('p', move |siv| turn_page(siv, &previous, false))
+        .on_event('f', move |siv| filter_dialog(siv, filter.clone()));
+    let layout = LinearLayout::vertical()
+        .child(status.with_name(AUDIT_STATUS))
+        .child(TextView::new(
+            "f: filter, n/p: next or previous page. Select an event for its details.",
+        ))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
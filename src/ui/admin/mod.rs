@@ -0,0 +1,834 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use chrono::Duration;
+use log::{error, info};
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        view::{Nameable, Resizable, Scrollable},
+        views::{Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView},
+        Cursive, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::{spawn, sync::Mutex};
+
+use crate::{
+    admin::AdminUtil,
+    audit::{AuditKind, AuditUtil},
+    db::gen::{ban, forum, user},
+    forum::{ForumNode, ForumUtil},
+    moderation::{describe_expiry, BanTarget, ModerationUtil},
+    session::{kick_session, list_sessions, SessionInfo},
+    settings::get_settings,
+    ui::library::{replace_library, Library},
+    user::{Role, UserInfo},
+};
+
+use self::audit::audit_screen;
+use super::{block_on, get_user, labeled_edit_view::LabeledEditView, stack::get_stack};
+
+mod audit;
+
+static USER_LIST: &str = "sysop_user_list";
+static FORUM_LIST: &str = "sysop_forum_list";
+static FORUM_NAME_EDIT: &str = "sysop_forum_name";
+static FORUM_DESCRIPTION_EDIT: &str = "sysop_forum_description";
+static RENAME_EDIT: &str = "sysop_rename";
+static BAN_LIST: &str = "sysop_ban_list";
+static SANCTION_REASON_EDIT: &str = "sysop_sanction_reason";
+static SANCTION_DAYS_EDIT: &str = "sysop_sanction_days";
+static BLOCKLIST_EDIT: &str = "sysop_blocklist";
+static SESSION_LIST: &str = "sysop_session_list";
+static KICK_REASON_EDIT: &str = "sysop_kick_reason";
+
+enum SysopOption {
+    Users,
+    Bans,
+    Forums,
+    Sessions,
+    Audit,
+    Reindex,
+}
+
+fn show_result(siv: &mut Cursive, result: Result<(), anyhow::Error>) {
+    if let Err(err) = result {
+        siv.add_layer(Dialog::info(err.to_string()));
+    }
+}
+
+pub fn sysop_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>) -> Box<dyn View> {
+    let actor = match get_user(db.clone(), key) {
+        Ok(actor) if actor.has_role(Role::Sysop) => actor,
+        _ => {
+            return Box::new(TextView::new(
+                "The sysop console is only available to sysops.",
+            ))
+        }
+    };
+    let title = format!("Sysop console. Logged in as {}.", actor.handle);
+    let select_view = SelectView::new()
+        .item("Manage (U)sers", SysopOption::Users)
+        .item("(B)ans and key blocklist", SysopOption::Bans)
+        .item("Manage (F)orums", SysopOption::Forums)
+        .item("Connected (S)essions", SysopOption::Sessions)
+        .item("(A)udit log", SysopOption::Audit)
+        .item("(R)eindex the library", SysopOption::Reindex)
+        .on_submit(move |siv, item| match item {
+            SysopOption::Users => {
+                get_stack(siv)
+                    .push(users_screen(db.clone(), actor.clone()))
+                    .unwrap();
+            }
+            SysopOption::Bans => {
+                get_stack(siv)
+                    .push(bans_screen(db.clone(), actor.clone()))
+                    .unwrap();
+            }
+            SysopOption::Forums => {
+                get_stack(siv)
+                    .push(forums_screen(db.clone(), actor.clone()))
+                    .unwrap();
+            }
+            SysopOption::Sessions => {
+                get_stack(siv)
+                    .push(sessions_screen(db.clone(), actor.clone()))
+                    .unwrap();
+            }
+            SysopOption::Audit => {
+                get_stack(siv).push(audit_screen(db.clone())).unwrap();
+            }
+            SysopOption::Reindex => confirm_reindex(siv),
+        });
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(title))
+        .child(DummyView)
+        .child(select_view);
+    Box::new(layout)
+}
+
+/// Everything the user management screens need to act on behalf of the sysop.
+#[derive(Clone)]
+struct Console {
+    actor: UserInfo,
+    admin: Arc<AdminUtil>,
+    moderation: Arc<ModerationUtil>,
+}
+
+impl Console {
+    fn new(db: Arc<Mutex<DatabaseConnection>>, actor: UserInfo) -> Console {
+        Console {
+            admin: Arc::new(AdminUtil::new(db.clone(), actor.clone())),
+            moderation: Arc::new(ModerationUtil::new(db)),
+            actor,
+        }
+    }
+}
+
+fn load_users(console: &Console) -> Result<Vec<(String, user::Model)>, anyhow::Error> {
+    let users = block_on(console.admin.list_users())?;
+    let banned: HashSet<i32> = block_on(console.moderation.active_bans())?
+        .into_iter()
+        .filter_map(|ban| ban.user_id)
+        .collect();
+    let muted: HashSet<i32> = block_on(console.moderation.active_mutes())?
+        .into_iter()
+        .map(|mute| mute.user_id)
+        .collect();
+    Ok(users
+        .into_iter()
+        .map(|user| {
+            let mut label = format!("{:24} {}", user.handle, Role::from_i32(user.role));
+            if banned.contains(&user.id) {
+                label.push_str(" [banned]");
+            }
+            if muted.contains(&user.id) {
+                label.push_str(" [muted]");
+            }
+            (label, user)
+        })
+        .collect())
+}
+
+fn refresh_users(siv: &mut Cursive, console: &Console) {
+    match load_users(console) {
+        Ok(users) => {
+            siv.call_on_name(USER_LIST, |list: &mut SelectView<user::Model>| {
+                let selected = list.selected_id();
+                list.clear();
+                for (label, user) in users {
+                    list.add_item(label, user);
+                }
+                if let Some(selected) = selected {
+                    list.set_selection(selected);
+                }
+            });
+        }
+        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+    }
+}
+
+fn users_screen(db: Arc<Mutex<DatabaseConnection>>, actor: UserInfo) -> Box<dyn View> {
+    let console = Console::new(db, actor);
+    let mut list = SelectView::<user::Model>::new();
+    match load_users(&console) {
+        Ok(users) => {
+            for (label, user) in users {
+                list.add_item(label, user);
+            }
+        }
+        Err(err) => error!("Failed to list users: {}", err),
+    }
+    let list = list.on_submit(move |siv, user| user_actions(siv, console.clone(), user.clone()));
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Select a user to rename, change their role, ban or mute them.",
+        ))
+        .child(DummyView)
+        .child(list.with_name(USER_LIST).scrollable().full_screen());
+    Box::new(layout)
+}
+
+fn user_actions(siv: &mut Cursive, console: Console, user: user::Model) {
+    let role = Role::from_i32(user.role);
+    let user_target = BanTarget {
+        user_id: Some(user.id),
+        ..Default::default()
+    };
+    let ban = match block_on(console.moderation.active_ban(&user_target)) {
+        Ok(ban) => ban,
+        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+    };
+    let mute = match block_on(console.moderation.active_mute(user.id)) {
+        Ok(mute) => mute,
+        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+    };
+    let mut description = format!("{} is a {}.", user.handle, role);
+    if let Some(ban) = &ban {
+        description.push_str(&format!(
+            "\nBanned {}: {}",
+            describe_expiry(&ban.expires),
+            ban.reason
+        ));
+    }
+    if let Some(mute) = &mute {
+        description.push_str(&format!(
+            "\nMuted {}: {}",
+            describe_expiry(&mute.expires),
+            mute.reason
+        ));
+    }
+    let mut dialog = Dialog::text(description).title("Manage user");
+    {
+        let console = console.clone();
+        let user = user.clone();
+        dialog.add_button("Rename", move |siv| {
+            siv.pop_layer();
+            rename_dialog(siv, console.clone(), user.clone());
+        });
+    }
+    {
+        let console = console.clone();
+        let user_id = user.id;
+        dialog.add_button("Set role", move |siv| {
+            siv.pop_layer();
+            role_dialog(siv, console.clone(), user_id);
+        });
+    }
+    {
+        let console = console.clone();
+        let user_id = user.id;
+        if ban.is_some() {
+            dialog.add_button("Unban", move |siv| {
+                siv.pop_layer();
+                let result = block_on(console.moderation.lift_user_bans(&console.actor, user_id));
+                show_result(siv, result);
+                refresh_users(siv, &console);
+            });
+        } else {
+            let target = user_target.clone();
+            dialog.add_button("Ban", move |siv| {
+                siv.pop_layer();
+                sanction_dialog(siv, console.clone(), Sanction::Ban(target.clone()));
+            });
+        }
+    }
+    {
+        let console = console.clone();
+        let user_id = user.id;
+        if mute.is_some() {
+            dialog.add_button("Unmute", move |siv| {
+                siv.pop_layer();
+                let result = block_on(console.moderation.unmute(&console.actor, user_id));
+                show_result(siv, result);
+                refresh_users(siv, &console);
+            });
+        } else {
+            dialog.add_button("Mute", move |siv| {
+                siv.pop_layer();
+                sanction_dialog(siv, console.clone(), Sanction::Mute(user_id));
+            });
+        }
+    }
+    siv.add_layer(dialog.dismiss_button("Close"));
+}
+
+fn rename_dialog(siv: &mut Cursive, console: Console, user: user::Model) {
+    let edit = EditView::new()
+        .content(user.handle.clone())
+        .with_name(RENAME_EDIT)
+        .min_width(24);
+    siv.add_layer(
+        Dialog::around(edit)
+            .title(format!("Rename {}", user.handle))
+            .button("Ok", move |siv| {
+                let handle = siv
+                    .call_on_name(RENAME_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                siv.pop_layer();
+                let result = block_on(console.admin.rename_user(user.id, &handle));
+                show_result(siv, result);
+                refresh_users(siv, &console);
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn role_dialog(siv: &mut Cursive, console: Console, user_id: i32) {
+    let mut roles = SelectView::new();
+    for role in Role::ALL {
+        roles.add_item(role.to_string(), role);
+    }
+    let roles = roles.on_submit(move |siv, role| {
+        siv.pop_layer();
+        let result = block_on(console.admin.set_role(user_id, *role));
+        show_result(siv, result);
+        refresh_users(siv, &console);
+    });
+    siv.add_layer(
+        Dialog::around(roles)
+            .title("Choose a role")
+            .dismiss_button("Cancel"),
+    );
+}
+
+#[derive(Clone)]
+enum Sanction {
+    Ban(BanTarget),
+    Mute(i32),
+}
+
+/// Asks for a reason and a duration in days, where a blank duration means permanent.
+fn sanction_dialog(siv: &mut Cursive, console: Console, sanction: Sanction) {
+    let title = match &sanction {
+        Sanction::Ban(_) => "Ban",
+        Sanction::Mute(_) => "Mute",
+    };
+    let fields = LinearLayout::vertical()
+        .child(LabeledEditView::new(
+            "Reason:",
+            Some(13),
+            "",
+            |_, _, _| {},
+            |siv, _| {
+                siv.focus_name(SANCTION_DAYS_EDIT).unwrap();
+            },
+            SANCTION_REASON_EDIT,
+        ))
+        .child(LabeledEditView::new(
+            "Days:",
+            Some(13),
+            "",
+            |_, _, _| {},
+            |_, _| {},
+            SANCTION_DAYS_EDIT,
+        ))
+        .child(TextView::new("Leave days blank for a permanent sanction."))
+        .min_width(50);
+    siv.add_layer(
+        Dialog::around(fields)
+            .title(title)
+            .button("Ok", move |siv| {
+                let reason = siv
+                    .call_on_name(SANCTION_REASON_EDIT, |edit: &mut EditView| {
+                        edit.get_content()
+                    })
+                    .unwrap();
+                let days = siv
+                    .call_on_name(SANCTION_DAYS_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                let duration = if days.trim().is_empty() {
+                    None
+                } else {
+                    match days.trim().parse::<i64>() {
+                        Ok(days) if days > 0 => Some(Duration::days(days)),
+                        _ => {
+                            siv.add_layer(Dialog::info("Days must be a positive number."));
+                            return;
+                        }
+                    }
+                };
+                siv.pop_layer();
+                let result = match &sanction {
+                    Sanction::Ban(target) => block_on(console.moderation.ban(
+                        &console.actor,
+                        target.clone(),
+                        &reason,
+                        duration,
+                    )),
+                    Sanction::Mute(user_id) => block_on(console.moderation.mute(
+                        &console.actor,
+                        *user_id,
+                        &reason,
+                        duration,
+                    )),
+                };
+                show_result(siv, result);
+                refresh_users(siv, &console);
+                refresh_bans(siv, &console);
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn load_bans(console: &Console) -> Result<Vec<(String, ban::Model)>, anyhow::Error> {
+    let handles: HashMap<i32, String> = block_on(console.admin.list_users())?
+        .into_iter()
+        .map(|user| (user.id, user.handle))
+        .collect();
+    Ok(block_on(console.moderation.active_bans())?
+        .into_iter()
+        .map(|ban| {
+            let target = match (&ban.user_id, &ban.fingerprint, &ban.address) {
+                (Some(user_id), _, _) => format!(
+                    "user {}",
+                    handles.get(user_id).cloned().unwrap_or(user_id.to_string())
+                ),
+                (None, Some(fingerprint), _) => format!("key {}", fingerprint),
+                (None, None, Some(address)) => format!("address {}", address),
+                (None, None, None) => "nothing".to_string(),
+            };
+            let label = format!(
+                "{} {}: {}",
+                target,
+                describe_expiry(&ban.expires),
+                ban.reason
+            );
+            (label, ban)
+        })
+        .collect())
+}
+
+fn refresh_bans(siv: &mut Cursive, console: &Console) {
+    match load_bans(console) {
+        Ok(bans) => {
+            siv.call_on_name(BAN_LIST, |list: &mut SelectView<ban::Model>| {
+                list.clear();
+                for (label, ban) in bans {
+                    list.add_item(label, ban);
+                }
+            });
+        }
+        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+    }
+}
+
+fn bans_screen(db: Arc<Mutex<DatabaseConnection>>, actor: UserInfo) -> Box<dyn View> {
+    let console = Console::new(db, actor);
+    let mut list = SelectView::<ban::Model>::new();
+    match load_bans(&console) {
+        Ok(bans) => {
+            for (label, ban) in bans {
+                list.add_item(label, ban);
+            }
+        }
+        Err(err) => error!("Failed to list bans: {}", err),
+    }
+    let list = {
+        let console = console.clone();
+        list.on_submit(move |siv, ban: &ban::Model| {
+            let console = console.clone();
+            let ban_id = ban.id;
+            siv.add_layer(
+                Dialog::text("Lift this ban?")
+                    .button("Lift", move |siv| {
+                        siv.pop_layer();
+                        let result = block_on(console.moderation.lift_ban(&console.actor, ban_id));
+                        show_result(siv, result);
+                        refresh_bans(siv, &console);
+                    })
+                    .dismiss_button("Cancel"),
+            );
+        })
+    };
+    let console_key = console.clone();
+    let console_address = console;
+    let list = OnEventView::new(list.with_name(BAN_LIST))
+        .on_event('k', move |siv| {
+            blocklist_dialog(siv, console_key.clone(), "Key fingerprint:", |value| {
+                BanTarget {
+                    fingerprint: Some(value),
+                    ..Default::default()
+                }
+            })
+        })
+        .on_event('a', move |siv| {
+            blocklist_dialog(siv, console_address.clone(), "Address:", |value| {
+                BanTarget {
+                    address: Some(value),
+                    ..Default::default()
+                }
+            })
+        });
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Enter: lift ban  k: block a key fingerprint  a: block an address",
+        ))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
+
+/// Asks for the key fingerprint or address to block, then for the ban details.
+fn blocklist_dialog(
+    siv: &mut Cursive,
+    console: Console,
+    label: &str,
+    make_target: fn(String) -> BanTarget,
+) {
+    let edit = EditView::new().with_name(BLOCKLIST_EDIT).min_width(50);
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(label))
+                .child(edit),
+        )
+        .title("Block")
+        .button("Next", move |siv| {
+            let value = siv
+                .call_on_name(BLOCKLIST_EDIT, |edit: &mut EditView| edit.get_content())
+                .unwrap();
+            let value = value.trim();
+            if value.is_empty() {
+                return;
+            }
+            let target = make_target(value.to_string());
+            siv.pop_layer();
+            sanction_dialog(siv, console.clone(), Sanction::Ban(target));
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+fn forum_label(node: &ForumNode) -> String {
+    format!(
+        "{}{}{}",
+        "  ".repeat(node.depth),
+        node.forum.name,
+        if node.forum.question_answer {
+            " (Q&A)"
+        } else {
+            ""
+        }
+    )
+}
+
+fn refresh_forums(siv: &mut Cursive, db: Arc<Mutex<DatabaseConnection>>, select: Option<i32>) {
+    match block_on(ForumUtil::new(db).forum_tree()) {
+        Ok(tree) => {
+            siv.call_on_name(FORUM_LIST, |list: &mut SelectView<forum::Model>| {
+                let previous = list.selection().map(|forum| forum.id);
+                let select = select.or(previous);
+                list.clear();
+                for node in tree {
+                    list.add_item(forum_label(&node), node.forum);
+                }
+                let index = list.iter().position(|(_, forum)| Some(forum.id) == select);
+                if let Some(index) = index {
+                    list.set_selection(index);
+                }
+            });
+        }
+        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+    }
+}
+
+fn selected_forum(siv: &mut Cursive) -> Option<forum::Model> {
+    siv.call_on_name(FORUM_LIST, |list: &mut SelectView<forum::Model>| {
+        list.selection()
+    })
+    .flatten()
+    .map(|forum| (*forum).clone())
+}
+
+fn forums_screen(db: Arc<Mutex<DatabaseConnection>>, actor: UserInfo) -> Box<dyn View> {
+    let admin = Arc::new(AdminUtil::new(db.clone(), actor));
+    let mut list = SelectView::<forum::Model>::new();
+    match block_on(ForumUtil::new(db.clone()).forum_tree()) {
+        Ok(tree) => {
+            for node in tree {
+                list.add_item(forum_label(&node), node.forum);
+            }
+        }
+        Err(err) => error!("Failed to list forums: {}", err),
+    }
+    let list = {
+        let admin = admin.clone();
+        let db = db.clone();
+        list.on_submit(move |siv, forum| {
+            forum_dialog(siv, admin.clone(), db.clone(), Some(forum.clone()), None)
+        })
+    };
+    let list = {
+        let (admin_new, db_new) = (admin.clone(), db.clone());
+        let (admin_sub, db_sub) = (admin.clone(), db.clone());
+        let (admin_up, db_up) = (admin.clone(), db.clone());
+        let (admin_down, db_down) = (admin.clone(), db.clone());
+        let (admin_delete, db_delete) = (admin.clone(), db.clone());
+        let (admin_qa, db_qa) = (admin.clone(), db.clone());
+        OnEventView::new(list.with_name(FORUM_LIST))
+            .on_event('n', move |siv| {
+                forum_dialog(siv, admin_new.clone(), db_new.clone(), None, None)
+            })
+            .on_event('s', move |siv| {
+                if let Some(parent) = selected_forum(siv) {
+                    forum_dialog(
+                        siv,
+                        admin_sub.clone(),
+                        db_sub.clone(),
+                        None,
+                        Some(parent.id),
+                    )
+                }
+            })
+            .on_event('u', move |siv| {
+                if let Some(forum) = selected_forum(siv) {
+                    let result = block_on(admin_up.move_forum(forum.id, -1));
+                    show_result(siv, result);
+                    refresh_forums(siv, db_up.clone(), Some(forum.id));
+                }
+            })
+            .on_event('d', move |siv| {
+                if let Some(forum) = selected_forum(siv) {
+                    let result = block_on(admin_down.move_forum(forum.id, 1));
+                    show_result(siv, result);
+                    refresh_forums(siv, db_down.clone(), Some(forum.id));
+                }
+            })
+            .on_event('q', move |siv| {
+                if let Some(forum) = selected_forum(siv) {
+                    let result =
+                        block_on(admin_qa.set_question_answer(forum.id, !forum.question_answer));
+                    show_result(siv, result);
+                    refresh_forums(siv, db_qa.clone(), Some(forum.id));
+                }
+            })
+            .on_event('x', move |siv| {
+                if let Some(forum) = selected_forum(siv) {
+                    let admin = admin_delete.clone();
+                    let db = db_delete.clone();
+                    siv.add_layer(
+                        Dialog::text(format!("Delete the forum \"{}\"?", forum.name))
+                            .button("Delete", move |siv| {
+                                siv.pop_layer();
+                                let result = block_on(admin.delete_forum(forum.id));
+                                show_result(siv, result);
+                                refresh_forums(siv, db.clone(), None);
+                            })
+                            .dismiss_button("Cancel"),
+                    );
+                }
+            })
+    };
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Enter: edit  n: new forum  s: new sub-forum  u/d: move up/down  q: toggle Q&A  x: delete",
+        ))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
+
+fn forum_dialog(
+    siv: &mut Cursive,
+    admin: Arc<AdminUtil>,
+    db: Arc<Mutex<DatabaseConnection>>,
+    existing: Option<forum::Model>,
+    parent: Option<i32>,
+) {
+    let (initial_name, initial_description) = existing
+        .as_ref()
+        .map(|forum| {
+            (
+                forum.name.clone(),
+                forum.description.clone().unwrap_or_default(),
+            )
+        })
+        .unwrap_or_default();
+    let title = match (&existing, parent) {
+        (Some(forum), _) => format!("Edit {}", forum.name),
+        (None, Some(_)) => "New sub-forum".to_string(),
+        (None, None) => "New forum".to_string(),
+    };
+    let fields = LinearLayout::vertical()
+        .child(LabeledEditView::new(
+            "Name:",
+            Some(13),
+            &initial_name,
+            |_, _, _| {},
+            |siv, _| {
+                siv.focus_name(FORUM_DESCRIPTION_EDIT).unwrap();
+            },
+            FORUM_NAME_EDIT,
+        ))
+        .child(LabeledEditView::new(
+            "Description:",
+            Some(13),
+            &initial_description,
+            |_, _, _| {},
+            |_, _| {},
+            FORUM_DESCRIPTION_EDIT,
+        ))
+        .min_width(50);
+    siv.add_layer(
+        Dialog::around(fields)
+            .title(title)
+            .button("Save", move |siv| {
+                let name = siv
+                    .call_on_name(FORUM_NAME_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                let description = siv
+                    .call_on_name(FORUM_DESCRIPTION_EDIT, |edit: &mut EditView| {
+                        edit.get_content()
+                    })
+                    .unwrap();
+                siv.pop_layer();
+                let result = match &existing {
+                    Some(forum) => block_on(admin.update_forum(forum.id, &name, &description)),
+                    None => block_on(admin.create_forum(&name, &description, parent)),
+                };
+                show_result(siv, result);
+                refresh_forums(siv, db.clone(), None);
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn session_label(session: &SessionInfo) -> String {
+    let mut label = format!(
+        "#{:<5} {:16} {:24} {:16} connected {} min, idle {} min",
+        session.id,
+        session.address.as_deref().unwrap_or("-"),
+        session.handle.as_deref().unwrap_or("(anonymous)"),
+        session.screen,
+        session.started.elapsed().as_secs() / 60,
+        session.last_activity.elapsed().as_secs() / 60
+    );
+    if session.kicked.is_some() {
+        label.push_str(" [kicked]");
+    }
+    label
+}
+
+fn refresh_sessions(siv: &mut Cursive) {
+    siv.call_on_name(SESSION_LIST, |list: &mut SelectView<u64>| {
+        let selected = list.selected_id();
+        list.clear();
+        for session in list_sessions() {
+            list.add_item(session_label(&session), session.id);
+        }
+        if let Some(selected) = selected {
+            list.set_selection(selected);
+        }
+    });
+}
+
+fn sessions_screen(db: Arc<Mutex<DatabaseConnection>>, actor: UserInfo) -> Box<dyn View> {
+    let mut list = SelectView::<u64>::new();
+    for session in list_sessions() {
+        list.add_item(session_label(&session), session.id);
+    }
+    let audit = Arc::new(AuditUtil::new(db));
+    let list = list.on_submit(move |siv, id| kick_dialog(siv, audit.clone(), actor.id(), *id));
+    let list = OnEventView::new(list.with_name(SESSION_LIST)).on_event('r', refresh_sessions);
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Select a session to disconnect it. Press r to refresh the list.",
+        ))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
+
+fn kick_dialog(siv: &mut Cursive, audit: Arc<AuditUtil>, actor: Option<i32>, id: u64) {
+    let edit = EditView::new().with_name(KICK_REASON_EDIT).min_width(32);
+    siv.add_layer(
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new("Reason, shown in the log:"))
+                .child(edit),
+        )
+        .title(format!("Disconnect session #{}", id))
+        .button("Disconnect", move |siv| {
+            let reason = siv
+                .call_on_name(KICK_REASON_EDIT, |edit: &mut EditView| edit.get_content())
+                .unwrap();
+            siv.pop_layer();
+            let reason = if reason.trim().is_empty() {
+                "Disconnected by a sysop"
+            } else {
+                reason.trim()
+            };
+            let target = list_sessions()
+                .into_iter()
+                .find(|session| session.id == id)
+                .and_then(|session| session.user_id);
+            if kick_session(id, reason) {
+                info!("Kicked session {}: {}", id, reason);
+                let detail = format!("Kicked session #{}: {}", id, reason);
+                if let Err(err) =
+                    block_on(audit.record(AuditKind::Admin, actor, target, None, &detail))
+                {
+                    error!("Failed to record kick: {}", err);
+                }
+            } else {
+                siv.add_layer(Dialog::info("That session has already ended."));
+            }
+            refresh_sessions(siv);
+        })
+        .dismiss_button("Cancel"),
+    );
+}
+
+fn confirm_reindex(siv: &mut Cursive) {
+    if Library::is_reindexing() {
+        siv.add_layer(Dialog::info("The library is already being reindexed."));
+        return;
+    }
+    siv.add_layer(
+        Dialog::text("Rebuild the library search index? Searches keep using the old index until the new one is ready.")
+            .title("Reindex library")
+            .button("Reindex", |siv| {
+                siv.pop_layer();
+                let settings = get_settings();
+                spawn(async move {
+                    match Library::reindex::<String>(
+                        "library",
+                        settings.library_path,
+                        settings.library_index_path,
+                    )
+                    .await
+                    {
+                        Ok(lib) => {
+                            replace_library(lib);
+                            info!("Library reindex finished");
+                        }
+                        Err(err) => error!("Library reindex failed: {}", err),
+                    }
+                });
+                siv.add_layer(Dialog::info("Reindexing started."));
+            })
+            .dismiss_button("Cancel"),
+    );
+}
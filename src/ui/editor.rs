@@ -0,0 +1,493 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    direction::Direction,
+    event::{AnyCb, Event, EventResult, Key},
+    view::{CannotFocus, Resizable, Scrollable, Selector, ViewNotFound},
+    views::{Dialog, HideableView, LinearLayout, ResizedView, ScrollView, TextArea, TextView},
+    Cursive, Printer, Rect, Vec2, View,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::gen::draft,
+    draft::{DraftTarget, DraftUtil},
+};
+
+use super::{block_on, markup::render_markup, stack::get_stack};
+
+static AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Every editor answers to this name as well as any it's given, so they can all be found at once.
+static EDITOR_NAME: &str = "editor";
+
+type EditArea = HideableView<ResizedView<ScrollView<TextArea>>>;
+type PreviewArea = HideableView<ResizedView<ScrollView<TextView>>>;
+type SubmitCallback = Rc<dyn Fn(&mut Cursive, &str) -> Result<(), anyhow::Error>>;
+
+/// Builds the text inserted by the quote key: the quoted author's name and their post with every
+/// line prefixed by `> `.
+fn quote_text(author: &str, body: &str) -> String {
+    let mut quoted = format!("> {} wrote:\n", author);
+    for line in body.lines() {
+        quoted.push_str(&format!("> {}\n", line));
+    }
+    quoted.push('\n');
+    quoted
+}
+
+/// Where an editor keeps its draft.
+#[derive(Clone)]
+struct DraftSlot {
+    db: Arc<Mutex<DatabaseConnection>>,
+    user_id: i32,
+    target: DraftTarget,
+    /// Where the last save went. Differs from `target` after a retarget until the next save.
+    stored: DraftTarget,
+}
+
+impl DraftSlot {
+    fn moved(&self) -> bool {
+        self.stored != self.target
+    }
+
+    fn save(&mut self, body: &str) {
+        let draft_util = DraftUtil::new(self.db.clone());
+        if self.moved() {
+            if let Err(err) = block_on(draft_util.discard(self.user_id, &self.stored)) {
+                error!("Failed to discard draft for {:?}: {}", self.stored, err);
+            }
+        }
+        match block_on(draft_util.save(self.user_id, &self.target, body)) {
+            Ok(()) => self.stored = self.target.clone(),
+            Err(err) => error!("Failed to save draft for {:?}: {}", self.target, err),
+        }
+    }
+
+    fn discard(&self) {
+        let draft_util = DraftUtil::new(self.db.clone());
+        let mut targets = vec![&self.target];
+        if self.moved() {
+            targets.push(&self.stored);
+        }
+        for target in targets {
+            if let Err(err) = block_on(draft_util.discard(self.user_id, target)) {
+                error!("Failed to discard draft for {:?}: {}", target, err);
+            }
+        }
+    }
+}
+
+/// Saves what's changed in every open editor as a draft. For when the session is about to be
+/// closed and there won't be another chance.
+pub fn save_all_drafts(siv: &mut Cursive) {
+    siv.call_on_all_named(EDITOR_NAME, EditorView::save_changes);
+}
+
+/// A full-screen multi-line editor for posts and mail. Ctrl-X submits, Ctrl-P toggles a preview of
+/// the rendered markup, Ctrl-K cuts the current line (repeated cuts collect lines), Ctrl-U pastes
+/// them, Ctrl-O inserts the quoted post and Ctrl-D saves a draft. Drafts are also saved every few
+/// seconds while the text changes, and offered for restoring with Ctrl-R next time.
+pub struct EditorView {
+    inner: LinearLayout,
+    preview: bool,
+    clipboard: Vec<String>,
+    cutting: bool,
+    quote: Option<String>,
+    saved: String,
+    last_autosave: Instant,
+    draft: Option<DraftSlot>,
+    restorable: Option<draft::Model>,
+    on_submit: SubmitCallback,
+}
+
+impl EditorView {
+    /// `on_submit` is called with the text on Ctrl-X. Errors it returns are shown to the user.
+    pub fn new<F>(initial: &str, on_submit: F) -> Self
+    where
+        F: Fn(&mut Cursive, &str) -> Result<(), anyhow::Error> + 'static,
+    {
+        let area = TextArea::new().content(initial).scrollable().full_screen();
+        let preview = TextView::new("").scrollable().full_screen();
+        let mut view = Self {
+            inner: LinearLayout::vertical()
+                .child(HideableView::new(area))
+                .child(HideableView::new(preview).hidden())
+                .child(TextView::new("")),
+            preview: false,
+            clipboard: Vec::new(),
+            cutting: false,
+            quote: None,
+            saved: initial.to_string(),
+            last_autosave: Instant::now(),
+            draft: None,
+            restorable: None,
+            on_submit: Rc::new(on_submit),
+        };
+        view.update_help();
+        view
+    }
+
+    /// Keeps drafts of what's written for `target`. An earlier draft is offered for restoring if
+    /// the editor starts out empty.
+    pub fn draft(
+        mut self,
+        db: Arc<Mutex<DatabaseConnection>>,
+        user_id: Option<i32>,
+        target: DraftTarget,
+    ) -> Self {
+        let user_id = match user_id {
+            Some(user_id) => user_id,
+            None => return self,
+        };
+        if self.saved.is_empty() {
+            match block_on(DraftUtil::new(db.clone()).load(user_id, &target)) {
+                Ok(restorable) => self.restorable = restorable,
+                Err(err) => error!("Failed to load draft for {:?}: {}", target, err),
+            }
+        }
+        self.draft = Some(DraftSlot {
+            db,
+            user_id,
+            stored: target.clone(),
+            target,
+        });
+        self.update_help();
+        self
+    }
+
+    /// Points the draft at `target` from now on, e.g. when a message's recipient changes. A draft
+    /// that was already saved moves there with the next save.
+    pub fn retarget(&mut self, target: DraftTarget) {
+        if let Some(draft) = &mut self.draft {
+            draft.target = target;
+        }
+    }
+
+    /// Makes `body` by `author` available to quote.
+    pub fn quote(mut self, author: &str, body: &str) -> Self {
+        self.quote = Some(quote_text(author, body));
+        self.update_help();
+        self
+    }
+
+    fn content(&mut self) -> String {
+        self.area().get_content().to_string()
+    }
+
+    fn update_help(&mut self) {
+        let mut help = if self.preview {
+            "Ctrl-P: back to editing  Ctrl-X: submit".to_string()
+        } else {
+            "Ctrl-X: submit  Ctrl-P: preview  Ctrl-K: cut line  Ctrl-U: paste".to_string()
+        };
+        if !self.preview && self.quote.is_some() {
+            help.push_str("  Ctrl-O: quote");
+        }
+        if self.draft.is_some() {
+            help.push_str("  Ctrl-D: save draft");
+        }
+        if let Some(restorable) = &self.restorable {
+            help.push_str(&format!(
+                "\nCtrl-R: restore your draft from {} UTC (writing something new replaces it)",
+                restorable.updated
+            ));
+        }
+        help.push_str("  Esc: leave");
+        self.inner
+            .get_child_mut(2)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<TextView>()
+            .unwrap()
+            .set_content(help);
+    }
+
+    fn area(&mut self) -> &mut TextArea {
+        self.inner
+            .get_child_mut(0)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<EditArea>()
+            .unwrap()
+            .get_inner_mut()
+            .get_inner_mut()
+            .get_inner_mut()
+    }
+
+    fn preview_area(&mut self) -> &mut PreviewArea {
+        self.inner
+            .get_child_mut(1)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<PreviewArea>()
+            .unwrap()
+    }
+
+    fn toggle_preview(&mut self) {
+        self.preview = !self.preview;
+        let preview = self.preview;
+        let rendered = render_markup(&self.content(), &HashSet::new(), None);
+        let preview_area = self.preview_area();
+        preview_area
+            .get_inner_mut()
+            .get_inner_mut()
+            .get_inner_mut()
+            .set_content(rendered);
+        preview_area.set_visible(preview);
+        self.inner
+            .get_child_mut(0)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<EditArea>()
+            .unwrap()
+            .set_visible(!preview);
+        // A short preview can't take focus, which is fine since there's nothing to scroll.
+        let _ = self.inner.set_focus_index(if preview { 1 } else { 0 });
+        self.update_help();
+    }
+
+    /// Returns the byte range of the line the cursor is on, including its newline.
+    fn current_line(&mut self) -> (usize, usize) {
+        let area = self.area();
+        let content = area.get_content();
+        let cursor = area.cursor();
+        let start = content[..cursor].rfind('\n').map_or(0, |index| index + 1);
+        let end = content[cursor..]
+            .find('\n')
+            .map_or(content.len(), |index| cursor + index + 1);
+        (start, end)
+    }
+
+    fn cut_line(&mut self) {
+        let (start, end) = self.current_line();
+        let content = self.content();
+        if start == end {
+            return;
+        }
+        if !self.cutting {
+            self.clipboard.clear();
+        }
+        self.clipboard
+            .push(content[start..end].trim_end_matches('\n').to_string());
+        let area = self.area();
+        area.set_content(format!("{}{}", &content[..start], &content[end..]));
+        area.set_cursor(start);
+    }
+
+    fn paste(&mut self) {
+        if self.clipboard.is_empty() {
+            return;
+        }
+        let (start, _) = self.current_line();
+        let content = self.content();
+        let pasted: String = self
+            .clipboard
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        let area = self.area();
+        area.set_content(format!(
+            "{}{}{}",
+            &content[..start],
+            pasted,
+            &content[start..]
+        ));
+        area.set_cursor(start + pasted.len());
+    }
+
+    fn insert_quote(&mut self) {
+        let mut quote = match &self.quote {
+            Some(quote) => quote.clone(),
+            None => return,
+        };
+        let content = self.content();
+        let area = self.area();
+        let cursor = area.cursor();
+        if cursor > 0 && !content[..cursor].ends_with('\n') {
+            quote.insert(0, '\n');
+        }
+        area.set_content(format!(
+            "{}{}{}",
+            &content[..cursor],
+            quote,
+            &content[cursor..]
+        ));
+        area.set_cursor(cursor + quote.len());
+    }
+
+    fn restore(&mut self) {
+        if let Some(restorable) = self.restorable.take() {
+            let area = self.area();
+            area.set_content(restorable.body.clone());
+            area.set_cursor(restorable.body.len());
+            self.saved = restorable.body;
+            self.update_help();
+        }
+    }
+
+    fn save(&mut self) -> bool {
+        let content = self.content();
+        match &mut self.draft {
+            Some(draft) => draft.save(&content),
+            None => return false,
+        }
+        self.saved = content;
+        self.last_autosave = Instant::now();
+        if self.restorable.take().is_some() {
+            self.update_help();
+        }
+        true
+    }
+
+    /// Saves a draft if the text or its target has changed since the last save.
+    fn save_changes(&mut self) {
+        let moved = self.draft.as_ref().map_or(false, DraftSlot::moved);
+        if self.draft.is_some() && (moved || self.content() != self.saved) {
+            self.save();
+        }
+    }
+
+    fn autosave(&mut self) {
+        if self.last_autosave.elapsed() >= AUTOSAVE_INTERVAL {
+            self.save_changes();
+        }
+    }
+
+    fn submit(&mut self) -> EventResult {
+        let content = self.content();
+        let on_submit = self.on_submit.clone();
+        let draft = self.draft.clone();
+        EventResult::with_cb(move |siv| match on_submit(siv, &content) {
+            Ok(()) => {
+                if let Some(draft) = &draft {
+                    draft.discard();
+                }
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        })
+    }
+
+    /// Asks what to do with the writing so far. Without a draft target, unchanged text is left
+    /// without asking.
+    fn confirm_leave(&mut self) -> EventResult {
+        let content = self.content();
+        let draft = match self.draft.clone() {
+            Some(draft) if !content.trim().is_empty() => draft,
+            Some(_) => return EventResult::Ignored,
+            None if content == self.saved => return EventResult::Ignored,
+            None => {
+                return EventResult::with_cb(|siv| {
+                    let dialog = Dialog::text("Leave without saving your changes?")
+                        .button("Leave", |siv| {
+                            siv.pop_layer();
+                            get_stack(siv).pop(siv).unwrap();
+                        })
+                        .dismiss_button("Keep writing");
+                    siv.add_layer(dialog);
+                })
+            }
+        };
+        EventResult::with_cb(move |siv| {
+            let keep = {
+                let draft = draft.clone();
+                let content = content.clone();
+                move |siv: &mut Cursive| {
+                    draft.clone().save(&content);
+                    siv.pop_layer();
+                    get_stack(siv).pop(siv).unwrap();
+                }
+            };
+            let discard = {
+                let draft = draft.clone();
+                move |siv: &mut Cursive| {
+                    draft.discard();
+                    siv.pop_layer();
+                    get_stack(siv).pop(siv).unwrap();
+                }
+            };
+            let dialog = Dialog::text("Keep what you've written as a draft?")
+                .button("Keep draft", keep)
+                .button("Discard", discard)
+                .dismiss_button("Keep writing");
+            siv.add_layer(dialog);
+        })
+    }
+}
+
+impl View for EditorView {
+    fn draw(&self, printer: &Printer) {
+        self.inner.draw(printer)
+    }
+    fn layout(&mut self, size: Vec2) {
+        self.inner.layout(size)
+    }
+    fn needs_relayout(&self) -> bool {
+        self.inner.needs_relayout()
+    }
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        self.inner.required_size(constraint)
+    }
+    fn on_event(&mut self, event: Event) -> EventResult {
+        let cutting = event == Event::CtrlChar('k');
+        let result = match event {
+            Event::CtrlChar('x') => self.submit(),
+            Event::CtrlChar('p') => {
+                self.toggle_preview();
+                EventResult::Consumed(None)
+            }
+            Event::Key(Key::Esc) => self.confirm_leave(),
+            _ if self.preview => self.inner.on_event(event),
+            Event::CtrlChar('k') => {
+                self.cut_line();
+                EventResult::Consumed(None)
+            }
+            Event::CtrlChar('u') => {
+                self.paste();
+                EventResult::Consumed(None)
+            }
+            Event::CtrlChar('o') => {
+                self.insert_quote();
+                EventResult::Consumed(None)
+            }
+            Event::CtrlChar('d') => {
+                if self.save() {
+                    EventResult::with_cb(|siv| siv.add_layer(Dialog::info("Draft saved.")))
+                } else {
+                    EventResult::Ignored
+                }
+            }
+            Event::CtrlChar('r') => {
+                self.restore();
+                EventResult::Consumed(None)
+            }
+            _ => self.inner.on_event(event),
+        };
+        self.cutting = cutting;
+        // Refresh events keep this going while the user pauses.
+        self.autosave();
+        result
+    }
+    fn call_on_any(&mut self, selector: &Selector, cb: AnyCb) {
+        match selector {
+            Selector::Name(name) if *name == EDITOR_NAME => cb(self),
+            _ => self.inner.call_on_any(selector, cb),
+        }
+    }
+    fn focus_view(&mut self, selector: &Selector) -> Result<EventResult, ViewNotFound> {
+        self.inner.focus_view(selector)
+    }
+    fn take_focus(&mut self, source: Direction) -> Result<EventResult, CannotFocus> {
+        self.inner.take_focus(source)
+    }
+    fn important_area(&self, view_size: Vec2) -> Rect {
+        self.inner.important_area(view_size)
+    }
+    fn type_name(&self) -> &'static str {
+        "EditorView"
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        direction::Orientation,
+        event::{AnyCb, Event, EventResult},
+        view::{Finder, Nameable, Resizable, Scrollable, Selector},
+        views::{
+            Dialog, DummyView, EditView, LinearLayout, OnEventView, ResizedView, ScrollView,
+            SelectView, TextView,
+        },
+        Printer, Vec2, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::gen::forum,
+    draft::DraftTarget,
+    forum::{unread_generation, ForumNode, ForumUtil, NewActivity, ThreadSummary},
+    throttle::{self, Action},
+    user::UserInfo,
+};
+
+use super::{
+    super::{
+        block_on, editor::EditorView, get_user, labeled_edit_view::LabeledEditView,
+        markup::MARKUP_HELP, stack::get_stack,
+    },
+    live::LiveView,
+    search::search_screen,
+    thread::thread_screen,
+};
+
+static FORUM_LIST: &str = "forum_list";
+static NEW_POSTS_LIST: &str = "new_posts_list";
+static NEW_THREAD_TITLE_EDIT: &str = "new_thread_title";
+static NEW_THREAD_BODY_EDIT: &str = "new_thread_body";
+
+fn forum_label(node: &ForumNode, unread: usize) -> String {
+    let mut label = format!("{}{}", "  ".repeat(node.depth), node.forum.name);
+    if let Some(description) = &node.forum.description {
+        label.push_str(&format!(": {}", description));
+    }
+    if unread > 0 {
+        label.push_str(&format!(" ({} unread)", unread));
+    }
+    label
+}
+
+fn refresh_forums(
+    list: &mut SelectView<ForumNode>,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: Option<&UserInfo>,
+) {
+    let forum_util = ForumUtil::new(db);
+    let forums = match block_on(forum_util.forum_tree()) {
+        Ok(forums) => forums,
+        Err(err) => {
+            error!("Failed to load forums: {}", err);
+            return;
+        }
+    };
+    let unread = match user {
+        Some(user) => block_on(forum_util.unread_by_forum(user)).unwrap_or_default(),
+        None => HashMap::new(),
+    };
+    let selected = list.selected_id();
+    list.clear();
+    for node in forums {
+        let label = forum_label(&node, unread.get(&node.forum.id).copied().unwrap_or(0));
+        list.add_item(label, node);
+    }
+    if let Some(selected) = selected {
+        list.set_selection(selected);
+    }
+}
+
+pub fn forum_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>) -> Box<dyn View> {
+    let forums = match block_on(ForumUtil::new(db.clone()).forum_tree()) {
+        Ok(forums) => forums,
+        Err(err) => return Box::new(TextView::new(err.to_string())),
+    };
+    if forums.is_empty() {
+        return Box::new(TextView::new(
+            "There are no forums yet. Ask the sysop to create one.",
+        ));
+    }
+    let user = get_user(db.clone(), key.clone()).ok();
+    let list = {
+        let db = db.clone();
+        let key = key.clone();
+        SelectView::<ForumNode>::new().on_submit(move |siv, node: &ForumNode| {
+            get_stack(siv)
+                .push(Box::new(ThreadListView::new(
+                    db.clone(),
+                    key.clone(),
+                    node.forum.clone(),
+                )))
+                .unwrap();
+        })
+    };
+    let list = {
+        let db = db.clone();
+        OnEventView::new(list.with_name(FORUM_LIST)).on_event('/', move |siv| {
+            get_stack(siv)
+                .push(search_screen(db.clone(), key.clone(), None))
+                .unwrap();
+        })
+    };
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Forums. Enter: open  /: search  Esc: back"))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    // Unread counts change as the user reads, so keep them current.
+    let reader_id = user.as_ref().and_then(|user| user.id());
+    Box::new(LiveView::new(reader_id, layout, move |layout| {
+        layout.call_on_name(FORUM_LIST, |list: &mut SelectView<ForumNode>| {
+            refresh_forums(list, db.clone(), user.as_ref())
+        });
+    }))
+}
+
+/// Threads with posts the user hasn't read since their last visit, across all forums.
+pub fn new_posts_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+) -> Box<dyn View> {
+    let user =
+        match get_user(db.clone(), key.clone()) {
+            Ok(user) => user,
+            Err(_) => return Box::new(TextView::new(
+                "Unread tracking is only available to registered users. Set up your profile first.",
+            )),
+        };
+    let list = {
+        let db = db.clone();
+        SelectView::<NewActivity>::new().on_submit(move |siv, activity: &NewActivity| {
+            get_stack(siv)
+                .push(thread_screen(
+                    db.clone(),
+                    key.clone(),
+                    activity.summary.thread.id,
+                    None,
+                ))
+                .unwrap();
+        })
+    };
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "New posts since your last visit. Enter: read from the first unread post  Esc: back",
+        ))
+        .child(DummyView)
+        .child(list.with_name(NEW_POSTS_LIST).scrollable().full_screen());
+    Box::new(LiveView::new(user.id(), layout, move |layout| {
+        let activity = match block_on(ForumUtil::new(db.clone()).new_activity(&user)) {
+            Ok(activity) => activity,
+            Err(err) => {
+                error!("Failed to load new posts: {}", err);
+                return;
+            }
+        };
+        layout.call_on_name(NEW_POSTS_LIST, |list: &mut SelectView<NewActivity>| {
+            list.clear();
+            for activity in activity {
+                let label = format!(
+                    "{:16} {:40} {:4} new  {}",
+                    activity.forum,
+                    activity.summary.thread.name,
+                    activity.summary.unread,
+                    activity.summary.last_post.clone().unwrap_or_default()
+                );
+                list.add_item(label, activity);
+            }
+        });
+    }))
+}
+
+/// The threads of one forum. Reloads itself whenever anyone posts or the user reads something.
+pub struct ThreadListView {
+    inner: LinearLayout,
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    user: Option<UserInfo>,
+    forum: forum::Model,
+    generation: u64,
+}
+
+impl ThreadListView {
+    pub fn new(
+        db: Arc<Mutex<DatabaseConnection>>,
+        key: Option<PublicKey>,
+        forum: forum::Model,
+    ) -> Self {
+        let list = {
+            let db = db.clone();
+            let key = key.clone();
+            SelectView::<ThreadSummary>::new().on_submit(move |siv, summary: &ThreadSummary| {
+                get_stack(siv)
+                    .push(thread_screen(
+                        db.clone(),
+                        key.clone(),
+                        summary.thread.id,
+                        None,
+                    ))
+                    .unwrap();
+            })
+        };
+        let inner = LinearLayout::vertical()
+            .child(TextView::new(format!(
+                "{}. !: sticky  #: locked  Enter: read  n: new thread  /: search  Esc: back",
+                forum.name
+            )))
+            .child(DummyView)
+            .child(list.scrollable().full_screen());
+        let user = get_user(db.clone(), key.clone()).ok();
+        let mut view = Self {
+            inner,
+            db,
+            key,
+            user,
+            forum,
+            generation: 0,
+        };
+        view.reload();
+        view
+    }
+
+    fn get_list(&mut self) -> &mut SelectView<ThreadSummary> {
+        self.inner
+            .get_child_mut(2)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<ResizedView<ScrollView<SelectView<ThreadSummary>>>>()
+            .unwrap()
+            .get_inner_mut()
+            .get_inner_mut()
+    }
+
+    fn current_generation(&self) -> u64 {
+        unread_generation(self.user.as_ref().and_then(|user| user.id()))
+    }
+
+    fn reload(&mut self) {
+        self.generation = self.current_generation();
+        let forum_util = ForumUtil::new(self.db.clone());
+        let threads = match block_on(forum_util.threads(self.user.as_ref(), self.forum.id)) {
+            Ok(threads) => threads,
+            Err(err) => {
+                error!("Failed to load threads: {}", err);
+                return;
+            }
+        };
+        let list = self.get_list();
+        let selected = list.selection().map(|summary| summary.thread.id);
+        list.clear();
+        for summary in threads {
+            let unread = match summary.unread {
+                0 => String::new(),
+                unread => format!("{} new", unread),
+            };
+            let label = format!(
+                "{}{:40} {:16} {:4} {:8} {}",
+                match (summary.thread.sticky, summary.thread.locked.is_some()) {
+                    (true, true) => "!#",
+                    (true, false) => "! ",
+                    (false, true) => " #",
+                    (false, false) => "  ",
+                },
+                summary.thread.name,
+                summary.author,
+                summary.posts,
+                unread,
+                summary.last_post.clone().unwrap_or_default()
+            );
+            list.add_item(label, summary);
+        }
+        let index = list
+            .iter()
+            .position(|(_, summary)| Some(summary.thread.id) == selected);
+        if let Some(index) = index {
+            list.set_selection(index);
+        }
+    }
+}
+
+impl View for ThreadListView {
+    fn draw(&self, printer: &Printer) {
+        self.inner.draw(printer)
+    }
+    fn needs_relayout(&self) -> bool {
+        self.generation != self.current_generation() || self.inner.needs_relayout()
+    }
+    fn on_event(&mut self, event: Event) -> EventResult {
+        if event == Event::Char('n') {
+            let db = self.db.clone();
+            let key = self.key.clone();
+            let forum = self.forum.clone();
+            return EventResult::with_cb(move |siv| match get_user(db.clone(), key.clone()) {
+                Ok(user) => {
+                    get_stack(siv)
+                        .push(new_thread_screen(db.clone(), user, forum.clone()))
+                        .unwrap();
+                }
+                Err(_) => siv.add_layer(Dialog::info("Set up your profile before posting.")),
+            });
+        }
+        if event == Event::Char('/') {
+            let db = self.db.clone();
+            let key = self.key.clone();
+            let forum = self.forum.clone();
+            return EventResult::with_cb(move |siv| {
+                get_stack(siv)
+                    .push(search_screen(db.clone(), key.clone(), Some(forum.clone())))
+                    .unwrap();
+            });
+        }
+        self.inner.on_event(event)
+    }
+    fn call_on_any(&mut self, selector: &Selector, cb: AnyCb) {
+        self.inner.call_on_any(selector, cb)
+    }
+    fn type_name(&self) -> &'static str {
+        "ThreadListView"
+    }
+    fn layout(&mut self, size: Vec2) {
+        if self.generation != self.current_generation() {
+            self.reload();
+        }
+        self.inner.layout(size)
+    }
+}
+
+fn new_thread_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    forum: forum::Model,
+) -> Box<dyn View> {
+    let min_width = 10;
+    let title_edit = LabeledEditView::new(
+        "Title:",
+        Some(min_width),
+        "",
+        |_, _, _| {},
+        |siv, _| {
+            siv.focus_name(NEW_THREAD_BODY_EDIT).unwrap();
+        },
+        NEW_THREAD_TITLE_EDIT,
+    );
+    let user_id = user.id();
+    let target = DraftTarget::NewThread { forum: forum.id };
+    let editor = {
+        let db = db.clone();
+        let forum_id = forum.id;
+        EditorView::new("", move |siv, body| {
+            throttle::check(get_stack(siv).session_id(), Action::Post)?;
+            let title = siv
+                .call_on_name(NEW_THREAD_TITLE_EDIT, |edit: &mut EditView| {
+                    edit.get_content()
+                })
+                .unwrap();
+            block_on(ForumUtil::new(db.clone()).create_thread(&user, forum_id, &title, body))?;
+            get_stack(siv).pop(siv).unwrap();
+            Ok(())
+        })
+    }
+    .draft(db, user_id, target);
+    let layout = LinearLayout::new(Orientation::Vertical)
+        .child(TextView::new(format!(
+            "New thread in {}. Enter the title, then write the first post below.\nFormatting: {}",
+            forum.name, MARKUP_HELP
+        )))
+        .child(title_edit.full_width())
+        .child(editor.with_name(NEW_THREAD_BODY_EDIT));
+    Box::new(layout.full_screen())
+}
@@ -0,0 +1,70 @@
+use ssh_ui::cursive::{
+    direction::Direction,
+    event::{AnyCb, Event, EventResult},
+    view::{CannotFocus, Selector, ViewNotFound},
+    Printer, Rect, Vec2, View,
+};
+
+use crate::forum::unread_generation;
+
+/// Wraps a view and calls `refresh` on it whenever forum content or what `reader_id` has read has
+/// changed since it was last refreshed.
+pub struct LiveView<V: View> {
+    inner: V,
+    reader_id: Option<i32>,
+    generation: u64,
+    refresh: Box<dyn Fn(&mut V)>,
+}
+
+impl<V: View> LiveView<V> {
+    pub fn new<F>(reader_id: Option<i32>, mut inner: V, refresh: F) -> Self
+    where
+        F: Fn(&mut V) + 'static,
+    {
+        let generation = unread_generation(reader_id);
+        refresh(&mut inner);
+        Self {
+            inner,
+            reader_id,
+            generation,
+            refresh: Box::new(refresh),
+        }
+    }
+}
+
+impl<V: View> View for LiveView<V> {
+    fn draw(&self, printer: &Printer) {
+        self.inner.draw(printer)
+    }
+    fn layout(&mut self, size: Vec2) {
+        if self.generation != unread_generation(self.reader_id) {
+            self.generation = unread_generation(self.reader_id);
+            (self.refresh)(&mut self.inner);
+        }
+        self.inner.layout(size)
+    }
+    fn needs_relayout(&self) -> bool {
+        self.generation != unread_generation(self.reader_id) || self.inner.needs_relayout()
+    }
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        self.inner.required_size(constraint)
+    }
+    fn on_event(&mut self, event: Event) -> EventResult {
+        self.inner.on_event(event)
+    }
+    fn call_on_any(&mut self, selector: &Selector, cb: AnyCb) {
+        self.inner.call_on_any(selector, cb)
+    }
+    fn focus_view(&mut self, selector: &Selector) -> Result<EventResult, ViewNotFound> {
+        self.inner.focus_view(selector)
+    }
+    fn take_focus(&mut self, source: Direction) -> Result<EventResult, CannotFocus> {
+        self.inner.take_focus(source)
+    }
+    fn important_area(&self, view_size: Vec2) -> Rect {
+        self.inner.important_area(view_size)
+    }
+    fn type_name(&self) -> &'static str {
+        self.inner.type_name()
+    }
+}
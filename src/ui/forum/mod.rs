@@ -1,11 +1,13 @@
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex as StdMutex};
 
 use sea_orm::DatabaseConnection;
 use ssh_ui::{
     cursive::{
         event::{AnyCb, Event, EventResult},
+        utils::markup::StyledString,
         view::Selector,
-        views::{EditView, LinearLayout, ResizedView, TextView},
+        views::{Dialog, EditView, LinearLayout, ResizedView, TextView},
         Printer, Vec2, View,
     },
     russh_keys::key::PublicKey,
@@ -18,16 +20,74 @@ use tokio::{
     },
 };
 
-use super::{get_user, labeled_edit_view::LabeledEditView};
+use crate::{
+    mention::MentionUtil,
+    moderation::{describe_expiry, ModerationUtil},
+    profile::ProfileUtil,
+    session::add_session_task,
+    throttle::{self, Action},
+};
+
+use super::{
+    block_on, get_user, labeled_edit_view::LabeledEditView, markup::render_markup,
+    profile::page::profile_page, stack::get_stack,
+};
+
+static WHOIS_COMMAND: &str = "/whois ";
+
+pub(crate) mod board;
+pub(crate) mod live;
+pub(crate) mod moderate;
+pub(crate) mod poll;
+pub(crate) mod search;
+pub(crate) mod thread;
 
 lazy_static! {
-    static ref CHAT_SENDERS: Mutex<Vec<Sender<String>>> = Mutex::new(Vec::new());
+    static ref CHAT_MEMBERS: StdMutex<Vec<ChatMember>> = StdMutex::new(Vec::new());
+}
+
+/// A session in the chat room, and where to send it lines.
+struct ChatMember {
+    session_id: u64,
+    handle: String,
+    sender: Sender<ChatLine>,
+}
+
+/// Takes a session out of chat and tells everyone else it left. Does nothing for sessions that
+/// aren't in chat, so it's safe to call whenever a session might be going away.
+pub(crate) fn leave_chat(session_id: u64) {
+    let mut members = CHAT_MEMBERS.lock().unwrap();
+    let leaving = match members
+        .iter()
+        .position(|member| member.session_id == session_id)
+    {
+        Some(index) => members.remove(index),
+        None => return,
+    };
+    // Never block here: this runs while sessions are torn down, possibly on the runtime.
+    for member in members.iter() {
+        let _ = member.sender.try_send(ChatLine {
+            handle: None,
+            text: format!("<leave> {}", leaving.handle),
+            mentions: HashSet::new(),
+        });
+    }
+}
+
+/// A line of chat. `mentions` holds the handles in `text` that resolved to real users.
+#[derive(Debug, Clone)]
+struct ChatLine {
+    handle: Option<String>,
+    text: String,
+    mentions: HashSet<String>,
 }
 
 pub struct ChatBoxView {
     inner: ResizedView<LinearLayout>,
-    messages: Arc<Mutex<Vec<String>>>,
-    _handle: tokio::task::JoinHandle<()>,
+    messages: Arc<Mutex<Vec<ChatLine>>>,
+    own_handle: String,
+    session_id: u64,
+    task: tokio::task::JoinHandle<()>,
 }
 
 impl ChatBoxView {
@@ -45,11 +105,14 @@ impl ChatBoxView {
     pub fn new(
         db: Arc<Mutex<DatabaseConnection>>,
         user: Option<PublicKey>,
+        session_id: u64,
         relayout_sender: Sender<()>,
     ) -> Self {
         let mut inner = LinearLayout::vertical();
+        let key = user.clone();
         let user = get_user(db.clone(), user).unwrap();
         let user_cloned = user.clone();
+        let moderation = ModerationUtil::new(db.clone());
         inner.add_child(ResizedView::with_full_screen(TextView::new("Chat box")));
         inner.add_child(LabeledEditView::new(
             "Message: ",
@@ -57,22 +120,71 @@ impl ChatBoxView {
             "",
             |_, _, _| {},
             move |siv, message| {
-                let message = format!("{}: {}", user_cloned.clone().handle, message);
-                let mut senders = CHAT_SENDERS.blocking_lock();
-                *senders = senders
-                    .clone()
-                    .into_iter()
-                    .filter(|sender| sender.blocking_send(message.to_string()).is_ok())
-                    .collect();
+                if let Some(handle) = message.strip_prefix(WHOIS_COMMAND) {
+                    match block_on(ProfileUtil::new(db.clone()).find_by_handle(handle)) {
+                        Ok(Some(user_id)) => {
+                            siv.find_name::<EditView>("chat_edit_box")
+                                .unwrap()
+                                .set_content("");
+                            get_stack(siv)
+                                .push(profile_page(db.clone(), key.clone(), user_id))
+                                .unwrap();
+                        }
+                        Ok(None) => siv.add_layer(Dialog::info(format!(
+                            "Nobody is called {}.",
+                            handle.trim()
+                        ))),
+                        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                    }
+                    return;
+                }
+                if let Some(user_id) = user_cloned.id() {
+                    if let Ok(Some(mute)) = block_on(moderation.active_mute(user_id)) {
+                        siv.add_layer(Dialog::info(format!(
+                            "You have been muted {}.\n\nReason: {}",
+                            describe_expiry(&mute.expires),
+                            mute.reason
+                        )));
+                        return;
+                    }
+                }
+                if let Err(err) = throttle::check(session_id, Action::Chat) {
+                    siv.add_layer(Dialog::info(err.to_string()));
+                    return;
+                }
+                let mentions = block_on(MentionUtil::new(db.clone()).record(
+                    &user_cloned,
+                    message,
+                    None,
+                    "chat",
+                ))
+                .unwrap_or_default();
+                let message = ChatLine {
+                    handle: Some(user_cloned.handle.clone()),
+                    text: message.to_string(),
+                    mentions,
+                };
+                CHAT_MEMBERS
+                    .lock()
+                    .unwrap()
+                    .retain(|member| member.sender.blocking_send(message.clone()).is_ok());
                 siv.find_name::<EditView>("chat_edit_box")
                     .unwrap()
                     .set_content("");
             },
             "chat_edit_box",
         ));
+        inner.add_child(TextView::new(format!(
+            "{}<handle>: see someone's profile",
+            WHOIS_COMMAND
+        )));
         let (message_sender, mut message_receiver) = channel(5);
-        let mut senders = CHAT_SENDERS.blocking_lock();
-        senders.push(message_sender);
+        let mut members = CHAT_MEMBERS.lock().unwrap();
+        members.push(ChatMember {
+            session_id,
+            handle: user.handle.clone(),
+            sender: message_sender,
+        });
         inner.set_focus_index(1).unwrap();
         let messages = Arc::new(Mutex::new(Vec::new()));
         let messages_cloned = messages.clone();
@@ -81,24 +193,40 @@ impl ChatBoxView {
                 match message_receiver.recv().await {
                     Some(message) => {
                         messages_cloned.lock().await.push(message);
-                        relayout_sender.send(()).await.unwrap();
+                        if relayout_sender.send(()).await.is_err() {
+                            break;
+                        }
                     }
                     None => break,
                 }
             }
         });
+        add_session_task(session_id, handle.abort_handle());
 
-        senders.iter().for_each(|sender| {
-            let _ = sender.blocking_send(format!("<join> {}", user.handle));
+        members.iter().for_each(|member| {
+            let _ = member.sender.blocking_send(ChatLine {
+                handle: None,
+                text: format!("<join> {}", user.handle),
+                mentions: HashSet::new(),
+            });
         });
         Self {
             inner: ResizedView::with_full_screen(inner),
             messages,
-            _handle: handle,
+            own_handle: user.handle,
+            session_id,
+            task: handle,
         }
     }
 }
 
+impl Drop for ChatBoxView {
+    fn drop(&mut self) {
+        leave_chat(self.session_id);
+        self.task.abort();
+    }
+}
+
 impl View for ChatBoxView {
     fn draw(&self, printer: &Printer) {
         self.inner.draw(printer)
@@ -116,7 +244,20 @@ impl View for ChatBoxView {
         "ChatBoxView"
     }
     fn layout(&mut self, size: Vec2) {
-        let text = self.messages.blocking_lock().join("\n");
+        let mut text = StyledString::new();
+        for (index, line) in self.messages.blocking_lock().iter().enumerate() {
+            if index > 0 {
+                text.append_plain("\n");
+            }
+            if let Some(handle) = &line.handle {
+                text.append_plain(format!("{}: ", handle));
+            }
+            text.append(render_markup(
+                &line.text,
+                &line.mentions,
+                Some(&self.own_handle),
+            ));
+        }
         self.get_text_view().set_content(text);
         self.inner.layout(size)
     }
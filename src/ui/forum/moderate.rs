@@ -0,0 +1,264 @@
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        view::{Nameable, Resizable, Scrollable},
+        views::{Dialog, DummyView, EditView, LinearLayout, SelectView, TextView},
+        Cursive, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::gen::thread,
+    forum::ForumUtil,
+    moderation::ModerationUtil,
+    user::{Role, UserInfo},
+};
+
+use super::{
+    super::{block_on, get_user, stack::get_stack},
+    thread::thread_screen,
+};
+
+static SPLIT_TITLE_EDIT: &str = "moderate_split_title";
+static LOG_LENGTH: u64 = 200;
+
+fn show_result(siv: &mut Cursive, result: Result<(), anyhow::Error>) {
+    if let Err(err) = result {
+        siv.add_layer(Dialog::info(err.to_string()));
+    }
+}
+
+/// Offers the moderator actions for `thread`. `selected` are the posts marked for splitting.
+pub(super) fn moderate_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    actor: UserInfo,
+    thread: thread::Model,
+    selected: Vec<i32>,
+) {
+    let moderation = Arc::new(ModerationUtil::new(db.clone()));
+    let mut dialog = Dialog::text(format!("Moderate \"{}\".", thread.name)).title("Moderate");
+    {
+        let moderation = moderation.clone();
+        let actor = actor.clone();
+        let locked = thread.locked.is_some();
+        let label = if locked { "Unlock" } else { "Lock" };
+        dialog.add_button(label, move |siv| {
+            siv.pop_layer();
+            let result = block_on(moderation.set_locked(&actor, thread.id, !locked));
+            show_result(siv, result);
+        });
+    }
+    {
+        let moderation = moderation.clone();
+        let actor = actor.clone();
+        let sticky = thread.sticky;
+        let label = if sticky { "Unsticky" } else { "Sticky" };
+        dialog.add_button(label, move |siv| {
+            siv.pop_layer();
+            let result = block_on(moderation.set_sticky(&actor, thread.id, !sticky));
+            show_result(siv, result);
+        });
+    }
+    {
+        let db = db.clone();
+        let moderation = moderation.clone();
+        let actor = actor.clone();
+        let thread = thread.clone();
+        dialog.add_button("Move", move |siv| {
+            siv.pop_layer();
+            move_dialog(siv, db.clone(), moderation.clone(), actor.clone(), &thread);
+        });
+    }
+    {
+        let db = db.clone();
+        let moderation = moderation.clone();
+        let actor = actor.clone();
+        let thread = thread.clone();
+        dialog.add_button("Merge", move |siv| {
+            siv.pop_layer();
+            merge_dialog(
+                siv,
+                db.clone(),
+                key.clone(),
+                moderation.clone(),
+                actor.clone(),
+                &thread,
+            );
+        });
+    }
+    dialog.add_button("Split", move |siv| {
+        siv.pop_layer();
+        split_dialog(
+            siv,
+            moderation.clone(),
+            actor.clone(),
+            thread.id,
+            selected.clone(),
+        );
+    });
+    siv.add_layer(dialog.dismiss_button("Close"));
+}
+
+fn move_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    moderation: Arc<ModerationUtil>,
+    actor: UserInfo,
+    thread: &thread::Model,
+) {
+    let tree = match block_on(ForumUtil::new(db).forum_tree()) {
+        Ok(tree) => tree,
+        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+    };
+    let mut forums = SelectView::new();
+    for node in tree {
+        if node.forum.id != thread.forum {
+            let label = format!("{}{}", "  ".repeat(node.depth), node.forum.name);
+            forums.add_item(label, node.forum.id);
+        }
+    }
+    let thread_id = thread.id;
+    let forums = forums.on_submit(move |siv, forum_id: &i32| {
+        siv.pop_layer();
+        let result = block_on(moderation.move_thread(&actor, thread_id, *forum_id));
+        show_result(siv, result);
+    });
+    siv.add_layer(
+        Dialog::around(forums.scrollable())
+            .title(format!("Move \"{}\" to", thread.name))
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn merge_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    moderation: Arc<ModerationUtil>,
+    actor: UserInfo,
+    thread: &thread::Model,
+) {
+    let forum_util = ForumUtil::new(db.clone());
+    let targets = block_on(async {
+        let mut targets = Vec::new();
+        for node in forum_util.forum_tree().await? {
+            for summary in forum_util.threads(None, node.forum.id).await? {
+                targets.push((node.forum.name.clone(), summary.thread));
+            }
+        }
+        Ok::<_, anyhow::Error>(targets)
+    });
+    let targets = match targets {
+        Ok(targets) => targets,
+        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+    };
+    let mut threads = SelectView::new();
+    for (forum, target) in targets {
+        if target.id != thread.id {
+            threads.add_item(format!("{}: {}", forum, target.name), target.id);
+        }
+    }
+    let source_id = thread.id;
+    let threads = threads.on_submit(move |siv, target_id: &i32| {
+        siv.pop_layer();
+        let result = block_on(moderation.merge_threads(&actor, source_id, *target_id));
+        match result {
+            Ok(_) => {
+                // The thread on screen no longer exists, so show the one it was merged into.
+                let mut stack = get_stack(siv);
+                stack.pop(siv).unwrap();
+                stack
+                    .push(thread_screen(db.clone(), key.clone(), *target_id, None))
+                    .unwrap();
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        }
+    });
+    siv.add_layer(
+        Dialog::around(threads.scrollable())
+            .title(format!("Merge \"{}\" into", thread.name))
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn split_dialog(
+    siv: &mut Cursive,
+    moderation: Arc<ModerationUtil>,
+    actor: UserInfo,
+    thread_id: i32,
+    selected: Vec<i32>,
+) {
+    let count = selected.len();
+    if count == 0 {
+        return siv.add_layer(Dialog::info(
+            "Press x on the posts to split off before choosing Split.",
+        ));
+    }
+    let edit = EditView::new().with_name(SPLIT_TITLE_EDIT).min_width(40);
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!(
+            "Split {} selected post{} into a new thread titled:",
+            count,
+            if count == 1 { "" } else { "s" }
+        )))
+        .child(edit);
+    siv.add_layer(
+        Dialog::around(layout)
+            .title("Split thread")
+            .button("Ok", move |siv| {
+                let title = siv
+                    .call_on_name(SPLIT_TITLE_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                siv.pop_layer();
+                let result =
+                    block_on(moderation.split_thread(&actor, thread_id, selected.clone(), &title));
+                match result {
+                    Ok(new_thread) => {
+                        siv.add_layer(Dialog::info(format!("Split off \"{}\".", new_thread.name)))
+                    }
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                }
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+/// Recent moderator actions, newest first.
+pub fn moderation_log_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+) -> Box<dyn View> {
+    match get_user(db.clone(), key) {
+        Ok(user) if user.has_role(Role::Moderator) => {}
+        _ => {
+            return Box::new(TextView::new(
+                "The moderation log is only available to moderators.",
+            ))
+        }
+    }
+    let entries = match block_on(ModerationUtil::new(db).log_entries(LOG_LENGTH)) {
+        Ok(entries) => entries,
+        Err(err) => return Box::new(TextView::new(err.to_string())),
+    };
+    let mut text = String::new();
+    for entry in entries.iter() {
+        text.push_str(&format!(
+            "{}  {:16} {:9} {}\n",
+            entry.entry.created, entry.actor, entry.entry.action, entry.entry.detail
+        ));
+    }
+    if entries.is_empty() {
+        text.push_str("Nothing has been moderated yet.");
+    }
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Moderation log. Esc: back"))
+        .child(DummyView)
+        .child(TextView::new(text).scrollable().full_screen());
+    Box::new(layout)
+}
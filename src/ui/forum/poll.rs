@@ -0,0 +1,252 @@
+use std::sync::Arc;
+
+use chrono::Duration;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    theme::Effect,
+    utils::markup::StyledString,
+    view::{Nameable, Resizable, Scrollable},
+    views::{Checkbox, Dialog, EditView, LinearLayout, ListView, SelectView, TextArea, TextView},
+    Cursive,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    forum::{ForumUtil, PollTally},
+    user::UserInfo,
+};
+
+use super::super::{block_on, labeled_edit_view::LabeledEditView};
+
+static POLL_QUESTION_EDIT: &str = "poll_question";
+static POLL_OPTIONS_EDIT: &str = "poll_options";
+static POLL_MULTIPLE_CHECKBOX: &str = "poll_multiple";
+static POLL_DAYS_EDIT: &str = "poll_days";
+static POLL_CHOICE_PREFIX: &str = "poll_choice_";
+static BAR_WIDTH: usize = 20;
+
+/// The poll as shown above a thread's posts: the question, then a bar per option.
+pub(super) fn render_poll(tally: &PollTally) -> StyledString {
+    let mut rendered = StyledString::styled(format!("Poll: {}", tally.poll.question), Effect::Bold);
+    let status = match (&tally.poll.closes, tally.is_closed()) {
+        (Some(closes), true) => format!("closed {} UTC", closes),
+        (Some(closes), false) => format!("closes {} UTC", closes),
+        (None, _) => "open".to_string(),
+    };
+    rendered.append_plain(format!(
+        "\n{} voter{}, {}{}\n",
+        tally.voters,
+        if tally.voters == 1 { "" } else { "s" },
+        if tally.poll.multiple {
+            "pick any, "
+        } else {
+            ""
+        },
+        status
+    ));
+    let width = tally
+        .options
+        .iter()
+        .map(|(option, _)| option.label.chars().count())
+        .max()
+        .unwrap_or(0);
+    let most = tally
+        .options
+        .iter()
+        .map(|(_, votes)| *votes)
+        .max()
+        .unwrap_or(0);
+    for (option, votes) in tally.options.iter() {
+        let bar = if most == 0 {
+            0
+        } else {
+            votes * BAR_WIDTH / most
+        };
+        let line = format!(
+            "{} {:width$} {:bar_width$} {}\n",
+            if tally.own.contains(&option.id) {
+                "*"
+            } else {
+                " "
+            },
+            option.label,
+            "#".repeat(bar),
+            votes,
+            width = width,
+            bar_width = BAR_WIDTH
+        );
+        if tally.own.contains(&option.id) {
+            rendered.append_styled(line, Effect::Bold);
+        } else {
+            rendered.append_plain(line);
+        }
+    }
+    if !tally.is_closed() {
+        rendered.append_plain("v: vote\n");
+    }
+    rendered
+}
+
+/// Lets the author of a thread attach a poll to it.
+pub(super) fn create_poll_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    thread_id: i32,
+) {
+    let fields = LinearLayout::vertical()
+        .child(LabeledEditView::new(
+            "Question:",
+            Some(10),
+            "",
+            |_, _, _| {},
+            |siv, _| {
+                siv.focus_name(POLL_OPTIONS_EDIT).unwrap();
+            },
+            POLL_QUESTION_EDIT,
+        ))
+        .child(TextView::new("Options, one per line:"))
+        .child(
+            TextArea::new()
+                .with_name(POLL_OPTIONS_EDIT)
+                .min_height(5)
+                .scrollable(),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(Checkbox::new().with_name(POLL_MULTIPLE_CHECKBOX))
+                .child(TextView::new(" Voters may pick more than one option")),
+        )
+        .child(LabeledEditView::new(
+            "Days open:",
+            Some(10),
+            "",
+            |_, _, _| {},
+            |_, _| {},
+            POLL_DAYS_EDIT,
+        ))
+        .child(TextView::new("Leave days blank to keep the poll open."))
+        .min_width(50);
+    siv.add_layer(
+        Dialog::around(fields)
+            .title("New poll")
+            .button("Ok", move |siv| {
+                let question = siv
+                    .call_on_name(POLL_QUESTION_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                let options: Vec<String> = siv
+                    .call_on_name(POLL_OPTIONS_EDIT, |area: &mut TextArea| {
+                        area.get_content().lines().map(String::from).collect()
+                    })
+                    .unwrap();
+                let multiple = siv
+                    .call_on_name(POLL_MULTIPLE_CHECKBOX, |checkbox: &mut Checkbox| {
+                        checkbox.is_checked()
+                    })
+                    .unwrap();
+                let days = siv
+                    .call_on_name(POLL_DAYS_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                let duration = if days.trim().is_empty() {
+                    None
+                } else {
+                    match days.trim().parse::<i64>() {
+                        Ok(days) if days > 0 => Some(Duration::days(days)),
+                        _ => {
+                            siv.add_layer(Dialog::info("Days must be a positive number."));
+                            return;
+                        }
+                    }
+                };
+                let result = block_on(
+                    ForumUtil::new(db.clone())
+                        .attach_poll(&user, thread_id, &question, &options, multiple, duration),
+                );
+                match result {
+                    Ok(_) => {
+                        siv.pop_layer();
+                    }
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                }
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn cast(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: &UserInfo,
+    poll_id: i32,
+    options: &[i32],
+) {
+    siv.pop_layer();
+    if let Err(err) = block_on(ForumUtil::new(db).vote(user, poll_id, options)) {
+        siv.add_layer(Dialog::info(err.to_string()));
+    }
+}
+
+/// Asks `user` for their choice in a poll. Voting again replaces the earlier ballot.
+pub(super) fn vote_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    tally: &PollTally,
+) {
+    if tally.is_closed() {
+        return siv.add_layer(Dialog::info("This poll has closed."));
+    }
+    let poll_id = tally.poll.id;
+    let mut dialog = if tally.poll.multiple {
+        let mut choices = ListView::new();
+        for (option, _) in tally.options.iter() {
+            let checkbox = Checkbox::new()
+                .with_checked(tally.own.contains(&option.id))
+                .with_name(format!("{}{}", POLL_CHOICE_PREFIX, option.id));
+            choices.add_child(&option.label, checkbox);
+        }
+        let option_ids: Vec<i32> = tally.options.iter().map(|(option, _)| option.id).collect();
+        let db = db.clone();
+        let user = user.clone();
+        Dialog::around(choices.scrollable()).button("Vote", move |siv| {
+            let chosen: Vec<i32> = option_ids
+                .iter()
+                .copied()
+                .filter(|option_id| {
+                    let name = format!("{}{}", POLL_CHOICE_PREFIX, option_id);
+                    siv.call_on_name(&name, |checkbox: &mut Checkbox| checkbox.is_checked())
+                        .unwrap_or(false)
+                })
+                .collect();
+            cast(siv, db.clone(), &user, poll_id, &chosen);
+        })
+    } else {
+        let mut choices = SelectView::new();
+        for (option, _) in tally.options.iter() {
+            choices.add_item(option.label.clone(), option.id);
+        }
+        if let Some(index) = tally
+            .options
+            .iter()
+            .position(|(option, _)| tally.own.contains(&option.id))
+        {
+            choices.set_selection(index);
+        }
+        let db = db.clone();
+        let user = user.clone();
+        let choices = choices.on_submit(move |siv, option_id: &i32| {
+            cast(siv, db.clone(), &user, poll_id, &[*option_id]);
+        });
+        Dialog::around(choices.scrollable())
+    };
+    if !tally.own.is_empty() {
+        dialog.add_button("Withdraw", move |siv| {
+            cast(siv, db.clone(), &user, poll_id, &[]);
+        });
+    }
+    siv.add_layer(
+        dialog
+            .title(tally.poll.question.clone())
+            .dismiss_button("Cancel"),
+    );
+}
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        theme::Effect,
+        utils::markup::StyledString,
+        view::{Nameable, Resizable, Scrollable},
+        views::{Dialog, DummyView, LinearLayout, SelectView, TextView},
+        View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::gen::forum,
+    forum::{search::SearchHit, ForumUtil},
+    throttle::{self, Action},
+};
+
+use super::{
+    super::{block_on, labeled_edit_view::LabeledEditView, stack::get_stack},
+    thread::thread_screen,
+};
+
+static SEARCH_EDIT: &str = "forum_search_box";
+static SEARCH_RESULTS: &str = "forum_search_results";
+static MAX_RESULTS: usize = 50;
+
+fn hit_label(hit: &SearchHit) -> StyledString {
+    let mut label = StyledString::plain(format!("{} ({}): ", hit.title, hit.author));
+    let fragment = hit.fragment.replace('\n', " ");
+    let mut position = 0;
+    for range in hit.highlighted.iter() {
+        if range.start < position || range.end > fragment.len() {
+            continue;
+        }
+        label.append_plain(&fragment[position..range.start]);
+        label.append_styled(&fragment[range.clone()], Effect::Reverse);
+        position = range.end;
+    }
+    label.append_plain(&fragment[position..]);
+    label
+}
+
+/// Searches forum posts, limited to one forum if `forum` is given.
+pub fn search_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    forum: Option<forum::Model>,
+) -> Box<dyn View> {
+    let forum_id = forum.as_ref().map(|forum| forum.id);
+    let search_box = {
+        let db = db.clone();
+        LabeledEditView::new(
+            "Search:",
+            None,
+            "",
+            |_, _, _| {},
+            move |siv, text| {
+                if let Err(err) = throttle::check(get_stack(siv).session_id(), Action::Search) {
+                    siv.add_layer(Dialog::info(err.to_string()));
+                    return;
+                }
+                let result =
+                    block_on(ForumUtil::new(db.clone()).search(text, forum_id, MAX_RESULTS));
+                match result {
+                    Ok(hits) => {
+                        let found = !hits.is_empty();
+                        siv.call_on_name(SEARCH_RESULTS, |results: &mut SelectView<SearchHit>| {
+                            results.clear();
+                            for hit in hits {
+                                results.add_item(hit_label(&hit), hit);
+                            }
+                        });
+                        if found {
+                            siv.focus_name(SEARCH_RESULTS).unwrap();
+                        } else {
+                            siv.add_layer(Dialog::info("Nothing matched."));
+                        }
+                    }
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                }
+            },
+            SEARCH_EDIT,
+        )
+    };
+    let results = SelectView::<SearchHit>::new().on_submit(move |siv, hit: &SearchHit| {
+        get_stack(siv)
+            .push(thread_screen(
+                db.clone(),
+                key.clone(),
+                hit.thread_id,
+                Some(hit.post_id),
+            ))
+            .unwrap();
+    });
+    let scope = match &forum {
+        Some(forum) => format!("Search {}.", forum.name),
+        None => "Search the forums.".to_string(),
+    };
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!(
+            "{} Narrow it down with author:handle or forum:name. Enter: search/open  Esc: back",
+            scope
+        )))
+        .child(search_box)
+        .child(DummyView)
+        .child(results.with_name(SEARCH_RESULTS).scrollable().full_screen());
+    Box::new(layout)
+}
@@ -0,0 +1,623 @@
+use std::cmp::max;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        direction::{Direction, Orientation},
+        event::{AnyCb, Event, EventResult},
+        theme::{ColorStyle, Effect},
+        utils::markup::StyledString,
+        view::{CannotFocus, Resizable, Scrollable, Selector},
+        views::{Dialog, LinearLayout, ResizedView, ScrollView, SelectView, TextView},
+        Cursive, Printer, Rect, Vec2, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::gen::thread,
+    draft::DraftTarget,
+    forum::{forum_generation, reaction_kinds, ForumUtil, PollTally, PostEntry, PostReactions},
+    mention::{find_mentions, MentionUtil},
+    throttle::{self, Action},
+    user::{Role, UserInfo},
+};
+
+use super::{
+    super::{
+        block_on,
+        editor::EditorView,
+        get_user,
+        markup::{render_markup, MARKUP_HELP},
+        profile::page::profile_page,
+        stack::get_stack,
+    },
+    moderate::moderate_dialog,
+    poll::{create_poll_dialog, render_poll, vote_dialog},
+};
+
+pub fn thread_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    thread_id: i32,
+    focus_post: Option<i32>,
+) -> Box<dyn View> {
+    Box::new(ThreadView::new(db, key, thread_id, focus_post))
+}
+
+/// One post in a thread: a header line with the author and time, the body, then a tally of
+/// reactions if there are any. Moderators can select posts to split them off into a new thread.
+struct PostPanel {
+    entry: PostEntry,
+    body: TextView,
+    reactions: Option<TextView>,
+    selected: bool,
+}
+
+impl PostPanel {
+    fn new(
+        entry: PostEntry,
+        known: &HashSet<String>,
+        own_handle: Option<&str>,
+        reactions: Option<&PostReactions>,
+        selected: bool,
+    ) -> Self {
+        let body = TextView::new(render_markup(&entry.post.body, known, own_handle));
+        let reactions = reactions.map(|reactions| TextView::new(Self::tally(reactions)));
+        Self {
+            entry,
+            body,
+            reactions,
+            selected,
+        }
+    }
+
+    /// Renders counts like `[+1 3] [thanks 1]`, with the reader's own reactions in bold.
+    fn tally(reactions: &PostReactions) -> StyledString {
+        let mut tally = StyledString::new();
+        for (kind, count) in reactions.counts.iter() {
+            if !tally.is_empty() {
+                tally.append_plain(" ");
+            }
+            let label = format!("[{} {}]", kind, count);
+            if reactions.own.contains(kind) {
+                tally.append_styled(label, Effect::Bold);
+            } else {
+                tally.append_plain(label);
+            }
+        }
+        tally
+    }
+
+    fn header(&self) -> String {
+        let header = format!(
+            "{}{} at {}",
+            if self.selected { "[x] " } else { "" },
+            self.entry.author,
+            self.entry.post.created.clone().unwrap_or_default()
+        );
+        match &self.entry.post.modified {
+            Some(modified) => format!("{} (edited {})", header, modified),
+            None => header,
+        }
+    }
+}
+
+impl View for PostPanel {
+    fn draw(&self, printer: &Printer) {
+        let header = self.header();
+        if printer.focused {
+            printer.with_color(ColorStyle::highlight(), |printer| {
+                printer.print_hline((0, 0), printer.size.x, " ");
+                printer.print((0, 0), &header);
+            });
+        } else {
+            printer.with_effect(Effect::Bold, |printer| printer.print((0, 0), &header));
+        }
+        self.body.draw(&printer.offset((0, 1)));
+        if let Some(reactions) = &self.reactions {
+            reactions.draw(&printer.offset((0, printer.size.y.saturating_sub(2))));
+        }
+    }
+    fn layout(&mut self, size: Vec2) {
+        let extra = if self.reactions.is_some() { 3 } else { 2 };
+        self.body
+            .layout(Vec2::new(size.x, size.y.saturating_sub(extra)));
+        if let Some(reactions) = &mut self.reactions {
+            reactions.layout(Vec2::new(size.x, 1));
+        }
+    }
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        let body = self.body.required_size(constraint);
+        // One row for the header and a blank row between posts.
+        let mut size = Vec2::new(max(body.x, self.header().len()), body.y + 2);
+        if let Some(reactions) = &mut self.reactions {
+            let reactions = reactions.required_size(constraint);
+            size = Vec2::new(max(size.x, reactions.x), size.y + 1);
+        }
+        size
+    }
+    fn take_focus(&mut self, _: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+    fn important_area(&self, view_size: Vec2) -> Rect {
+        Rect::from_size((0, 0), view_size)
+    }
+    fn type_name(&self) -> &'static str {
+        "PostPanel"
+    }
+}
+
+/// A thread's posts, oldest first, below its poll if it has one. In Q&A forums the replies are
+/// ranked by votes instead, unless the reader asks for posting order. Opens at the first unread
+/// post and reloads itself whenever anyone posts.
+pub struct ThreadView {
+    inner: LinearLayout,
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    user: Option<UserInfo>,
+    thread_id: i32,
+    thread: Option<thread::Model>,
+    poll: Option<PollTally>,
+    generation: u64,
+    focus_post: Option<i32>,
+    first_unread: Option<i32>,
+    scroll_pending: bool,
+    selected: HashSet<i32>,
+    question_answer: bool,
+    by_score: bool,
+}
+
+impl ThreadView {
+    pub fn new(
+        db: Arc<Mutex<DatabaseConnection>>,
+        key: Option<PublicKey>,
+        thread_id: i32,
+        focus_post: Option<i32>,
+    ) -> Self {
+        let user = get_user(db.clone(), key.clone()).ok();
+        let mut help =
+            "Up/Down: move between posts  u: first unread  r: reply  e: edit  +: react  a: author's profile  Esc: back"
+                .to_string();
+        if matches!(&user, Some(user) if user.has_role(Role::Moderator)) {
+            help.push_str("\nx: select for splitting  m: moderate");
+        }
+        let inner = LinearLayout::vertical()
+            .child(TextView::new(""))
+            .child(TextView::new(""))
+            .child(LinearLayout::vertical().scrollable().full_screen())
+            .child(TextView::new(help));
+        let mut view = Self {
+            inner,
+            db,
+            key,
+            user,
+            thread_id,
+            thread: None,
+            poll: None,
+            generation: forum_generation(),
+            focus_post,
+            first_unread: None,
+            scroll_pending: false,
+            selected: HashSet::new(),
+            question_answer: false,
+            by_score: false,
+        };
+        view.reload();
+        view
+    }
+
+    fn get_header(&mut self) -> &mut TextView {
+        self.inner
+            .get_child_mut(0)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<TextView>()
+            .unwrap()
+    }
+
+    fn get_poll(&mut self) -> &mut TextView {
+        self.inner
+            .get_child_mut(1)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<TextView>()
+            .unwrap()
+    }
+
+    fn get_scroll(&mut self) -> &mut ScrollView<LinearLayout> {
+        self.inner
+            .get_child_mut(2)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<ResizedView<ScrollView<LinearLayout>>>()
+            .unwrap()
+            .get_inner_mut()
+    }
+
+    fn focused_panel(&mut self) -> Option<&mut PostPanel> {
+        let posts = self.get_scroll().get_inner_mut();
+        let index = posts.get_focus_index();
+        posts
+            .get_child_mut(index)?
+            .as_any_mut()
+            .downcast_mut::<PostPanel>()
+    }
+
+    fn focused_entry(&mut self) -> Option<PostEntry> {
+        self.focused_panel().map(|panel| panel.entry.clone())
+    }
+
+    fn is_moderator(&self) -> bool {
+        matches!(&self.user, Some(user) if user.has_role(Role::Moderator))
+    }
+
+    fn reload(&mut self) {
+        self.generation = forum_generation();
+        let forum_util = ForumUtil::new(self.db.clone());
+        if self.thread.is_none() {
+            // Opening a thread lands on the first post that's new to the reader.
+            if let Some(user) = &self.user {
+                self.first_unread =
+                    block_on(forum_util.first_unread(user, self.thread_id)).unwrap_or(None);
+            }
+            if self.focus_post.is_none() {
+                self.focus_post = self.first_unread;
+            }
+        }
+        let loaded = block_on(async {
+            let thread = forum_util.thread(self.thread_id).await?;
+            let forum = forum_util.forum(thread.forum).await?;
+            let posts = forum_util.posts(self.thread_id).await?;
+            let post_ids = posts.iter().map(|entry| entry.post.id).collect();
+            let reactions = forum_util.reactions(self.user.as_ref(), post_ids).await?;
+            let poll = forum_util.poll(self.user.as_ref(), self.thread_id).await?;
+            Ok::<_, anyhow::Error>((thread, forum, posts, reactions, poll))
+        });
+        let (thread, forum, mut posts, reactions, poll) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                error!("Failed to load thread {}: {}", self.thread_id, err);
+                self.get_header().set_content(err.to_string());
+                return;
+            }
+        };
+        let handles = posts
+            .iter()
+            .flat_map(|entry| find_mentions(&entry.post.body))
+            .map(|token| token.handle);
+        let known = block_on(MentionUtil::new(self.db.clone()).resolve_handles(handles))
+            .unwrap_or_default();
+        // Keep the reader where they were unless we've been asked to jump somewhere.
+        let focus_post = match self.focus_post.take() {
+            Some(focus_post) => {
+                self.scroll_pending = true;
+                Some(focus_post)
+            }
+            None => self.focused_entry().map(|entry| entry.post.id),
+        };
+        let own_handle = self.user.as_ref().map(|user| user.handle.clone());
+        let count = posts.len();
+        let last_post = posts.last().map(|entry| entry.post.id);
+        if forum.question_answer != self.question_answer {
+            // Answers are ranked by default whenever the thread turns out to be a question.
+            self.question_answer = forum.question_answer;
+            self.by_score = forum.question_answer;
+        }
+        if self.by_score && posts.len() > 1 {
+            // The question stays on top, and the sort is stable so ties keep posting order.
+            let score = |entry: &PostEntry| {
+                reactions
+                    .get(&entry.post.id)
+                    .map_or(0, |reactions| reactions.score())
+            };
+            posts[1..].sort_by_key(|entry| -score(entry));
+        }
+        // Posts split off or merged away since the last load can't be selected any more.
+        self.selected
+            .retain(|post_id| posts.iter().any(|entry| entry.post.id == *post_id));
+        let mut focus_index = None;
+        let mut layout = LinearLayout::vertical();
+        for (index, entry) in posts.into_iter().enumerate() {
+            if Some(entry.post.id) == focus_post {
+                focus_index = Some(index);
+            }
+            let selected = self.selected.contains(&entry.post.id);
+            let post_reactions = reactions.get(&entry.post.id);
+            layout.add_child(PostPanel::new(
+                entry,
+                &known,
+                own_handle.as_deref(),
+                post_reactions,
+                selected,
+            ));
+        }
+        if let Some(focus_index) = focus_index {
+            layout.set_focus_index(focus_index).unwrap();
+        }
+        *self.get_scroll().get_inner_mut() = layout;
+        let mut flags = String::new();
+        if thread.sticky {
+            flags.push_str("[sticky] ");
+        }
+        if thread.locked.is_some() {
+            flags.push_str("[locked] ");
+        }
+        let mut title = format!(
+            "{}{} ({} post{})",
+            flags,
+            thread.name,
+            count,
+            if count == 1 { "" } else { "s" }
+        );
+        if self.question_answer {
+            title.push_str(if self.by_score {
+                "\nAnswers ranked by votes. s: show in posting order"
+            } else {
+                "\nAnswers in posting order. s: rank by votes"
+            });
+        }
+        self.get_header().set_content(title);
+        let started_thread = matches!(&self.user, Some(user) if user.id() == Some(thread.author));
+        let poll_text = match &poll {
+            Some(poll) => render_poll(poll),
+            None if started_thread => StyledString::plain("p: attach a poll\n"),
+            None => StyledString::new(),
+        };
+        self.get_poll().set_content(poll_text);
+        self.poll = poll;
+        self.thread = Some(thread);
+        // Everything in the thread is on screen now, so count it as read.
+        if let (Some(user), Some(last_post)) = (&self.user, last_post) {
+            if let Err(err) = block_on(forum_util.mark_read(user, self.thread_id, last_post)) {
+                error!("Failed to mark thread {} read: {}", self.thread_id, err);
+            }
+        }
+    }
+}
+
+impl View for ThreadView {
+    fn draw(&self, printer: &Printer) {
+        self.inner.draw(printer)
+    }
+    fn needs_relayout(&self) -> bool {
+        self.scroll_pending || self.generation != forum_generation() || self.inner.needs_relayout()
+    }
+    fn on_event(&mut self, event: Event) -> EventResult {
+        if event == Event::Char('r') {
+            let thread = match &self.thread {
+                Some(thread) => thread.clone(),
+                None => return EventResult::Ignored,
+            };
+            // The focused post is offered for quoting.
+            let quoted = self.focused_entry();
+            let db = self.db.clone();
+            let key = self.key.clone();
+            return EventResult::with_cb(move |siv| match get_user(db.clone(), key.clone()) {
+                Ok(user) if thread.locked.is_some() && !user.has_role(Role::Moderator) => {
+                    siv.add_layer(Dialog::info("This thread is locked."));
+                }
+                Ok(user) => {
+                    get_stack(siv)
+                        .push(reply_screen(
+                            db.clone(),
+                            user,
+                            thread.clone(),
+                            quoted.clone(),
+                        ))
+                        .unwrap();
+                }
+                Err(_) => siv.add_layer(Dialog::info("Set up your profile before posting.")),
+            });
+        }
+        if event == Event::Char('u') {
+            if let Some(first_unread) = self.first_unread {
+                self.focus_post = Some(first_unread);
+                self.reload();
+            }
+            return EventResult::Consumed(None);
+        }
+        if event == Event::Char('e') {
+            let entry = match self.focused_entry() {
+                Some(entry) => entry,
+                None => return EventResult::Ignored,
+            };
+            let db = self.db.clone();
+            let key = self.key.clone();
+            return EventResult::with_cb(move |siv| match get_user(db.clone(), key.clone()) {
+                Ok(user) => {
+                    get_stack(siv)
+                        .push(edit_screen(db.clone(), user, entry.clone()))
+                        .unwrap();
+                }
+                Err(_) => siv.add_layer(Dialog::info("Set up your profile before posting.")),
+            });
+        }
+        if event == Event::Char('a') {
+            let author = match self.focused_entry() {
+                Some(entry) => entry.post.author,
+                None => return EventResult::Ignored,
+            };
+            let db = self.db.clone();
+            let key = self.key.clone();
+            return EventResult::with_cb(move |siv| {
+                get_stack(siv)
+                    .push(profile_page(db.clone(), key.clone(), author))
+                    .unwrap();
+            });
+        }
+        if event == Event::Char('+') {
+            let (user, entry) = match (self.user.clone(), self.focused_entry()) {
+                (Some(user), Some(entry)) => (user, entry),
+                _ => return EventResult::Ignored,
+            };
+            let db = self.db.clone();
+            return EventResult::with_cb(move |siv| {
+                react_dialog(siv, db.clone(), user.clone(), &entry)
+            });
+        }
+        if event == Event::Char('v') {
+            let (user, poll) = match (&self.user, &self.poll) {
+                (Some(user), Some(poll)) => (user.clone(), poll.clone()),
+                _ => return EventResult::Ignored,
+            };
+            let db = self.db.clone();
+            return EventResult::with_cb(move |siv| {
+                vote_dialog(siv, db.clone(), user.clone(), &poll)
+            });
+        }
+        if event == Event::Char('p') && self.poll.is_none() {
+            let (user, thread_id) = match (&self.user, &self.thread) {
+                (Some(user), Some(thread)) => (user.clone(), thread.id),
+                _ => return EventResult::Ignored,
+            };
+            let db = self.db.clone();
+            return EventResult::with_cb(move |siv| {
+                create_poll_dialog(siv, db.clone(), user.clone(), thread_id)
+            });
+        }
+        if event == Event::Char('s') && self.question_answer {
+            self.by_score = !self.by_score;
+            self.reload();
+            return EventResult::Consumed(None);
+        }
+        if event == Event::Char('x') && self.is_moderator() {
+            if let Some(panel) = self.focused_panel() {
+                panel.selected = !panel.selected;
+                let (post_id, selected) = (panel.entry.post.id, panel.selected);
+                if selected {
+                    self.selected.insert(post_id);
+                } else {
+                    self.selected.remove(&post_id);
+                }
+            }
+            return EventResult::Consumed(None);
+        }
+        if event == Event::Char('m') && self.is_moderator() {
+            let (actor, thread) = match (&self.user, &self.thread) {
+                (Some(actor), Some(thread)) => (actor.clone(), thread.clone()),
+                _ => return EventResult::Ignored,
+            };
+            let db = self.db.clone();
+            let key = self.key.clone();
+            let selected: Vec<i32> = self.selected.iter().copied().collect();
+            return EventResult::with_cb(move |siv| {
+                moderate_dialog(
+                    siv,
+                    db.clone(),
+                    key.clone(),
+                    actor.clone(),
+                    thread.clone(),
+                    selected.clone(),
+                )
+            });
+        }
+        self.inner.on_event(event)
+    }
+    fn call_on_any(&mut self, selector: &Selector, cb: AnyCb) {
+        self.inner.call_on_any(selector, cb)
+    }
+    fn take_focus(&mut self, source: Direction) -> Result<EventResult, CannotFocus> {
+        self.inner.take_focus(source)
+    }
+    fn type_name(&self) -> &'static str {
+        "ThreadView"
+    }
+    fn layout(&mut self, size: Vec2) {
+        if self.generation != forum_generation() {
+            self.reload();
+        }
+        self.inner.layout(size);
+        // The focused post's position is only known once the posts have been laid out.
+        if self.scroll_pending {
+            self.scroll_pending = false;
+            self.get_scroll().scroll_to_important_area();
+        }
+    }
+}
+
+/// Lets `user` add or take back a reaction on the post in `entry`.
+fn react_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    entry: &PostEntry,
+) {
+    let post_id = entry.post.id;
+    let own = match block_on(ForumUtil::new(db.clone()).reactions(Some(&user), vec![post_id])) {
+        Ok(mut reactions) => reactions.remove(&post_id).unwrap_or_default().own,
+        Err(err) => return siv.add_layer(Dialog::info(err.to_string())),
+    };
+    let mut kinds = SelectView::new();
+    for kind in reaction_kinds() {
+        let mark = if own.contains(&kind) { "[x]" } else { "[ ]" };
+        kinds.add_item(format!("{} {}", mark, kind), kind);
+    }
+    let kinds = kinds.on_submit(move |siv, kind: &String| {
+        siv.pop_layer();
+        let result = block_on(ForumUtil::new(db.clone()).toggle_reaction(&user, post_id, kind));
+        if let Err(err) = result {
+            siv.add_layer(Dialog::info(err.to_string()));
+        }
+    });
+    siv.add_layer(
+        Dialog::around(kinds.scrollable())
+            .title(format!("React to {}'s post", entry.author))
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn reply_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    thread: thread::Model,
+    quoted: Option<PostEntry>,
+) -> Box<dyn View> {
+    let user_id = user.id();
+    let target = DraftTarget::Reply { thread: thread.id };
+    let mut editor = {
+        let db = db.clone();
+        let thread_id = thread.id;
+        EditorView::new("", move |siv, body| {
+            throttle::check(get_stack(siv).session_id(), Action::Post)?;
+            block_on(ForumUtil::new(db.clone()).reply(&user, thread_id, body))?;
+            get_stack(siv).pop(siv).unwrap();
+            Ok(())
+        })
+    }
+    .draft(db, user_id, target);
+    if let Some(quoted) = quoted {
+        editor = editor.quote(&quoted.author, &quoted.post.body);
+    }
+    let layout = LinearLayout::new(Orientation::Vertical)
+        .child(TextView::new(format!(
+            "Reply to \"{}\".\nFormatting: {}",
+            thread.name, MARKUP_HELP
+        )))
+        .child(editor);
+    Box::new(layout.full_screen())
+}
+
+fn edit_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    entry: PostEntry,
+) -> Box<dyn View> {
+    let post_id = entry.post.id;
+    let editor = EditorView::new(&entry.post.body, move |siv, body| {
+        block_on(ForumUtil::new(db.clone()).edit_post(&user, post_id, body))?;
+        get_stack(siv).pop(siv).unwrap();
+        Ok(())
+    });
+    let layout = LinearLayout::new(Orientation::Vertical)
+        .child(TextView::new(format!(
+            "Edit {}'s post.\nFormatting: {}",
+            entry.author, MARKUP_HELP
+        )))
+        .child(editor);
+    Box::new(layout.full_screen())
+}
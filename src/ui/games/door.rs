@@ -0,0 +1,280 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key},
+    theme::{Color, ColorStyle, Effect, Style},
+    view::CannotFocus,
+    Printer, Vec2, View,
+};
+use tokio::sync::{mpsc::Sender, Mutex};
+
+use crate::{
+    door::{DoorDefinition, DoorProcess, DropFileInfo, ScoreUtil, DEFAULT_TIME_LIMIT},
+    session::session_time_left,
+    ui::block_on,
+    user::UserInfo,
+};
+
+/// A door's terminal, with a status line underneath. The door is started the first time the
+/// view is laid out, once the size of its terminal is known. A score it reports is filed under
+/// its name.
+pub struct DoorView {
+    db: Arc<Mutex<DatabaseConnection>>,
+    door: DoorDefinition,
+    user: UserInfo,
+    node: u64,
+    relayout_sender: Sender<()>,
+    process: Option<DoorProcess>,
+    error: Option<String>,
+    /// How the game went, once the door has exited and reported a score.
+    result: Option<String>,
+    started: Instant,
+    size: Vec2,
+}
+
+impl DoorView {
+    pub(crate) fn new(
+        db: Arc<Mutex<DatabaseConnection>>,
+        door: DoorDefinition,
+        user: UserInfo,
+        node: u64,
+        relayout_sender: Sender<()>,
+    ) -> Self {
+        DoorView {
+            db,
+            door,
+            user,
+            node,
+            relayout_sender,
+            process: None,
+            error: None,
+            result: None,
+            started: Instant::now(),
+            size: Vec2::zero(),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        matches!(&self.process, Some(process) if !process.has_exited())
+    }
+
+    fn status(&self) -> String {
+        if let Some(error) = &self.error {
+            return format!("{}  Esc: back", error);
+        }
+        if !self.is_running() {
+            return match &self.result {
+                Some(result) => format!("{} has closed. {}  Esc: back", self.door.name, result),
+                None => format!("{} has closed. Esc: back", self.door.name),
+            };
+        }
+        match self.door.time_limit {
+            Some(limit) => {
+                let left = limit.saturating_sub(self.started.elapsed()).as_secs();
+                format!(
+                    "{}  Ctrl-]: hang up  {}:{:02} left",
+                    self.door.name,
+                    left / 60,
+                    left % 60
+                )
+            }
+            None => format!("{}  Ctrl-]: hang up", self.door.name),
+        }
+    }
+
+    fn record_score(&mut self) {
+        let score = match self
+            .process
+            .as_ref()
+            .and_then(|process| process.take_score())
+        {
+            Some(score) => score,
+            None => return,
+        };
+        let scores = ScoreUtil::new(self.db.clone());
+        let result =
+            block_on(scores.record(&self.door.name, &self.user, score.score, &score.metadata));
+        self.result = Some(match result {
+            Ok(true) => format!("Score: {}, a personal best!", score.score),
+            Ok(false) => format!("Score: {}.", score.score),
+            Err(err) => {
+                error!("Failed to record score at {}: {}", self.door.name, err);
+                format!("Score: {}, but it couldn't be saved.", score.score)
+            }
+        });
+    }
+}
+
+/// Splits our size into the door's terminal and the status line below it.
+fn terminal_size(size: Vec2) -> (u16, u16) {
+    let rows = size.y.saturating_sub(1).clamp(1, u16::MAX as usize) as u16;
+    let cols = size.x.clamp(1, u16::MAX as usize) as u16;
+    (rows, cols)
+}
+
+fn color(color: vt100::Color) -> Color {
+    match color {
+        vt100::Color::Default => Color::TerminalDefault,
+        vt100::Color::Idx(index) => Color::from_256colors(index),
+        vt100::Color::Rgb(r, g, b) => Color::Rgb(r, g, b),
+    }
+}
+
+fn cell_style(cell: &vt100::Cell, cursor: bool) -> Style {
+    let (mut front, mut back) = (color(cell.fgcolor()), color(cell.bgcolor()));
+    if cell.inverse() != cursor {
+        std::mem::swap(&mut front, &mut back);
+    }
+    let mut style = Style::from(ColorStyle::new(front, back));
+    if cell.bold() {
+        style.effects.insert(Effect::Bold);
+    }
+    if cell.underline() {
+        style.effects.insert(Effect::Underline);
+    }
+    style
+}
+
+/// What a terminal would send the door for `event`, if anything.
+fn key_bytes(event: &Event) -> Option<Vec<u8>> {
+    let bytes: &[u8] = match event {
+        Event::Char(c) => return Some(c.to_string().into_bytes()),
+        Event::CtrlChar(c @ 'a'..='z') => return Some(vec![*c as u8 - b'a' + 1]),
+        Event::Key(Key::Enter) => b"\r",
+        Event::Key(Key::Tab) => b"\t",
+        Event::Key(Key::Backspace) => b"\x7f",
+        Event::Key(Key::Esc) => b"\x1b",
+        Event::Key(Key::Up) => b"\x1b[A",
+        Event::Key(Key::Down) => b"\x1b[B",
+        Event::Key(Key::Right) => b"\x1b[C",
+        Event::Key(Key::Left) => b"\x1b[D",
+        Event::Key(Key::Home) => b"\x1b[H",
+        Event::Key(Key::End) => b"\x1b[F",
+        Event::Key(Key::Ins) => b"\x1b[2~",
+        Event::Key(Key::Del) => b"\x1b[3~",
+        Event::Key(Key::PageUp) => b"\x1b[5~",
+        Event::Key(Key::PageDown) => b"\x1b[6~",
+        Event::Key(Key::F1) => b"\x1bOP",
+        Event::Key(Key::F2) => b"\x1bOQ",
+        Event::Key(Key::F3) => b"\x1bOR",
+        Event::Key(Key::F4) => b"\x1bOS",
+        _ => return None,
+    };
+    Some(bytes.to_vec())
+}
+
+impl View for DoorView {
+    fn draw(&self, printer: &Printer) {
+        if let Some(process) = &self.process {
+            let parser = process.screen();
+            let screen = parser.screen();
+            let (rows, cols) = screen.size();
+            let cursor = if screen.hide_cursor() || !self.is_running() {
+                None
+            } else {
+                Some(screen.cursor_position())
+            };
+            for row in 0..rows {
+                for col in 0..cols {
+                    let cell = match screen.cell(row, col) {
+                        Some(cell) if !cell.is_wide_continuation() => cell,
+                        _ => continue,
+                    };
+                    let contents = cell.contents();
+                    let text: &str = if contents.is_empty() { " " } else { &contents };
+                    let style = cell_style(cell, cursor == Some((row, col)));
+                    printer.with_style(style, |printer| {
+                        printer.print((col as usize, row as usize), text);
+                    });
+                }
+            }
+        }
+        let status_row = printer.size.y.saturating_sub(1);
+        printer.with_color(ColorStyle::highlight(), |printer| {
+            printer.print_hline((0, status_row), printer.size.x, " ");
+            printer.print((0, status_row), &self.status());
+        });
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        let (rows, cols) = terminal_size(size);
+        if self.process.is_none() && self.error.is_none() {
+            // The door gets no more time than the caller has left.
+            if let Some(left) = session_time_left(self.node) {
+                self.door.time_limit =
+                    Some(self.door.time_limit.map_or(left, |limit| limit.min(left)));
+            }
+            let info = DropFileInfo {
+                user: self.user.clone(),
+                node: self.node,
+                time_left: self.door.time_limit.unwrap_or(DEFAULT_TIME_LIMIT),
+                rows,
+                cols,
+                ansi: true,
+            };
+            match DoorProcess::spawn(&self.door, &info, self.relayout_sender.clone()) {
+                Ok(process) => {
+                    self.process = Some(process);
+                    self.started = Instant::now();
+                }
+                Err(err) => {
+                    error!("Failed to start door {}: {}", self.door.name, err);
+                    self.error = Some(format!("{} couldn't be started.", self.door.name));
+                }
+            }
+        } else if !self.is_running() {
+            self.record_score();
+        } else if size != self.size {
+            if let Some(process) = &self.process {
+                if let Err(err) = process.resize(rows, cols) {
+                    error!("Failed to resize door {}: {}", self.door.name, err);
+                }
+            }
+        }
+        self.size = size;
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        constraint
+    }
+
+    fn needs_relayout(&self) -> bool {
+        self.process
+            .as_ref()
+            .map(|process| process.take_updated())
+            .unwrap_or(false)
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        // Once the door is gone, keys go back to the board so Esc leaves the screen.
+        if !self.is_running() {
+            return EventResult::Ignored;
+        }
+        let process = self.process.as_mut().unwrap();
+        match event {
+            // Terminal parsers disagree on whether Ctrl-] is reported as itself or as Ctrl-5.
+            Event::CtrlChar(']') | Event::CtrlChar('5') => process.hang_up(),
+            Event::Refresh | Event::WindowResize => return EventResult::Ignored,
+            event => {
+                if let Some(bytes) = key_bytes(&event) {
+                    if let Err(err) = process.write(&bytes) {
+                        error!("Failed to write to door {}: {}", self.door.name, err);
+                    }
+                }
+            }
+        }
+        EventResult::Consumed(None)
+    }
+
+    fn take_focus(&mut self, _source: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "DoorView"
+    }
+}
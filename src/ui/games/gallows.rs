@@ -0,0 +1,296 @@
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    direction::Direction,
+    event::{Event, EventResult, Key},
+    view::{CannotFocus, Resizable},
+    views::{LinearLayout, ResizedView, TextView},
+    Printer, Vec2, View,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    door::{Door, DoorStateUtil, ScoreUtil},
+    ui::{block_on, library::get_library},
+    user::UserInfo,
+};
+
+const MISSES_ALLOWED: usize = 6;
+
+static GALLOWS: [&str; MISSES_ALLOWED + 1] = [
+    "  +---+\n  |   |\n      |\n      |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n      |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n  |   |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|   |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|\\  |\n      |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|\\  |\n /    |\n      |\n=========",
+    "  +---+\n  |   |\n  O   |\n /|\\  |\n / \\  |\n      |\n=========",
+];
+
+/// Hangman, played with words out of the library.
+pub(crate) struct Gallows;
+
+impl Door for Gallows {
+    fn id(&self) -> &'static str {
+        "gallows"
+    }
+
+    fn name(&self) -> &'static str {
+        "Gallows"
+    }
+
+    fn description(&self) -> &'static str {
+        "Guess the word, pulled from the library, before the hangman is finished"
+    }
+
+    fn open(
+        &self,
+        db: Arc<Mutex<DatabaseConnection>>,
+        user: UserInfo,
+    ) -> Result<Box<dyn View>, anyhow::Error> {
+        let saved = block_on(DoorStateUtil::new(db.clone()).load(self.id(), &user))?;
+        let game = saved
+            .as_deref()
+            .and_then(GallowsGame::decode)
+            .unwrap_or_default();
+        let mut view = GallowsView {
+            inner: LinearLayout::vertical()
+                .child(TextView::new(""))
+                .child(TextView::new(
+                    "Type a letter to guess it. Enter: next word. Esc: leave",
+                ))
+                .full_screen(),
+            game,
+            db,
+            user,
+            message: String::new(),
+        };
+        if view.game.word.is_empty() {
+            view.next_word();
+        }
+        view.refresh();
+        Ok(Box::new(view))
+    }
+}
+
+/// A player's record, and the word they're on if they're part way through one.
+#[derive(Debug, Default)]
+struct GallowsGame {
+    won: u32,
+    played: u32,
+    streak: u32,
+    best_streak: u32,
+    word: String,
+    guesses: String,
+}
+
+impl GallowsGame {
+    fn encode(&self) -> String {
+        let or_dash = |text: &str| {
+            if text.is_empty() {
+                "-".to_string()
+            } else {
+                text.to_string()
+            }
+        };
+        format!(
+            "{} {} {} {} {} {}",
+            self.won,
+            self.played,
+            self.streak,
+            self.best_streak,
+            or_dash(&self.word),
+            or_dash(&self.guesses)
+        )
+    }
+
+    fn decode(saved: &str) -> Option<GallowsGame> {
+        let fields: Vec<&str> = saved.split_whitespace().collect();
+        let [won, played, streak, best_streak, word, guesses] = fields[..] else {
+            return None;
+        };
+        let or_empty = |text: &str| if text == "-" { "" } else { text }.to_string();
+        Some(GallowsGame {
+            won: won.parse().ok()?,
+            played: played.parse().ok()?,
+            streak: streak.parse().ok()?,
+            best_streak: best_streak.parse().ok()?,
+            word: or_empty(word),
+            guesses: or_empty(guesses),
+        })
+    }
+
+    fn misses(&self) -> usize {
+        self.guesses
+            .chars()
+            .filter(|c| !self.word.contains(*c))
+            .count()
+    }
+
+    fn is_solved(&self) -> bool {
+        !self.word.is_empty() && self.word.chars().all(|c| self.guesses.contains(c))
+    }
+
+    fn is_over(&self) -> bool {
+        self.is_solved() || self.misses() >= MISSES_ALLOWED
+    }
+
+    fn masked_word(&self) -> String {
+        self.word
+            .chars()
+            .map(|c| {
+                if self.guesses.contains(c) || self.is_over() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .map(String::from)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+struct GallowsView {
+    inner: ResizedView<LinearLayout>,
+    game: GallowsGame,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    message: String,
+}
+
+impl GallowsView {
+    fn next_word(&mut self) {
+        let library = match get_library("library") {
+            Some(library) => library,
+            None => {
+                self.message = "The library is still opening. Try again in a moment.".into();
+                return;
+            }
+        };
+        match library.random_words(1, 5..=10) {
+            Ok(words) if !words.is_empty() => {
+                self.game.word = words[0].clone();
+                self.game.guesses.clear();
+                self.message.clear();
+            }
+            Ok(_) => self.message = "The library didn't have any good words.".into(),
+            Err(err) => {
+                error!("Failed to pick a word: {}", err);
+                self.message = "Couldn't pick a word.".into();
+            }
+        }
+        self.save();
+    }
+
+    fn guess(&mut self, letter: char) {
+        if self.game.word.is_empty() || self.game.is_over() {
+            return;
+        }
+        if self.game.guesses.contains(letter) {
+            self.message = format!("You already tried {}.", letter.to_ascii_uppercase());
+            return;
+        }
+        self.game.guesses.push(letter);
+        self.message.clear();
+        if self.game.is_over() {
+            self.game.played += 1;
+            if self.game.is_solved() {
+                self.game.won += 1;
+                self.game.streak += 1;
+                self.game.best_streak = self.game.best_streak.max(self.game.streak);
+                self.message = "You got it!".into();
+                let metadata = format!("Last word: {}", self.game.word.to_uppercase());
+                let scores = ScoreUtil::new(self.db.clone());
+                match block_on(scores.record(
+                    Gallows.id(),
+                    &self.user,
+                    self.game.streak as i64,
+                    &metadata,
+                )) {
+                    Ok(true) => self.message.push_str(" That's a new personal best."),
+                    Ok(false) => {}
+                    Err(err) => error!("Failed to record gallows score: {}", err),
+                }
+            } else {
+                self.game.streak = 0;
+                self.message = "Hanged! Better luck with the next one.".into();
+            }
+        }
+        self.save();
+    }
+
+    fn save(&self) {
+        let states = DoorStateUtil::new(self.db.clone());
+        if let Err(err) = block_on(states.save(Gallows.id(), &self.user, &self.game.encode())) {
+            error!("Failed to save gallows game: {}", err);
+        }
+    }
+
+    fn refresh(&mut self) {
+        let game = &self.game;
+        let misses: String = game
+            .guesses
+            .chars()
+            .filter(|c| !game.word.contains(*c))
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let board = format!(
+            "{}\n\n{}\n\nMisses: {}\n{}\n\nWon {} of {} played. Streak {}, best {}.",
+            GALLOWS[game.misses().min(MISSES_ALLOWED)],
+            game.masked_word(),
+            misses,
+            self.message,
+            game.won,
+            game.played,
+            game.streak,
+            game.best_streak
+        );
+        self.inner
+            .get_inner_mut()
+            .get_child_mut(0)
+            .unwrap()
+            .as_any_mut()
+            .downcast_mut::<TextView>()
+            .unwrap()
+            .set_content(board);
+    }
+}
+
+impl View for GallowsView {
+    fn draw(&self, printer: &Printer) {
+        self.inner.draw(printer)
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        self.inner.layout(size)
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        self.inner.required_size(constraint)
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Char(c) if c.is_ascii_alphabetic() => self.guess(c.to_ascii_lowercase()),
+            Event::Key(Key::Enter) => {
+                if self.game.word.is_empty() || self.game.is_over() {
+                    self.next_word();
+                }
+            }
+            _ => return EventResult::Ignored,
+        }
+        self.refresh();
+        EventResult::Consumed(None)
+    }
+
+    fn take_focus(&mut self, _source: Direction) -> Result<EventResult, CannotFocus> {
+        Ok(EventResult::Consumed(None))
+    }
+
+    fn type_name(&self) -> &'static str {
+        "GallowsView"
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        views::{Dialog, DummyView, LinearLayout, SelectView, TextView},
+        View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::{mpsc::Sender, Mutex};
+
+use crate::{
+    door::{Door, DoorDefinition},
+    settings::get_settings,
+};
+
+use self::{door::DoorView, gallows::Gallows, scores::leaderboards_screen, trade::TradeWinds};
+
+use super::{get_user, stack::get_stack};
+
+pub(crate) mod door;
+mod gallows;
+mod scores;
+mod trade;
+
+lazy_static! {
+    /// Every native door, in the order the games menu lists them.
+    static ref NATIVE_DOORS: Vec<Box<dyn Door>> = vec![Box::new(TradeWinds), Box::new(Gallows)];
+}
+
+pub(crate) fn native_doors() -> &'static [Box<dyn Door>] {
+    &NATIVE_DOORS
+}
+
+/// The name to show for the door whose scores are filed under `id`.
+pub(crate) fn door_name(id: &str) -> String {
+    native_doors()
+        .iter()
+        .find(|door| door.id() == id)
+        .map(|door| door.name().to_string())
+        .unwrap_or_else(|| id.to_string())
+}
+
+enum Game {
+    Native(&'static dyn Door),
+    External(DoorDefinition),
+    Leaderboards,
+}
+
+pub fn games_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    relayout_sender: Sender<()>,
+) -> Box<dyn View> {
+    let user = match get_user(db.clone(), key) {
+        Ok(user) => user,
+        Err(_) => {
+            return Box::new(TextView::new(
+                "Games are only available to registered users. Set up your profile first.",
+            ))
+        }
+    };
+    let mut select_view = SelectView::new();
+    for door in native_doors() {
+        select_view.add_item(
+            format!("{}: {}", door.name(), door.description()),
+            Game::Native(door.as_ref()),
+        );
+    }
+    for door in get_settings().doors {
+        select_view.add_item(door.name.clone(), Game::External(door));
+    }
+    select_view.add_item("See the leaderboards", Game::Leaderboards);
+    select_view.set_on_submit(move |siv, game| match game {
+        Game::Native(door) => match door.open(db.clone(), user.clone()) {
+            Ok(view) => get_stack(siv).push(view).unwrap(),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        },
+        Game::External(door) => {
+            let mut stack = get_stack(siv);
+            let node = stack.session_id();
+            stack
+                .push(Box::new(DoorView::new(
+                    db.clone(),
+                    door.clone(),
+                    user.clone(),
+                    node,
+                    relayout_sender.clone(),
+                )))
+                .unwrap();
+        }
+        Game::Leaderboards => {
+            get_stack(siv)
+                .push(leaderboards_screen(db.clone()))
+                .unwrap();
+        }
+    });
+    Box::new(
+        LinearLayout::vertical()
+            .child(TextView::new("Pick a game to play."))
+            .child(DummyView)
+            .child(select_view),
+    )
+}
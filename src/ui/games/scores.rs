@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use chrono::Duration;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    view::Scrollable,
+    views::{DummyView, LinearLayout, SelectView, TextView},
+    View,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    db::from_now,
+    door::{ScoreEntry, ScoreUtil},
+    settings::get_settings,
+    ui::{block_on, stack::get_stack},
+};
+
+use super::{door_name, native_doors};
+
+/// How many players each door gets on the combined boards.
+const SUMMARY_LENGTH: usize = 5;
+const BOARD_LENGTH: usize = 20;
+
+#[derive(Clone)]
+enum Board {
+    AllTime,
+    Week,
+    Door(String),
+}
+
+/// What every door's scores are filed under, and its name. Native doors come first, then the
+/// configured ones, which file scores under their names.
+fn scored_doors() -> Vec<(String, String)> {
+    let native = native_doors()
+        .iter()
+        .map(|door| (door.id().to_string(), door.name().to_string()));
+    let external = get_settings()
+        .doors
+        .into_iter()
+        .map(|door| (door.name.clone(), door.name));
+    native.chain(external).collect()
+}
+
+fn format_entries(entries: &[ScoreEntry]) -> String {
+    if entries.is_empty() {
+        return "  Nobody has played yet.\n".into();
+    }
+    entries
+        .iter()
+        .enumerate()
+        .map(|(rank, entry)| {
+            format!(
+                "{:>4}. {:<20} {:>10}  {}\n",
+                rank + 1,
+                entry.handle,
+                entry.score.score,
+                entry.score.metadata
+            )
+        })
+        .collect()
+}
+
+fn board_screen(db: Arc<Mutex<DatabaseConnection>>, board: Board) -> Box<dyn View> {
+    let scores = ScoreUtil::new(db);
+    let text = match &board {
+        Board::AllTime | Board::Week => {
+            let since = match board {
+                Board::Week => Some(from_now(Duration::days(-7))),
+                _ => None,
+            };
+            let mut text = String::new();
+            for (id, name) in scored_doors() {
+                let entries = block_on(scores.leaderboard(&id, since.clone(), SUMMARY_LENGTH))
+                    .unwrap_or_default();
+                text.push_str(&format!("{}\n{}\n", name, format_entries(&entries)));
+            }
+            text
+        }
+        Board::Door(door) => {
+            let entries =
+                block_on(scores.leaderboard(door, None, BOARD_LENGTH)).unwrap_or_default();
+            format!("{}\n{}", door_name(door), format_entries(&entries))
+        }
+    };
+    let title = match board {
+        Board::AllTime => "All-time high scores",
+        Board::Week => "High scores from the last seven days",
+        Board::Door(_) => "Hall of fame",
+    };
+    Box::new(
+        LinearLayout::vertical()
+            .child(TextView::new(title))
+            .child(DummyView)
+            .child(TextView::new(text).scrollable()),
+    )
+}
+
+pub(super) fn leaderboards_screen(db: Arc<Mutex<DatabaseConnection>>) -> Box<dyn View> {
+    let mut select_view = SelectView::new()
+        .item("All-time high scores", Board::AllTime)
+        .item("This week's high scores", Board::Week);
+    for (id, name) in scored_doors() {
+        select_view.add_item(format!("{} hall of fame", name), Board::Door(id));
+    }
+    select_view.set_on_submit(move |siv, board| {
+        get_stack(siv)
+            .push(board_screen(db.clone(), board.clone()))
+            .unwrap();
+    });
+    Box::new(select_view)
+}
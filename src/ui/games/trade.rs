@@ -0,0 +1,451 @@
+use std::sync::{Arc, Mutex as StdMutex};
+
+use log::error;
+use rand::Rng;
+use sea_orm::DatabaseConnection;
+use ssh_ui::cursive::{
+    view::{Nameable, Resizable},
+    views::{Dialog, DummyView, EditView, LinearLayout, SelectView, TextView},
+    Cursive, View,
+};
+use thiserror::Error;
+use tokio::sync::Mutex;
+
+use crate::{
+    door::{Door, DoorStateUtil, ScoreUtil},
+    ui::block_on,
+    user::UserInfo,
+};
+
+static TRADE_STATUS: &str = "trade_status";
+static TRADE_AMOUNT_EDIT: &str = "trade_amount";
+
+const PORTS: [&str; 5] = ["Lisbon", "Venice", "Alexandria", "Calicut", "Canton"];
+/// Goods and their usual price.
+const GOODS: [(&str, u64); 4] = [("Grain", 12), ("Tea", 40), ("Spice", 90), ("Silk", 200)];
+const DAYS: u32 = 30;
+const HOLD: u64 = 100;
+const STARTING_CASH: u64 = 500;
+const STARTING_DEBT: u64 = 1000;
+
+#[derive(Debug, Error)]
+enum TradeError {
+    #[error("You can't afford that.")]
+    CantAfford,
+    #[error("Your hold isn't big enough.")]
+    HoldFull,
+    #[error("You don't have that much to sell.")]
+    NotEnoughCargo,
+    #[error("You don't owe that much.")]
+    OverPayment,
+    #[error("The season is over. Start a new voyage.")]
+    SeasonOver,
+}
+
+/// A merchant voyage: buy low in one port, sail, sell high in another, and pay off the
+/// moneylender before the season ends.
+pub(crate) struct TradeWinds;
+
+impl Door for TradeWinds {
+    fn id(&self) -> &'static str {
+        "trade_winds"
+    }
+
+    fn name(&self) -> &'static str {
+        "Trade Winds"
+    }
+
+    fn description(&self) -> &'static str {
+        "Trade grain, tea, spice and silk between five ports in thirty days"
+    }
+
+    fn open(
+        &self,
+        db: Arc<Mutex<DatabaseConnection>>,
+        user: UserInfo,
+    ) -> Result<Box<dyn View>, anyhow::Error> {
+        let saved = block_on(DoorStateUtil::new(db.clone()).load(self.id(), &user))?;
+        let game = saved
+            .as_deref()
+            .and_then(TradeGame::decode)
+            .unwrap_or_else(|| TradeGame::new(0));
+        Ok(trade_screen(db, user, game))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TradeGame {
+    day: u32,
+    port: usize,
+    cash: u64,
+    debt: u64,
+    cargo: [u64; GOODS.len()],
+    prices: [u64; GOODS.len()],
+    /// Best worth at the end of a season, kept across voyages.
+    best: u64,
+    news: String,
+}
+
+impl TradeGame {
+    fn new(best: u64) -> TradeGame {
+        let mut game = TradeGame {
+            day: 1,
+            port: 0,
+            cash: STARTING_CASH,
+            debt: STARTING_DEBT,
+            cargo: [0; GOODS.len()],
+            prices: [0; GOODS.len()],
+            best,
+            news: String::new(),
+        };
+        game.roll_prices();
+        game
+    }
+
+    fn encode(&self) -> String {
+        let mut fields = vec![
+            self.day as u64,
+            self.port as u64,
+            self.cash,
+            self.debt,
+            self.best,
+        ];
+        fields.extend(self.cargo);
+        fields.extend(self.prices);
+        fields
+            .iter()
+            .map(u64::to_string)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn decode(saved: &str) -> Option<TradeGame> {
+        let fields = saved
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<u64>, _>>()
+            .ok()?;
+        if fields.len() != 5 + 2 * GOODS.len() || fields[1] as usize >= PORTS.len() {
+            return None;
+        }
+        Some(TradeGame {
+            day: fields[0] as u32,
+            port: fields[1] as usize,
+            cash: fields[2],
+            debt: fields[3],
+            best: fields[4],
+            cargo: fields[5..5 + GOODS.len()].try_into().ok()?,
+            prices: fields[5 + GOODS.len()..].try_into().ok()?,
+            news: String::new(),
+        })
+    }
+
+    fn is_over(&self) -> bool {
+        self.day > DAYS
+    }
+
+    fn cargo_carried(&self) -> u64 {
+        self.cargo.iter().sum()
+    }
+
+    /// Cash plus cargo at today's prices, less debt.
+    fn worth(&self) -> i64 {
+        let cargo: u64 = self
+            .cargo
+            .iter()
+            .zip(self.prices)
+            .map(|(amount, price)| amount * price)
+            .sum();
+        (self.cash + cargo) as i64 - self.debt as i64
+    }
+
+    fn roll_prices(&mut self) {
+        let mut rng = rand::thread_rng();
+        self.news.clear();
+        for (good, (name, base)) in GOODS.iter().enumerate() {
+            let percent = match rng.gen_range(0..20) {
+                0 => {
+                    self.news = format!("A glut of {} has crashed prices!", name.to_lowercase());
+                    25
+                }
+                1 => {
+                    self.news = format!("{} is scarce and fetching a fortune!", name);
+                    300
+                }
+                _ => rng.gen_range(50..=160),
+            };
+            self.prices[good] = (base * percent / 100).max(1);
+        }
+    }
+
+    fn buy(&mut self, good: usize, amount: u64) -> Result<(), TradeError> {
+        if self.is_over() {
+            return Err(TradeError::SeasonOver);
+        }
+        let cost = amount * self.prices[good];
+        if cost > self.cash {
+            return Err(TradeError::CantAfford);
+        }
+        if self.cargo_carried() + amount > HOLD {
+            return Err(TradeError::HoldFull);
+        }
+        self.cash -= cost;
+        self.cargo[good] += amount;
+        Ok(())
+    }
+
+    fn sell(&mut self, good: usize, amount: u64) -> Result<(), TradeError> {
+        if self.is_over() {
+            return Err(TradeError::SeasonOver);
+        }
+        if amount > self.cargo[good] {
+            return Err(TradeError::NotEnoughCargo);
+        }
+        self.cargo[good] -= amount;
+        self.cash += amount * self.prices[good];
+        Ok(())
+    }
+
+    fn repay(&mut self, amount: u64) -> Result<(), TradeError> {
+        if amount > self.debt {
+            return Err(TradeError::OverPayment);
+        }
+        if amount > self.cash {
+            return Err(TradeError::CantAfford);
+        }
+        self.cash -= amount;
+        self.debt -= amount;
+        Ok(())
+    }
+
+    /// A day at sea. Interest on the debt runs at 5% a day.
+    fn sail(&mut self, port: usize) -> Result<(), TradeError> {
+        if self.is_over() {
+            return Err(TradeError::SeasonOver);
+        }
+        self.port = port;
+        self.day += 1;
+        self.debt += self.debt / 20;
+        self.roll_prices();
+        if self.is_over() {
+            self.best = self.best.max(self.worth().max(0) as u64);
+        }
+        Ok(())
+    }
+
+    fn describe(&self) -> String {
+        let mut text = if self.is_over() {
+            format!("The season is over. You finished worth {}.\n", self.worth())
+        } else {
+            format!(
+                "Day {} of {}, docked at {}.\n",
+                self.day, DAYS, PORTS[self.port]
+            )
+        };
+        text.push_str(&format!(
+            "Cash: {}  Debt: {}  Hold: {}/{}  Best season: {}\n\n",
+            self.cash,
+            self.debt,
+            self.cargo_carried(),
+            HOLD,
+            self.best
+        ));
+        text.push_str(&format!("{:<8}{:>8}{:>8}\n", "Goods", "Price", "Aboard"));
+        for (good, (name, _)) in GOODS.iter().enumerate() {
+            text.push_str(&format!(
+                "{:<8}{:>8}{:>8}\n",
+                name, self.prices[good], self.cargo[good]
+            ));
+        }
+        if !self.news.is_empty() {
+            text.push_str(&format!("\n{}", self.news));
+        }
+        text
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TradeAction {
+    Buy(usize),
+    Sell(usize),
+    Sail,
+    Repay,
+    NewVoyage,
+}
+
+fn save(db: &Arc<Mutex<DatabaseConnection>>, user: &UserInfo, game: &TradeGame) {
+    let states = DoorStateUtil::new(db.clone());
+    if let Err(err) = block_on(states.save(TradeWinds.id(), user, &game.encode())) {
+        error!("Failed to save trade game: {}", err);
+    }
+}
+
+/// Applies `action` to the game, then saves it and updates the screen, or explains why it
+/// couldn't be done. Returns whether the action went through.
+fn apply(
+    siv: &mut Cursive,
+    db: &Arc<Mutex<DatabaseConnection>>,
+    user: &UserInfo,
+    game: &StdMutex<TradeGame>,
+    action: impl FnOnce(&mut TradeGame) -> Result<(), TradeError>,
+) -> bool {
+    let mut game = game.lock().unwrap();
+    if let Err(err) = action(&mut game) {
+        siv.add_layer(Dialog::info(err.to_string()));
+        return false;
+    }
+    save(db, user, &game);
+    siv.call_on_name(TRADE_STATUS, |status: &mut TextView| {
+        status.set_content(game.describe())
+    });
+    true
+}
+
+/// Puts the finished season on the leaderboard and tells the player how they did.
+fn end_season(
+    siv: &mut Cursive,
+    db: &Arc<Mutex<DatabaseConnection>>,
+    user: &UserInfo,
+    game: &TradeGame,
+) {
+    let worth = game.worth();
+    let metadata = format!("Retired in {}", PORTS[game.port]);
+    let scores = ScoreUtil::new(db.clone());
+    let summary = match block_on(scores.record(TradeWinds.id(), user, worth, &metadata)) {
+        Ok(true) => format!("You finished the season worth {}, your best yet!", worth),
+        Ok(false) => format!("You finished the season worth {}.", worth),
+        Err(err) => {
+            error!("Failed to record trade score: {}", err);
+            format!("You finished the season worth {}.", worth)
+        }
+    };
+    siv.add_layer(Dialog::info(summary).title("The season is over"));
+}
+
+/// Asks how much of something to trade, then hands the amount to `on_amount`.
+fn amount_dialog<F>(siv: &mut Cursive, title: String, on_amount: F)
+where
+    F: Fn(&mut Cursive, u64) + Send + Sync + 'static,
+{
+    siv.add_layer(
+        Dialog::around(EditView::new().with_name(TRADE_AMOUNT_EDIT).min_width(10))
+            .title(title)
+            .button("Ok", move |siv| {
+                let amount = siv
+                    .call_on_name(TRADE_AMOUNT_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                match amount.trim().parse::<u64>() {
+                    Ok(amount) => {
+                        siv.pop_layer();
+                        on_amount(siv, amount);
+                    }
+                    Err(_) => siv.add_layer(Dialog::info("Enter a whole number.")),
+                }
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn trade_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    game: TradeGame,
+) -> Box<dyn View> {
+    let status = TextView::new(game.describe()).with_name(TRADE_STATUS);
+    let game = Arc::new(StdMutex::new(game));
+    let mut actions = SelectView::new();
+    for (good, (name, _)) in GOODS.iter().enumerate() {
+        actions.add_item(
+            format!("Buy {}", name.to_lowercase()),
+            TradeAction::Buy(good),
+        );
+    }
+    for (good, (name, _)) in GOODS.iter().enumerate() {
+        actions.add_item(
+            format!("Sell {}", name.to_lowercase()),
+            TradeAction::Sell(good),
+        );
+    }
+    actions.add_item("Set sail", TradeAction::Sail);
+    actions.add_item("Repay the moneylender", TradeAction::Repay);
+    actions.add_item("Start a new voyage", TradeAction::NewVoyage);
+    actions.set_on_submit(move |siv, action| {
+        let (db, user, game) = (db.clone(), user.clone(), game.clone());
+        match *action {
+            TradeAction::Buy(good) => {
+                let (name, can_buy) = {
+                    let game = game.lock().unwrap();
+                    let affordable = game.cash / game.prices[good];
+                    (GOODS[good].0, affordable.min(HOLD - game.cargo_carried()))
+                };
+                amount_dialog(
+                    siv,
+                    format!("Buy how much {}? (up to {})", name.to_lowercase(), can_buy),
+                    move |siv, amount| {
+                        apply(siv, &db, &user, &game, |game| game.buy(good, amount));
+                    },
+                );
+            }
+            TradeAction::Sell(good) => {
+                let (name, aboard) = (GOODS[good].0, game.lock().unwrap().cargo[good]);
+                amount_dialog(
+                    siv,
+                    format!("Sell how much {}? ({} aboard)", name.to_lowercase(), aboard),
+                    move |siv, amount| {
+                        apply(siv, &db, &user, &game, |game| game.sell(good, amount));
+                    },
+                );
+            }
+            TradeAction::Repay => {
+                let debt = game.lock().unwrap().debt;
+                amount_dialog(
+                    siv,
+                    format!("Repay how much? (you owe {})", debt),
+                    move |siv, amount| {
+                        apply(siv, &db, &user, &game, |game| game.repay(amount));
+                    },
+                );
+            }
+            TradeAction::Sail => {
+                let here = game.lock().unwrap().port;
+                let mut ports = SelectView::new();
+                for (port, name) in PORTS.iter().enumerate() {
+                    if port != here {
+                        ports.add_item(*name, port);
+                    }
+                }
+                ports.set_on_submit(move |siv, port| {
+                    siv.pop_layer();
+                    let sailed = apply(siv, &db, &user, &game, |game| game.sail(*port));
+                    if sailed && game.lock().unwrap().is_over() {
+                        end_season(siv, &db, &user, &game.lock().unwrap());
+                    }
+                });
+                siv.add_layer(
+                    Dialog::around(ports)
+                        .title("Sail where?")
+                        .dismiss_button("Stay"),
+                );
+            }
+            TradeAction::NewVoyage => {
+                siv.add_layer(
+                    Dialog::text("Abandon this voyage and start over?")
+                        .button("Start over", move |siv| {
+                            siv.pop_layer();
+                            apply(siv, &db, &user, &game, |game| {
+                                *game = TradeGame::new(game.best);
+                                Ok(())
+                            });
+                        })
+                        .dismiss_button("Keep going"),
+                );
+            }
+        }
+    });
+    Box::new(
+        LinearLayout::vertical()
+            .child(status)
+            .child(DummyView)
+            .child(actions),
+    )
+}
@@ -4,8 +4,11 @@ use figlet_rs::FIGfont;
 use sea_orm::DatabaseConnection;
 use ssh_ui::{
     cursive::{
+        direction::Direction,
+        event::{AnyCb, Event, EventResult},
+        view::{CannotFocus, Finder, Nameable, Selector, ViewNotFound},
         views::{DummyView, LinearLayout, SelectView, TextView},
-        View,
+        Printer, Rect, Vec2, View,
     },
     russh_keys::key::PublicKey,
 };
@@ -16,17 +19,46 @@ use tokio::{
 };
 
 use crate::{
+    door::ScoreUtil,
+    mail::MailUtil,
+    mention::MentionUtil,
     ui::{
-        forum::ChatBoxView, library::search::LibrarySearchView, profile::profile_screen,
+        admin::sysop_screen,
+        block_on,
+        forum::{
+            board::{forum_screen, new_posts_screen},
+            moderate::moderation_log_screen,
+            ChatBoxView,
+        },
+        games::{door_name, games_screen},
+        library::search::LibrarySearchView,
+        mail::mail_screen,
+        members::{members_screen, whos_online_screen},
+        mentions::mentions_screen,
+        profile::{page::profile_page, profile_screen},
         stack::get_stack,
     },
-    user::UserUtil,
+    user::{Role, UserInfo, UserUtil},
 };
 
+/// How many of the latest personal bests to show under the banner.
+const RECENT_ACHIEVEMENTS: u64 = 3;
+static WELCOME: &str = "home_welcome";
+static MENU: &str = "home_menu";
+
 enum HomeOption {
     Profile,
     Forum,
+    NewPosts,
+    Chat,
+    Members,
+    Online,
+    Mail,
+    Mentions,
     Library,
+    Games,
+    ModerationLog,
+    Sysop,
     Disconnect,
 }
 
@@ -35,62 +67,257 @@ pub fn home_screen(
     db: Arc<Mutex<DatabaseConnection>>,
     key: Option<PublicKey>,
 ) -> Box<dyn View> {
+    let user = {
+        let db = db.clone();
+        let key = key.clone();
+        block_in_place(move || {
+            Handle::current().block_on(async move {
+                let user_util = UserUtil::new(db.clone(), key.clone());
+                user_util.get_user().await
+            })
+        })
+    };
+    let user = user.ok();
     let mut select_view = SelectView::new()
-        .item("Edit your (P)rofile", HomeOption::Profile)
+        .item("Your (P)rofile", HomeOption::Profile)
         .item(
             "(F)orum: Discussion boards for various topics",
             HomeOption::Forum,
         )
+        .item("(N)ew posts since your last visit", HomeOption::NewPosts)
+        .item("(C)hat with whoever is around", HomeOption::Chat)
+        .item("Browse the member (D)irectory", HomeOption::Members)
+        .item("(W)ho's online", HomeOption::Online)
+        .item("Read your (M)ail", HomeOption::Mail)
+        .item(
+            mentions_label(db.clone(), user.as_ref()),
+            HomeOption::Mentions,
+        )
         .item("Visit the (L)ibrary", HomeOption::Library)
-        .item("Disconnect", HomeOption::Disconnect);
+        .item("Play some (G)ames", HomeOption::Games);
+    if matches!(&user, Some(user) if user.has_role(Role::Moderator)) {
+        select_view.add_item("Moderation l(O)g", HomeOption::ModerationLog);
+    }
+    if matches!(&user, Some(user) if user.has_role(Role::Sysop)) {
+        select_view.add_item("(S)ysop console", HomeOption::Sysop);
+    }
+    select_view.add_item("Disconnect", HomeOption::Disconnect);
     {
         let db = db.clone();
         let key = key.clone();
+        let user_id = user.as_ref().and_then(|user| user.id());
         select_view.set_on_submit(move |siv, item| match item {
             HomeOption::Profile => {
+                // Newcomers pick a handle before they have a profile to look at.
+                let screen = match user_id {
+                    Some(user_id) => profile_page(db.clone(), key.clone(), user_id),
+                    None => profile_screen(db.clone(), key.clone()),
+                };
+                get_stack(siv).push_screen(screen, "Profiles").unwrap();
+            }
+            HomeOption::Forum => {
                 get_stack(siv)
-                    .push(profile_screen(db.clone(), key.clone()))
+                    .push_screen(forum_screen(db.clone(), key.clone()), "Forum")
                     .unwrap();
             }
-            HomeOption::Forum => {
+            HomeOption::NewPosts => {
+                get_stack(siv)
+                    .push_screen(new_posts_screen(db.clone(), key.clone()), "New posts")
+                    .unwrap();
+            }
+            HomeOption::Chat => {
+                let mut stack = get_stack(siv);
+                let session_id = stack.session_id();
+                stack
+                    .push_screen(
+                        Box::new(ChatBoxView::new(
+                            db.clone(),
+                            key.clone(),
+                            session_id,
+                            force_relayout_sender.clone(),
+                        )),
+                        "Chat",
+                    )
+                    .unwrap();
+            }
+            HomeOption::Members => {
+                get_stack(siv)
+                    .push_screen(members_screen(db.clone(), key.clone()), "Member directory")
+                    .unwrap();
+            }
+            HomeOption::Online => {
+                get_stack(siv)
+                    .push_screen(whos_online_screen(db.clone(), key.clone()), "Who's online")
+                    .unwrap();
+            }
+            HomeOption::Mail => {
                 get_stack(siv)
-                    .push(Box::new(ChatBoxView::new(
-                        db.clone(),
-                        key.clone(),
-                        force_relayout_sender.clone(),
-                    )))
+                    .push_screen(mail_screen(db.clone(), key.clone()), "Mail")
+                    .unwrap();
+            }
+            HomeOption::Mentions => {
+                get_stack(siv)
+                    .push_screen(mentions_screen(db.clone(), key.clone()), "Mentions")
                     .unwrap();
             }
             HomeOption::Library => {
                 get_stack(siv)
-                    .push(Box::new(LibrarySearchView::new(
-                        "library",
-                        force_relayout_sender.clone(),
-                    )))
+                    .push_screen(
+                        Box::new(LibrarySearchView::new(
+                            "library",
+                            force_relayout_sender.clone(),
+                        )),
+                        "Library",
+                    )
+                    .unwrap();
+            }
+            HomeOption::Games => {
+                get_stack(siv)
+                    .push_screen(
+                        games_screen(db.clone(), key.clone(), force_relayout_sender.clone()),
+                        "Games",
+                    )
+                    .unwrap();
+            }
+            HomeOption::ModerationLog => {
+                get_stack(siv)
+                    .push_screen(
+                        moderation_log_screen(db.clone(), key.clone()),
+                        "Moderation log",
+                    )
+                    .unwrap();
+            }
+            HomeOption::Sysop => {
+                get_stack(siv)
+                    .push_screen(sysop_screen(db.clone(), key.clone()), "Sysop console")
                     .unwrap();
             }
             HomeOption::Disconnect => siv.quit(),
         });
     }
-    let header = {
+    let figure = {
         let small_font = FIGfont::from_content(include_str!("./speed.flf")).unwrap();
-        let figure = small_font.convert("BBS").unwrap();
-        let user = block_in_place(move || {
-            Handle::current().block_on(async move {
-                let user_util = UserUtil::new(db.clone(), key.clone());
-                user_util.get_user().await
+        TextView::new(small_font.convert("BBS").unwrap().to_string())
+    };
+    let welcome = TextView::new(welcome(db.clone(), user.as_ref())).with_name(WELCOME);
+    let achievements =
+        block_on(ScoreUtil::new(db.clone()).recent_achievements(RECENT_ACHIEVEMENTS))
+            .unwrap_or_default()
+            .iter()
+            .map(|entry| {
+                format!(
+                    "\n  {} set a personal best of {} at {}",
+                    entry.handle,
+                    entry.score.score,
+                    door_name(&entry.score.door)
+                )
             })
-        });
-        let welcome = if let Ok(user) = user {
-            format!("Welcome to the Future, {}.", user.handle)
-        } else {
-            "Welcome to the Future".into()
-        };
-        TextView::new(format!("{}\n{}", figure, welcome))
+            .collect::<String>();
+    let mut layout = LinearLayout::vertical()
+        .child(figure)
+        .child(welcome)
+        .child(DummyView);
+    if !achievements.is_empty() {
+        layout.add_child(TextView::new(format!(
+            "Recent achievements:{}",
+            achievements
+        )));
+        layout.add_child(DummyView);
+    }
+    layout.add_child(select_view.with_name(MENU));
+    Box::new(HomeView {
+        inner: layout,
+        db,
+        user,
+    })
+}
+
+fn welcome(db: Arc<Mutex<DatabaseConnection>>, user: Option<&UserInfo>) -> String {
+    let user = match user {
+        Some(user) => user,
+        None => return "Welcome to the Future".into(),
+    };
+    let unread = block_on(MailUtil::new(db).unread_count(user)).unwrap_or(0);
+    match unread {
+        0 => format!("Welcome to the Future, {}.", user.handle),
+        1 => format!(
+            "Welcome to the Future, {}. You have 1 unread message.",
+            user.handle
+        ),
+        unread => format!(
+            "Welcome to the Future, {}. You have {} unread messages.",
+            user.handle, unread
+        ),
+    }
+}
+
+fn mentions_label(db: Arc<Mutex<DatabaseConnection>>, user: Option<&UserInfo>) -> String {
+    let unseen_mentions = match user {
+        Some(user) => block_on(MentionUtil::new(db).unseen_count(user)).unwrap_or(0),
+        None => 0,
     };
-    let layout = LinearLayout::vertical()
-        .child(header)
-        .child(DummyView)
-        .child(select_view);
-    Box::new(layout)
+    format!(
+        "See where you've been mentioned (@) ({} new)",
+        unseen_mentions
+    )
+}
+
+/// The main menu. Reading mail and mentions happens on screens pushed over it, so its counts are
+/// brought up to date whenever it's back on top.
+struct HomeView {
+    inner: LinearLayout,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: Option<UserInfo>,
+}
+
+impl HomeView {
+    fn refresh(&mut self) {
+        let welcome = welcome(self.db.clone(), self.user.as_ref());
+        self.inner
+            .call_on_name(WELCOME, |view: &mut TextView| view.set_content(welcome));
+        let mentions = mentions_label(self.db.clone(), self.user.as_ref());
+        self.inner
+            .call_on_name(MENU, |view: &mut SelectView<HomeOption>| {
+                let index = view
+                    .iter()
+                    .position(|(_, item)| matches!(item, HomeOption::Mentions));
+                if let Some((label, _)) = index.and_then(|index| view.get_item_mut(index)) {
+                    *label = mentions.into();
+                }
+            });
+    }
+}
+
+impl View for HomeView {
+    fn draw(&self, printer: &Printer) {
+        self.inner.draw(printer)
+    }
+    fn layout(&mut self, size: Vec2) {
+        self.inner.layout(size)
+    }
+    fn needs_relayout(&self) -> bool {
+        self.inner.needs_relayout()
+    }
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        self.inner.required_size(constraint)
+    }
+    fn on_event(&mut self, event: Event) -> EventResult {
+        self.inner.on_event(event)
+    }
+    fn call_on_any(&mut self, selector: &Selector, cb: AnyCb) {
+        self.inner.call_on_any(selector, cb)
+    }
+    fn focus_view(&mut self, selector: &Selector) -> Result<EventResult, ViewNotFound> {
+        self.inner.focus_view(selector)
+    }
+    fn take_focus(&mut self, source: Direction) -> Result<EventResult, CannotFocus> {
+        self.refresh();
+        self.inner.take_focus(source)
+    }
+    fn important_area(&self, view_size: Vec2) -> Rect {
+        self.inner.important_area(view_size)
+    }
+    fn type_name(&self) -> &'static str {
+        "HomeView"
+    }
 }
@@ -2,9 +2,13 @@ pub mod search;
 pub mod viewer;
 
 use std::fs::{create_dir, remove_dir_all, rename};
+use std::ops::RangeInclusive;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+use log::warn;
+use rand::Rng;
 use tantivy::collector::TopDocs;
 use tantivy::query::{BooleanQuery, FuzzyTermQuery, Occur, Query, TermQuery};
 use tantivy::schema::{IndexRecordOption, STORED};
@@ -12,7 +16,8 @@ use tantivy::{
     schema::{Field, Schema, TEXT},
     Index, IndexWriter,
 };
-use tantivy::{ReloadPolicy, Searcher, Term};
+use tantivy::{DocAddress, ReloadPolicy, Searcher, Term};
+use tokio::task::spawn_blocking;
 use zim::{DirectoryEntry, MimeType, Target, Zim};
 
 lazy_static! {
@@ -23,6 +28,13 @@ pub(crate) fn push_library(lib: Library) {
     LIBRARY.lock().unwrap().push(lib);
 }
 
+/// Swaps in a freshly opened library, replacing any existing library with the same name.
+pub(crate) fn replace_library(lib: Library) {
+    let mut libraries = LIBRARY.lock().unwrap();
+    libraries.retain(|existing| existing.name != lib.name);
+    libraries.push(lib);
+}
+
 pub(crate) fn get_library(name: &str) -> Option<Library> {
     LIBRARY
         .lock()
@@ -38,6 +50,8 @@ static CONTENT_FIELD: &str = "content";
 static CLUSTER_FIELD: &str = "cluster";
 static BLOB_FIELD: &str = "blob";
 
+static REINDEXING: AtomicBool = AtomicBool::new(false);
+
 #[derive(Clone)]
 pub(crate) struct Library {
     name: String,
@@ -56,14 +70,45 @@ pub(crate) struct Article {
     pub(crate) content_html: String,
 }
 
+/// Clears `REINDEXING` when a reindex ends, however it ends.
+struct ReindexGuard;
+
+impl ReindexGuard {
+    fn acquire() -> Result<Self, anyhow::Error> {
+        if REINDEXING.swap(true, Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("A reindex is already in progress"));
+        }
+        Ok(ReindexGuard)
+    }
+}
+
+impl Drop for ReindexGuard {
+    fn drop(&mut self) {
+        REINDEXING.store(false, Ordering::SeqCst);
+    }
+}
+
 impl Library {
+    /// Opens a ZIM file, building its search index first if there isn't one yet. Indexing can take
+    /// a long time, so it runs on a blocking thread.
     pub(crate) async fn open<P: AsRef<Path> + Sized>(
         name: &str,
         zim_path: P,
         index_path: P,
+    ) -> Result<Self, anyhow::Error> {
+        let name = name.to_string();
+        let zim_path = zim_path.as_ref().to_path_buf();
+        let index_path = index_path.as_ref().to_path_buf();
+        spawn_blocking(move || Self::open_blocking(&name, &zim_path, &index_path)).await?
+    }
+
+    fn open_blocking(
+        name: &str,
+        zim_path: &Path,
+        index_path: &Path,
     ) -> Result<Self, anyhow::Error> {
         let zim = Zim::new(zim_path)?;
-        let index = Self::ensure_indexed(&zim, index_path).await.unwrap();
+        let index = Self::ensure_indexed(&zim, index_path)?;
         let reader = index
             .reader_builder()
             .reload_policy(ReloadPolicy::OnCommit)
@@ -89,7 +134,59 @@ impl Library {
         Ok(library)
     }
 
-    async fn ensure_indexed<P: AsRef<Path> + Sized>(
+    /// Rebuilds the search index for a ZIM file from scratch and opens the result. Sessions keep
+    /// searching the library that's already open until the caller swaps in the new one. The old
+    /// index directory is moved aside during the rebuild and put back if the rebuild fails.
+    pub(crate) async fn reindex<P: AsRef<Path> + Sized>(
+        name: &str,
+        zim_path: P,
+        index_path: P,
+    ) -> Result<Self, anyhow::Error> {
+        let _guard = ReindexGuard::acquire()?;
+        let name = name.to_string();
+        let zim_path = zim_path.as_ref().to_path_buf();
+        let index_path = index_path.as_ref().to_path_buf();
+        spawn_blocking(move || Self::reindex_blocking(&name, &zim_path, &index_path)).await?
+    }
+
+    fn reindex_blocking(
+        name: &str,
+        zim_path: &Path,
+        index_path: &Path,
+    ) -> Result<Self, anyhow::Error> {
+        let checksum = Zim::new(zim_path)?.checksum;
+        let final_path = index_path.join(format!("{:X}.idx", checksum));
+        let old_path = index_path.join(format!("{:X}.idx.old", checksum));
+        if old_path.exists() {
+            remove_dir_all(&old_path)?;
+        }
+        if final_path.exists() {
+            rename(&final_path, &old_path)?;
+        }
+        match Self::open_blocking(name, zim_path, index_path) {
+            Ok(library) => {
+                if old_path.exists() {
+                    remove_dir_all(&old_path)?;
+                }
+                Ok(library)
+            }
+            Err(err) => {
+                if old_path.exists() {
+                    if final_path.exists() {
+                        remove_dir_all(&final_path)?;
+                    }
+                    rename(&old_path, &final_path)?;
+                }
+                Err(err)
+            }
+        }
+    }
+
+    pub(crate) fn is_reindexing() -> bool {
+        REINDEXING.load(Ordering::SeqCst)
+    }
+
+    fn ensure_indexed<P: AsRef<Path> + Sized>(
         zim: &Zim,
         index_directory: P,
     ) -> Result<Index, anyhow::Error> {
@@ -143,7 +240,7 @@ impl Library {
                     Ok(_) => {
                         indexed += 1;
                     }
-                    Err(_) => todo!(),
+                    Err(err) => warn!("Skipped {} while indexing: {}", entry.url, err),
                 };
             }
             index_writer.commit()?;
@@ -192,6 +289,46 @@ impl Library {
         Ok(())
     }
 
+    /// Picks up to `count` different words, `lengths` letters long, out of the titles of random
+    /// articles. Only plain ASCII words are used so games don't have to deal with accents.
+    pub(crate) fn random_words(
+        &self,
+        count: usize,
+        lengths: RangeInclusive<usize>,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let mut rng = rand::thread_rng();
+        let segments = self.searcher.segment_readers();
+        let mut words: Vec<String> = Vec::new();
+        if segments.is_empty() {
+            return Ok(words);
+        }
+        for _ in 0..count * 50 {
+            if words.len() >= count {
+                break;
+            }
+            let segment = rng.gen_range(0..segments.len());
+            let max_doc = segments[segment].max_doc();
+            if max_doc == 0 {
+                continue;
+            }
+            let address = DocAddress::new(segment as u32, rng.gen_range(0..max_doc));
+            let title = match self.searcher.doc(address)?.get_first(self.title_field) {
+                Some(title) => title.as_text().unwrap_or_default().to_lowercase(),
+                None => continue,
+            };
+            for word in title.split(|c: char| !c.is_alphanumeric()) {
+                if lengths.contains(&word.len())
+                    && word.chars().all(|c| c.is_ascii_lowercase())
+                    && !words.iter().any(|existing| existing == word)
+                {
+                    words.push(word.to_string());
+                    break;
+                }
+            }
+        }
+        Ok(words)
+    }
+
     pub fn search(&self, title: &str, limit: usize) -> Result<Vec<Article>, anyhow::Error> {
         let mut title_queries = Vec::new();
         let mut content_queries = Vec::new();
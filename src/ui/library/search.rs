@@ -1,15 +1,21 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use ssh_ui::cursive::{
     direction::Direction,
     event::{AnyCb, Event, EventResult},
     view::{CannotFocus, Nameable, Resizable, Selector, ViewNotFound},
-    views::{DummyView, LinearLayout, NamedView, ResizedView, SelectView},
+    views::{DummyView, LinearLayout, NamedView, ResizedView, SelectView, TextView},
     Printer, Vec2, View,
 };
-use tokio::{spawn, sync::mpsc::Sender};
+use tokio::{spawn, sync::mpsc::Sender, time::sleep};
 
-use crate::ui::{labeled_edit_view::LabeledEditView, stack::get_stack};
+use crate::{
+    throttle::{self, Action, ThrottleError},
+    ui::{labeled_edit_view::LabeledEditView, stack::get_stack},
+};
 
 use super::{get_library, viewer::ReaderView, Article, Library};
 
@@ -60,13 +66,38 @@ async fn search_cb(
     Ok(())
 }
 
+/// Waits out a throttled search and takes a token for it, unless a later keystroke starts a
+/// search of its own first.
+async fn wait_to_search(
+    session_id: u64,
+    mut wait: u64,
+    search_result_repository: &Mutex<(u64, u64, Arc<Vec<Article>>, bool)>,
+) -> bool {
+    let counter = {
+        let mut result_tuple = search_result_repository.lock().unwrap();
+        result_tuple.0 += 1;
+        result_tuple.0
+    };
+    loop {
+        sleep(Duration::from_secs(wait)).await;
+        if search_result_repository.lock().unwrap().0 != counter {
+            return false;
+        }
+        match throttle::check(session_id, Action::Search) {
+            Ok(()) => return true,
+            Err(ThrottleError::Throttled(seconds)) => wait = seconds,
+        }
+    }
+}
+
+/// Returns whether the results are for what's in the search box now.
 fn update_search_results(
     sv: &mut SelectView<Article>,
     search_result_repository: Arc<Mutex<(u64, u64, Arc<Vec<Article>>, bool)>>,
-) {
+) -> bool {
     let mut result_tuple = search_result_repository.lock().unwrap();
     if !result_tuple.3 {
-        return;
+        return false;
     }
     sv.clear();
     for (idx, article) in result_tuple.2.iter().enumerate() {
@@ -76,6 +107,7 @@ fn update_search_results(
         );
     }
     result_tuple.3 = false;
+    result_tuple.1 == result_tuple.0
 }
 
 pub struct LibrarySearchView {
@@ -94,12 +126,30 @@ impl LibrarySearchView {
                 None,
                 "",
                 move |siv, text, _cursor| {
+                    // Every search decompresses articles, so fast typists are throttled too. This
+                    // runs on every keystroke, so the message is shown in place rather than as a
+                    // dialog, and the search is run once it's allowed so the results catch up.
+                    let session_id = get_stack(siv).session_id();
+                    let (status, wait) = match throttle::check(session_id, Action::Search) {
+                        Ok(()) => (String::new(), None),
+                        Err(err @ ThrottleError::Throttled(seconds)) => {
+                            (err.to_string(), Some(seconds))
+                        }
+                    };
+                    siv.call_on_name("library_search_status", |view: &mut TextView| {
+                        view.set_content(status)
+                    });
                     let max_results = siv.screen_size().y; // Upper bound.
                     let text = text.to_string();
                     let lib_name = lib_name.clone();
                     let search_result_repository = search_result_repository.clone();
                     let relayout_sender = relayout_sender.clone();
                     spawn(async move {
+                        if let Some(wait) = wait {
+                            if !wait_to_search(session_id, wait, &search_result_repository).await {
+                                return;
+                            }
+                        }
                         match search_cb(&text, max_results, &lib_name, search_result_repository)
                             .await
                         {
@@ -125,6 +175,7 @@ impl LibrarySearchView {
         });
         let mut layout = LinearLayout::vertical()
             .child(search_box)
+            .child(TextView::new("").with_name("library_search_status"))
             .child(DummyView)
             .child(results_box.with_name("library_search_results"));
         layout.set_focus_index(0).unwrap();
@@ -157,15 +208,26 @@ impl View for LibrarySearchView {
             let articles = self
                 .inner
                 .get_inner_mut()
-                .get_child_mut(2)
+                .get_child_mut(3)
                 .unwrap()
                 .as_any_mut()
                 .downcast_mut::<NamedView<SelectView<Article>>>()
                 .unwrap();
-            update_search_results(
+            let caught_up = update_search_results(
                 &mut articles.get_mut(),
                 self.search_result_repository.clone(),
             );
+            if caught_up {
+                self.inner
+                    .get_inner_mut()
+                    .get_child_mut(1)
+                    .unwrap()
+                    .as_any_mut()
+                    .downcast_mut::<NamedView<TextView>>()
+                    .unwrap()
+                    .get_mut()
+                    .set_content("");
+            }
         }
         self.inner.on_event(event)
     }
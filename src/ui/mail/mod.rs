@@ -0,0 +1,326 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        direction::Orientation,
+        utils::markup::StyledString,
+        view::{Nameable, Resizable, Scrollable},
+        views::{Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView},
+        Cursive, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    draft::DraftTarget,
+    mail::{MailEntry, MailUtil},
+    user::UserInfo,
+};
+
+use super::{
+    block_on,
+    editor::EditorView,
+    get_user,
+    labeled_edit_view::LabeledEditView,
+    markup::{render_markup, MARKUP_HELP},
+    stack::get_stack,
+};
+
+static MAIL_LIST: &str = "mail_list";
+static COMPOSE_TO_EDIT: &str = "mail_compose_to";
+static COMPOSE_SUBJECT_EDIT: &str = "mail_compose_subject";
+static COMPOSE_BODY_EDIT: &str = "mail_compose_body";
+
+enum MailOption {
+    Inbox,
+    Outbox,
+    Compose,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Folder {
+    Inbox,
+    Outbox,
+}
+
+pub fn mail_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>) -> Box<dyn View> {
+    let user = match get_user(db.clone(), key) {
+        Ok(user) => user,
+        Err(_) => {
+            return Box::new(TextView::new(
+                "Mail is only available to registered users. Set up your profile first.",
+            ))
+        }
+    };
+    let unread = block_on(MailUtil::new(db.clone()).unread_count(&user)).unwrap_or(0);
+    let select_view = SelectView::new()
+        .item(format!("(I)nbox ({} unread)", unread), MailOption::Inbox)
+        .item("(O)utbox", MailOption::Outbox)
+        .item("(C)ompose a message", MailOption::Compose)
+        .on_submit(move |siv, item| match item {
+            MailOption::Inbox => {
+                get_stack(siv)
+                    .push(folder_screen(db.clone(), user.clone(), Folder::Inbox))
+                    .unwrap();
+            }
+            MailOption::Outbox => {
+                get_stack(siv)
+                    .push(folder_screen(db.clone(), user.clone(), Folder::Outbox))
+                    .unwrap();
+            }
+            MailOption::Compose => {
+                get_stack(siv)
+                    .push(compose_screen(db.clone(), user.clone(), "", "", None, None))
+                    .unwrap();
+            }
+        });
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Private mail"))
+        .child(DummyView)
+        .child(select_view);
+    Box::new(layout)
+}
+
+fn load_folder(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: &UserInfo,
+    folder: Folder,
+) -> Vec<(String, MailEntry)> {
+    let mail_util = MailUtil::new(db);
+    let entries = match folder {
+        Folder::Inbox => block_on(mail_util.inbox(user)),
+        Folder::Outbox => block_on(mail_util.outbox(user)),
+    };
+    match entries {
+        Ok(entries) => entries
+            .into_iter()
+            .map(|entry| {
+                let (marker, other) = match folder {
+                    Folder::Inbox if !entry.message.read => ("*", &entry.sender),
+                    Folder::Inbox => (" ", &entry.sender),
+                    Folder::Outbox => (" ", &entry.recipient),
+                };
+                let label = format!(
+                    "{} {} {:16} {}",
+                    marker, entry.message.created, other, entry.message.subject
+                );
+                (label, entry)
+            })
+            .collect(),
+        Err(err) => {
+            error!("Failed to load mail: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+fn refresh_folder(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: &UserInfo,
+    folder: Folder,
+) {
+    let entries = load_folder(db, user, folder);
+    siv.call_on_name(MAIL_LIST, |list: &mut SelectView<MailEntry>| {
+        let selected = list.selected_id();
+        list.clear();
+        for (label, entry) in entries {
+            list.add_item(label, entry);
+        }
+        if let Some(selected) = selected {
+            list.set_selection(selected);
+        }
+    });
+}
+
+fn folder_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    folder: Folder,
+) -> Box<dyn View> {
+    let mut list = SelectView::<MailEntry>::new();
+    for (label, entry) in load_folder(db.clone(), &user, folder) {
+        list.add_item(label, entry);
+    }
+    let list = {
+        let db = db.clone();
+        let user = user.clone();
+        list.on_submit(move |siv, entry: &MailEntry| {
+            let conversation = conversation_screen(db.clone(), user.clone(), entry.message.id);
+            // Reading marks messages as read, so refresh before the folder leaves the top of the stack.
+            refresh_folder(siv, db.clone(), &user, folder);
+            get_stack(siv).push(conversation).unwrap();
+        })
+    };
+    let list = OnEventView::new(list.with_name(MAIL_LIST)).on_event('d', move |siv| {
+        let selected = siv
+            .call_on_name(MAIL_LIST, |list: &mut SelectView<MailEntry>| {
+                list.selection()
+            })
+            .flatten();
+        if let Some(entry) = selected {
+            let db = db.clone();
+            let user = user.clone();
+            let message_id = entry.message.id;
+            siv.add_layer(
+                Dialog::text(format!("Delete \"{}\"?", entry.message.subject))
+                    .button("Delete", move |siv| {
+                        siv.pop_layer();
+                        if let Err(err) =
+                            block_on(MailUtil::new(db.clone()).delete(&user, message_id))
+                        {
+                            siv.add_layer(Dialog::info(err.to_string()));
+                        }
+                        refresh_folder(siv, db.clone(), &user, folder);
+                    })
+                    .dismiss_button("Cancel"),
+            );
+        }
+    });
+    let title = match folder {
+        Folder::Inbox => "Inbox",
+        Folder::Outbox => "Outbox",
+    };
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!(
+            "{}. Enter: read  d: delete  Esc: back",
+            title
+        )))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
+
+fn conversation_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    message_id: i32,
+) -> Box<dyn View> {
+    let entries = match block_on(MailUtil::new(db.clone()).conversation(&user, message_id)) {
+        Ok(entries) => entries,
+        Err(err) => return Box::new(TextView::new(err.to_string())),
+    };
+    let mut text = StyledString::new();
+    for entry in entries.iter() {
+        text.append_plain(format!(
+            "From {} to {} at {}\nSubject: {}\n\n",
+            entry.sender, entry.recipient, entry.message.created, entry.message.subject,
+        ));
+        text.append(render_markup(&entry.message.body, &HashSet::new(), None));
+        text.append_plain("\n\n");
+    }
+    let latest = entries.last().cloned();
+    let body = OnEventView::new(TextView::new(text).scrollable().full_screen()).on_event(
+        'r',
+        move |siv| {
+            if let Some(latest) = &latest {
+                // Reply to whoever isn't us, so replying to our own message continues the thread.
+                let to = if Some(latest.message.sender) == user.id() {
+                    latest.recipient.clone()
+                } else {
+                    latest.sender.clone()
+                };
+                let subject = if latest.message.subject.starts_with("Re: ") {
+                    latest.message.subject.clone()
+                } else {
+                    format!("Re: {}", latest.message.subject)
+                };
+                get_stack(siv)
+                    .push(compose_screen(
+                        db.clone(),
+                        user.clone(),
+                        &to,
+                        &subject,
+                        Some(latest.message.id),
+                        Some(latest),
+                    ))
+                    .unwrap();
+            }
+        },
+    );
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("r: reply  Esc: back"))
+        .child(DummyView)
+        .child(body);
+    Box::new(layout)
+}
+
+fn mail_target(to: &str) -> DraftTarget {
+    DraftTarget::Mail {
+        recipient: to.trim().to_string(),
+    }
+}
+
+pub(crate) fn compose_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    user: UserInfo,
+    to: &str,
+    subject: &str,
+    reply_to: Option<i32>,
+    quoted: Option<&MailEntry>,
+) -> Box<dyn View> {
+    let min_width = 10;
+    let to_edit = LabeledEditView::new(
+        "To:",
+        Some(min_width),
+        to,
+        |siv, to, _| {
+            // Drafts are kept per recipient, so follow whoever the message is addressed to now.
+            siv.call_on_name(COMPOSE_BODY_EDIT, |editor: &mut EditorView| {
+                editor.retarget(mail_target(to))
+            });
+        },
+        |siv, _| {
+            siv.focus_name(COMPOSE_SUBJECT_EDIT).unwrap();
+        },
+        COMPOSE_TO_EDIT,
+    );
+    let subject_edit = LabeledEditView::new(
+        "Subject:",
+        Some(min_width),
+        subject,
+        |_, _, _| {},
+        |siv, _| {
+            siv.focus_name(COMPOSE_BODY_EDIT).unwrap();
+        },
+        COMPOSE_SUBJECT_EDIT,
+    );
+    let user_id = user.id();
+    let target = mail_target(to);
+    let mut editor = {
+        let db = db.clone();
+        EditorView::new("", move |siv, body| {
+            let to = siv
+                .call_on_name(COMPOSE_TO_EDIT, |edit: &mut EditView| edit.get_content())
+                .unwrap();
+            let subject = siv
+                .call_on_name(COMPOSE_SUBJECT_EDIT, |edit: &mut EditView| {
+                    edit.get_content()
+                })
+                .unwrap();
+            block_on(MailUtil::new(db.clone()).send(&user, &to, &subject, body, reply_to))?;
+            get_stack(siv).pop(siv).unwrap();
+            siv.add_layer(Dialog::info(format!("Message sent to {}.", to.trim())));
+            Ok(())
+        })
+    }
+    .draft(db, user_id, target);
+    if let Some(quoted) = quoted {
+        editor = editor.quote(&quoted.sender, &quoted.message.body);
+    }
+    let mut layout = LinearLayout::new(Orientation::Vertical)
+        .child(TextView::new(format!(
+            "Compose a message. Fill in the recipient and subject, then write below.\nFormatting: {}",
+            MARKUP_HELP
+        )))
+        .child(to_edit.full_width())
+        .child(subject_edit.full_width())
+        .child(editor.with_name(COMPOSE_BODY_EDIT));
+    let focus = if to.is_empty() { 1 } else { 3 };
+    layout.set_focus_index(focus).unwrap();
+    Box::new(layout.full_screen())
+}
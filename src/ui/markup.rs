@@ -0,0 +1,287 @@
+//! A small markdown subset for posts, mail and chat: `**bold**`, `*italics*`, `` `code` ``,
+//! fenced code blocks, `>` quotes, `-`/`1.` lists and `[text](url)` links. A backslash before a
+//! punctuation character shows it literally, and a message that starts with a backslash followed by
+//! a space or line break is shown exactly as typed after it.
+
+use std::collections::HashSet;
+
+use ssh_ui::cursive::{
+    theme::{BaseColor, Color, Effect, Style},
+    utils::markup::StyledString,
+};
+
+use super::mentions::append_mentions;
+
+pub static MARKUP_HELP: &str =
+    "**bold** *italics* `code` ```code block``` > quote - list [text](url) \\ escapes";
+
+fn code_style() -> Style {
+    Color::Light(BaseColor::Yellow).into()
+}
+
+fn aside_style() -> Style {
+    Color::Light(BaseColor::Black).into()
+}
+
+fn with_effect(style: Style, effect: Effect) -> Style {
+    Style::merge(&[style, effect.into()])
+}
+
+/// Renders `text` for display. `known` and `own_handle` are used to highlight @mentions.
+pub fn render_markup(
+    text: &str,
+    known: &HashSet<String>,
+    own_handle: Option<&str>,
+) -> StyledString {
+    // Works in single-line chat as well as in posts.
+    if let Some(literal) = text
+        .strip_prefix('\\')
+        .and_then(|rest| rest.strip_prefix([' ', '\n']))
+    {
+        return StyledString::plain(literal);
+    }
+    let mut renderer = Renderer {
+        rendered: StyledString::new(),
+        known,
+        own_handle,
+    };
+    let mut in_code_block = false;
+    let mut first = true;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if !first {
+            renderer.rendered.append_plain("\n");
+        }
+        first = false;
+        if in_code_block {
+            renderer
+                .rendered
+                .append_styled(format!("  {}", line), code_style());
+        } else {
+            renderer.line(line);
+        }
+    }
+    renderer.rendered
+}
+
+/// Finds the closing `delimiter` for one opened just before `from`, skipping escaped characters.
+fn find_closing(text: &str, from: usize, delimiter: &str) -> Option<usize> {
+    let mut index = from;
+    while index < text.len() {
+        let rest = &text[index..];
+        if let Some(escaped) = rest.strip_prefix('\\') {
+            index += 1 + escaped.chars().next().map_or(0, char::len_utf8);
+            continue;
+        }
+        if rest.starts_with(delimiter) && index > from {
+            let before = text[..index].chars().next_back();
+            let after = text[index + delimiter.len()..].chars().next();
+            let closes = before.map_or(false, |before| !before.is_whitespace())
+                // Underscores inside words, like snake_case, aren't emphasis.
+                && !(delimiter.starts_with('_') && after.map_or(false, char::is_alphanumeric));
+            if closes {
+                return Some(index);
+            }
+        }
+        index += rest.chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+struct Renderer<'a> {
+    rendered: StyledString,
+    known: &'a HashSet<String>,
+    own_handle: Option<&'a str>,
+}
+
+impl<'a> Renderer<'a> {
+    fn plain(&mut self, text: &str, style: Style) {
+        append_mentions(&mut self.rendered, text, style, self.known, self.own_handle);
+    }
+
+    fn line(&mut self, line: &str) {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        if trimmed.starts_with('>') {
+            let mut depth = 0;
+            let mut rest = trimmed;
+            while let Some(inner) = rest.strip_prefix('>') {
+                depth += 1;
+                rest = inner.trim_start();
+            }
+            self.rendered
+                .append_styled("│ ".repeat(depth), aside_style());
+            self.inline(rest, Style::none());
+            return;
+        }
+        for bullet in ["- ", "* ", "+ "] {
+            if let Some(item) = trimmed.strip_prefix(bullet) {
+                self.rendered.append_plain(format!("{}• ", indent));
+                self.inline(item, Style::none());
+                return;
+            }
+        }
+        let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits > 0 && trimmed[digits..].starts_with(". ") {
+            self.rendered
+                .append_plain(format!("{}{}", indent, &trimmed[..digits + 2]));
+            self.inline(&trimmed[digits + 2..], Style::none());
+            return;
+        }
+        self.inline(line, Style::none());
+    }
+
+    fn inline(&mut self, text: &str, style: Style) {
+        let mut plain_start = 0;
+        let mut index = 0;
+        while index < text.len() {
+            let rest = &text[index..];
+            let c = rest.chars().next().unwrap();
+            let next = rest[c.len_utf8()..].chars().next();
+            if c == '\\' && next.map_or(false, |next| next.is_ascii_punctuation()) {
+                self.plain(&text[plain_start..index], style);
+                plain_start = index + 1;
+                index += 2;
+                continue;
+            }
+            if c == '`' {
+                if let Some(end) = rest[1..].find('`') {
+                    self.plain(&text[plain_start..index], style);
+                    self.rendered
+                        .append_styled(&rest[1..1 + end], Style::merge(&[style, code_style()]));
+                    index += end + 2;
+                    plain_start = index;
+                    continue;
+                }
+            }
+            if c == '*' || c == '_' {
+                // One marker is italics, two bold and three both.
+                let run = rest.chars().take(3).take_while(|&other| other == c).count();
+                let delimiter = &rest[..run];
+                let start = index + delimiter.len();
+                let opens = text[..index]
+                    .chars()
+                    .next_back()
+                    .map_or(true, |before| c == '*' || !before.is_alphanumeric())
+                    && !text[start..]
+                        .chars()
+                        .next()
+                        .map_or(true, char::is_whitespace);
+                if let Some(end) = opens
+                    .then(|| find_closing(text, start, delimiter))
+                    .flatten()
+                {
+                    self.plain(&text[plain_start..index], style);
+                    let inner_style = match run {
+                        1 => with_effect(style, Effect::Italic),
+                        2 => with_effect(style, Effect::Bold),
+                        _ => with_effect(with_effect(style, Effect::Bold), Effect::Italic),
+                    };
+                    self.inline(&text[start..end], inner_style);
+                    index = end + delimiter.len();
+                    plain_start = index;
+                    continue;
+                }
+            }
+            if c == '[' {
+                // Only the nearest `]` can close the link text, so in `[a] b [c](d)` just the
+                // second pair is a link.
+                let link = rest
+                    .find(']')
+                    .filter(|&middle| {
+                        rest[middle + 1..].starts_with('(') && !rest[1..middle].contains('[')
+                    })
+                    .and_then(|middle| {
+                        rest[middle + 2..]
+                            .find(')')
+                            .map(|end| (middle, middle + 2 + end))
+                    });
+                if let Some((middle, end)) = link {
+                    self.plain(&text[plain_start..index], style);
+                    self.inline(&rest[1..middle], with_effect(style, Effect::Underline));
+                    self.rendered
+                        .append_styled(format!(" <{}>", &rest[middle + 2..end]), aside_style());
+                    index += end + 1;
+                    plain_start = index;
+                    continue;
+                }
+            }
+            index += c.len_utf8();
+        }
+        self.plain(&text[plain_start..], style);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes out how `text` renders, with each styled run wrapped in tags like `<b>..</>`.
+    fn describe(text: &str) -> String {
+        let rendered = render_markup(text, &HashSet::new(), None);
+        let mut described = String::new();
+        for span in rendered.spans() {
+            let mut tags = Vec::new();
+            if span.attr.effects.contains(Effect::Bold) {
+                tags.push("b");
+            }
+            if span.attr.effects.contains(Effect::Italic) {
+                tags.push("i");
+            }
+            if span.attr.effects.contains(Effect::Underline) {
+                tags.push("u");
+            }
+            if span.attr.color == code_style().color {
+                tags.push("code");
+            }
+            if span.attr.color == aside_style().color {
+                tags.push("aside");
+            }
+            if tags.is_empty() {
+                described.push_str(span.content);
+            } else {
+                described.push_str(&format!("<{}>{}</>", tags.join(" "), span.content));
+            }
+        }
+        described
+    }
+
+    #[test]
+    fn renders_markup() {
+        let cases = [
+            ("plain text", "plain text"),
+            ("**bold** text", "<b>bold</> text"),
+            ("*italics* and _more_", "<i>italics</> and <i>more</>"),
+            ("***both***", "<b i>both</>"),
+            (
+                "**bold with *italics* inside**",
+                "<b>bold with </><b i>italics</><b> inside</>",
+            ),
+            ("snake_case_name", "snake_case_name"),
+            ("2 * 3 * 4", "2 * 3 * 4"),
+            ("`*code*`", "<code>*code*</>"),
+            ("\\*not italics\\*", "*not italics*"),
+            (
+                "[docs](https://example.com)",
+                "<u>docs</><aside> <https://example.com></>",
+            ),
+            ("[a] b [c](d)", "[a] b <u>c</><aside> <d></>"),
+            ("[a](b", "[a](b"),
+            ("> quoted", "<aside>│ </>quoted"),
+            (">> nested", "<aside>│ │ </>nested"),
+            ("- item", "• item"),
+            ("  * nested item", "  • nested item"),
+            ("12. item", "12. item"),
+            ("```\nlet x = 1;\n```", "<code>  let x = 1;</>"),
+            ("\\ **as typed**", "**as typed**"),
+            ("\\\n**as typed**\n> too", "**as typed**\n> too"),
+            ("\\**escaped**", "*<i>escaped</>*"),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(describe(text), expected, "rendering {:?}", text);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        event::Event,
+        view::{Nameable, Resizable, Scrollable},
+        views::{Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView},
+        Cursive, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    profile::{MemberOrder, ProfileUtil},
+    session::list_sessions,
+    user::Role,
+};
+
+use super::{
+    block_on, get_user, labeled_edit_view::LabeledEditView, profile::page::profile_page,
+    stack::get_stack,
+};
+
+static MEMBER_LIST: &str = "member_list";
+static MEMBER_SEARCH_EDIT: &str = "member_search";
+static ONLINE_LIST: &str = "online_list";
+
+fn load_members(
+    db: Arc<Mutex<DatabaseConnection>>,
+    search: &str,
+    order: MemberOrder,
+) -> Vec<(String, i32)> {
+    match block_on(ProfileUtil::new(db).members(search, order)) {
+        Ok(members) => members
+            .into_iter()
+            .map(|member| {
+                let joined = member.user.joined.unwrap_or_default();
+                let label = format!(
+                    "{:20} {:9} {:10} {:>6}  {}",
+                    member.user.handle,
+                    member.role.to_string(),
+                    joined.get(..10).unwrap_or(&joined),
+                    member.posts,
+                    member.user.status.unwrap_or_default()
+                );
+                (label, member.user.id)
+            })
+            .collect(),
+        Err(err) => {
+            error!("Failed to list members: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+fn refresh_members(siv: &mut Cursive, db: Arc<Mutex<DatabaseConnection>>, order: MemberOrder) {
+    let search = siv
+        .call_on_name(MEMBER_SEARCH_EDIT, |edit: &mut EditView| edit.get_content())
+        .unwrap();
+    let members = load_members(db, &search, order);
+    siv.call_on_name(MEMBER_LIST, |list: &mut SelectView<i32>| {
+        list.clear();
+        for (label, user_id) in members {
+            list.add_item(label, user_id);
+        }
+    });
+}
+
+/// Every registered member, searchable by handle.
+pub fn members_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>) -> Box<dyn View> {
+    let order = Arc::new(Mutex::new(MemberOrder::Handle));
+    let search = {
+        let db = db.clone();
+        let order = order.clone();
+        LabeledEditView::new(
+            "Search:",
+            None,
+            "",
+            move |siv, _, _| refresh_members(siv, db.clone(), *order.blocking_lock()),
+            |siv, _| {
+                siv.focus_name(MEMBER_LIST).unwrap();
+            },
+            MEMBER_SEARCH_EDIT,
+        )
+    };
+    let mut list = SelectView::<i32>::new();
+    for (label, user_id) in load_members(db.clone(), "", MemberOrder::Handle) {
+        list.add_item(label, user_id);
+    }
+    let list = {
+        let db = db.clone();
+        list.on_submit(move |siv, user_id: &i32| {
+            get_stack(siv)
+                .push(profile_page(db.clone(), key.clone(), *user_id))
+                .unwrap();
+        })
+    };
+    let mut list = OnEventView::new(list.with_name(MEMBER_LIST));
+    for (trigger, sort) in [
+        ('h', MemberOrder::Handle),
+        ('j', MemberOrder::Joined),
+        ('p', MemberOrder::Posts),
+    ] {
+        let db = db.clone();
+        let order = order.clone();
+        list = list.on_event(trigger, move |siv| {
+            *order.blocking_lock() = sort;
+            refresh_members(siv, db.clone(), sort);
+        });
+    }
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Enter: view profile  h/j/p: sort by handle/join date/posts  Esc: back",
+        ))
+        .child(search)
+        .child(DummyView)
+        .child(TextView::new(format!(
+            "{:20} {:9} {:10} {:>6}  {}",
+            "Handle", "Role", "Joined", "Posts", "Status"
+        )))
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    if seconds < 3600 {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    } else {
+        format!("{}h{:02}m", seconds / 3600, seconds / 60 % 60)
+    }
+}
+
+/// Lists the sessions, with where they called from when `show_addresses` is set.
+fn load_online(show_addresses: bool) -> Vec<(String, Option<String>)> {
+    let mut sessions = list_sessions();
+    sessions.sort_by_key(|session| session.last_activity.elapsed());
+    sessions
+        .into_iter()
+        .map(|session| {
+            let mut label = format!(
+                "{:20} {:16} {:>7} {:>7}",
+                session.handle.as_deref().unwrap_or("(anonymous)"),
+                session.screen,
+                format_duration(session.last_activity.elapsed()),
+                format_duration(session.started.elapsed()),
+            );
+            if show_addresses {
+                label.push_str(&format!("  {}", session.address.as_deref().unwrap_or("-")));
+            }
+            (label, session.handle)
+        })
+        .collect()
+}
+
+fn refresh_online(siv: &mut Cursive, show_addresses: bool) {
+    let sessions = load_online(show_addresses);
+    siv.call_on_name(ONLINE_LIST, |list: &mut SelectView<Option<String>>| {
+        let selected = list.selected_id();
+        list.clear();
+        for (label, handle) in sessions {
+            list.add_item(label, handle);
+        }
+        if let Some(selected) = selected {
+            list.set_selection(selected);
+        }
+    });
+}
+
+/// Everyone connected right now, most recently active first. Idle times tick along on their own.
+/// Sysops also see where each session called from.
+pub fn whos_online_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+) -> Box<dyn View> {
+    let show_addresses = matches!(
+        get_user(db.clone(), key.clone()),
+        Ok(user) if user.has_role(Role::Sysop)
+    );
+    let mut list = SelectView::<Option<String>>::new();
+    for (label, handle) in load_online(show_addresses) {
+        list.add_item(label, handle);
+    }
+    let list = list.on_submit(move |siv, handle: &Option<String>| {
+        let handle = match handle {
+            Some(handle) => handle,
+            None => return siv.add_layer(Dialog::info("Anonymous visitors have no profile.")),
+        };
+        match block_on(ProfileUtil::new(db.clone()).find_by_handle(handle)) {
+            Ok(Some(user_id)) => {
+                get_stack(siv)
+                    .push(profile_page(db.clone(), key.clone(), user_id))
+                    .unwrap();
+            }
+            Ok(None) => siv.add_layer(Dialog::info(format!("Nobody is called {}.", handle))),
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        }
+    });
+    let list = OnEventView::new(list.with_name(ONLINE_LIST))
+        .on_pre_event(Event::Refresh, move |siv| {
+            refresh_online(siv, show_addresses)
+        })
+        .on_event('r', move |siv| refresh_online(siv, show_addresses));
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("Enter: view profile  r: refresh  Esc: back"))
+        .child(DummyView)
+        .child(TextView::new(format!(
+            "{:20} {:16} {:>7} {:>7}{}",
+            "Handle",
+            "Where",
+            "Idle",
+            "On for",
+            if show_addresses { "  From" } else { "" }
+        )))
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
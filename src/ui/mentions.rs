@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use log::error;
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        theme::{BaseColor, Color, Effect, Style},
+        utils::markup::StyledString,
+        view::{Resizable, Scrollable},
+        views::{Dialog, DummyView, LinearLayout, SelectView, TextView},
+        View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    forum::ForumUtil,
+    mention::{find_mentions, MentionEntry, MentionUtil},
+};
+
+use super::{block_on, forum::thread::thread_screen, get_user, stack::get_stack};
+
+/// Appends `text` in `style`, with `@handle` tokens that name a known user picked out. Mentions of
+/// `own_handle` stand out more.
+pub fn append_mentions(
+    styled: &mut StyledString,
+    text: &str,
+    style: Style,
+    known: &HashSet<String>,
+    own_handle: Option<&str>,
+) {
+    let mut position = 0;
+    for token in find_mentions(text) {
+        if !known.contains(&token.handle) {
+            continue;
+        }
+        styled.append_styled(&text[position..token.start], style);
+        let highlight = if Some(token.handle.as_str()) == own_handle {
+            Style::merge(&[style, Effect::Reverse.into(), Effect::Bold.into()])
+        } else {
+            Style::merge(&[
+                style,
+                Color::Light(BaseColor::Cyan).into(),
+                Effect::Bold.into(),
+            ])
+        };
+        styled.append_styled(&text[token.start..token.end], highlight);
+        position = token.end;
+    }
+    styled.append_styled(&text[position..], style);
+}
+
+pub fn mentions_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+) -> Box<dyn View> {
+    let user = match get_user(db.clone(), key.clone()) {
+        Ok(user) => user,
+        Err(_) => {
+            return Box::new(TextView::new(
+                "Mentions are only tracked for registered users. Set up your profile first.",
+            ))
+        }
+    };
+    let mention_util = MentionUtil::new(db.clone());
+    let mentions = match block_on(mention_util.mentions(&user)) {
+        Ok(mentions) => mentions,
+        Err(err) => {
+            error!("Failed to load mentions: {}", err);
+            Vec::new()
+        }
+    };
+    if let Err(err) = block_on(mention_util.mark_seen(&user)) {
+        error!("Failed to mark mentions seen: {}", err);
+    }
+    let mut list = SelectView::<MentionEntry>::new();
+    for entry in mentions {
+        let label = format!(
+            "{} {} {:16} in {}: {}",
+            if entry.mention.seen { " " } else { "*" },
+            entry.mention.created,
+            entry.author,
+            entry.mention.context,
+            entry.mention.excerpt
+        );
+        list.add_item(label, entry);
+    }
+    let list = list.on_submit(move |siv, entry: &MentionEntry| {
+        let post_id = match entry.mention.post {
+            Some(post_id) => post_id,
+            None => {
+                siv.add_layer(Dialog::info(format!(
+                    "{} in {}:\n\n{}",
+                    entry.author, entry.mention.context, entry.mention.excerpt
+                )));
+                return;
+            }
+        };
+        match block_on(ForumUtil::new(db.clone()).post(post_id)) {
+            Ok(post) => {
+                get_stack(siv)
+                    .push(thread_screen(
+                        db.clone(),
+                        key.clone(),
+                        post.thread,
+                        Some(post.id),
+                    ))
+                    .unwrap();
+            }
+            Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+        }
+    });
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(
+            "Mentions of you. Enter: go to the post  Esc: back",
+        ))
+        .child(DummyView)
+        .child(list.scrollable().full_screen());
+    Box::new(layout)
+}
@@ -1,3 +1,4 @@
+use std::future::Future;
 use std::sync::Arc;
 
 use sea_orm::DatabaseConnection;
@@ -6,12 +7,20 @@ use tokio::{runtime::Handle, sync::Mutex, task::block_in_place};
 
 use crate::user::{UserInfo, UserUtil};
 
+pub(crate) mod admin;
+pub(crate) mod editor;
 pub(crate) mod forum;
+pub(crate) mod games;
 pub(crate) mod home;
 pub(crate) mod labeled_edit_view;
 pub(crate) mod library;
+pub(crate) mod mail;
+pub(crate) mod markup;
+pub(crate) mod members;
+pub(crate) mod mentions;
 pub(crate) mod profile;
 pub(crate) mod stack;
+pub(crate) mod toast;
 
 pub fn get_user(
     db: Arc<Mutex<DatabaseConnection>>,
@@ -24,3 +33,8 @@ pub fn get_user(
         })
     })
 }
+
+/// Runs a future to completion from inside a synchronous cursive callback.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    block_in_place(move || Handle::current().block_on(future))
+}
@@ -5,7 +5,7 @@ use ssh_ui::{
     cursive::{
         direction::Orientation,
         view::Resizable,
-        views::{LinearLayout, TextView},
+        views::{Dialog, LinearLayout, TextView},
         View,
     },
     russh_keys::key::PublicKey,
@@ -16,6 +16,8 @@ use crate::user::UserUtil;
 
 use super::{labeled_edit_view::LabeledEditView, stack::get_stack};
 
+pub(crate) mod page;
+
 pub fn profile_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>) -> Box<dyn View> {
     let user = {
         let db = db.clone();
@@ -75,7 +77,7 @@ pub fn profile_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>
                     let key = key.clone();
                     let handle_val = handle_val.clone();
                     let contact_val = contact_val_submit.clone();
-                    block_in_place(move || {
+                    let result = block_in_place(move || {
                         Handle::current().block_on(async move {
                             let user_util = UserUtil::new(db.clone(), Some(key.clone()));
                             user_util
@@ -84,10 +86,14 @@ pub fn profile_screen(db: Arc<Mutex<DatabaseConnection>>, key: Option<PublicKey>
                                     &*contact_val.lock().await,
                                 )
                                 .await
-                                .unwrap();
-                        });
+                        })
                     });
-                    get_stack(siv).pop(siv).unwrap();
+                    match result {
+                        Ok(()) => {
+                            get_stack(siv).pop(siv).unwrap();
+                        }
+                        Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                    }
                 },
                 contact_label,
             )
@@ -0,0 +1,232 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use sea_orm::DatabaseConnection;
+use ssh_ui::{
+    cursive::{
+        theme::Effect,
+        utils::markup::StyledString,
+        view::{Nameable, Resizable, Scrollable},
+        views::{Dialog, DummyView, EditView, LinearLayout, OnEventView, SelectView, TextView},
+        Cursive, View,
+    },
+    russh_keys::key::PublicKey,
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    profile::{Profile, ProfileUtil},
+    session::list_sessions,
+    ui::{
+        block_on,
+        editor::EditorView,
+        forum::thread::thread_screen,
+        get_user,
+        mail::compose_screen,
+        markup::{render_markup, MARKUP_HELP},
+        stack::get_stack,
+    },
+    user::UserInfo,
+};
+
+use super::profile_screen;
+
+static STATUS_EDIT: &str = "profile_status_edit";
+
+/// Shows a user's profile to anyone. The owner can change their status and bio from here.
+pub fn profile_page(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    user_id: i32,
+) -> Box<dyn View> {
+    let profile = match block_on(ProfileUtil::new(db.clone()).profile(user_id)) {
+        Ok(profile) => profile,
+        Err(err) => return Box::new(TextView::new(err.to_string())),
+    };
+    let viewer = get_user(db.clone(), key.clone()).ok();
+    let owner = viewer.clone().filter(|viewer| viewer.id() == Some(user_id));
+    let mut threads = SelectView::new();
+    for thread in profile.recent_threads.iter() {
+        threads.add_item(thread.name.clone(), thread.id);
+    }
+    let threads = {
+        let db = db.clone();
+        let key = key.clone();
+        threads.on_submit(move |siv, thread_id: &i32| {
+            get_stack(siv)
+                .push(thread_screen(db.clone(), key.clone(), *thread_id, None))
+                .unwrap();
+        })
+    };
+    let mut help = "Enter: open thread  m: send mail  Esc: back".to_string();
+    if owner.is_some() {
+        help.push_str("\ns: set status  b: edit bio  e: edit handle and contact");
+    }
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(describe(&profile)))
+        .child(DummyView)
+        .child(TextView::new("Recently active in:"))
+        .child(threads)
+        .child(DummyView)
+        .child(TextView::new(help))
+        .scrollable()
+        .full_screen();
+    let mut view = {
+        let db = db.clone();
+        let handle = profile.user.handle.clone();
+        OnEventView::new(layout).on_event('m', move |siv| match &viewer {
+            Some(viewer) => {
+                get_stack(siv)
+                    .push(compose_screen(
+                        db.clone(),
+                        viewer.clone(),
+                        &handle,
+                        "",
+                        None,
+                        None,
+                    ))
+                    .unwrap();
+            }
+            None => siv.add_layer(Dialog::info("Set up your profile before sending mail.")),
+        })
+    };
+    if let Some(user) = owner {
+        let status = profile.user.status.clone().unwrap_or_default();
+        let bio = profile.user.bio.clone().unwrap_or_default();
+        let (db_status, key_status, user_status) = (db.clone(), key.clone(), user.clone());
+        let (db_bio, key_bio) = (db.clone(), key.clone());
+        view = view
+            .on_event('s', move |siv| {
+                status_dialog(
+                    siv,
+                    db_status.clone(),
+                    key_status.clone(),
+                    user_status.clone(),
+                    &status,
+                )
+            })
+            .on_event('b', move |siv| {
+                get_stack(siv)
+                    .push(bio_screen(
+                        db_bio.clone(),
+                        key_bio.clone(),
+                        user.clone(),
+                        &bio,
+                    ))
+                    .unwrap();
+            })
+            .on_event('e', move |siv| {
+                get_stack(siv)
+                    .push(profile_screen(db.clone(), key.clone()))
+                    .unwrap();
+            });
+    }
+    Box::new(view)
+}
+
+fn describe(profile: &Profile) -> StyledString {
+    let user = &profile.user;
+    let mut text = StyledString::styled(&user.handle, Effect::Bold);
+    text.append_plain(format!(" ({})", profile.role));
+    if let Some(status) = &user.status {
+        text.append_plain("\n");
+        text.append_styled(status, Effect::Italic);
+    }
+    let online = list_sessions()
+        .iter()
+        .any(|session| session.handle.as_ref() == Some(&user.handle));
+    let last_seen = match (&user.last_seen, online) {
+        (_, true) => "online now".to_string(),
+        (Some(last_seen), false) => format!("{} UTC", last_seen),
+        (None, false) => "never".to_string(),
+    };
+    let joined = match &user.joined {
+        Some(joined) => format!("{} UTC", joined),
+        None => "before we kept track".to_string(),
+    };
+    text.append_plain(format!(
+        "\n\nJoined: {}\nLast seen: {}\nPosts: {}\n",
+        joined, last_seen, profile.posts
+    ));
+    if let Some(contact) = user
+        .contact
+        .as_deref()
+        .filter(|contact| !contact.is_empty())
+    {
+        text.append_plain(format!("Contact: {}\n", contact));
+    }
+    if let Some(bio) = &user.bio {
+        text.append_plain("\n");
+        text.append(render_markup(bio, &HashSet::new(), None));
+        text.append_plain("\n");
+    }
+    text
+}
+
+/// Replaces the profile page on top of the stack with a fresh copy, after `pops` screens above
+/// it have been closed.
+fn reopen(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    user: &UserInfo,
+    pops: usize,
+) {
+    let mut stack = get_stack(siv);
+    for _ in 0..pops + 1 {
+        stack.pop(siv).unwrap();
+    }
+    if let Some(user_id) = user.id() {
+        stack.push(profile_page(db, key, user_id)).unwrap();
+    }
+}
+
+fn status_dialog(
+    siv: &mut Cursive,
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    user: UserInfo,
+    status: &str,
+) {
+    let edit = EditView::new()
+        .content(status)
+        .with_name(STATUS_EDIT)
+        .min_width(50);
+    siv.add_layer(
+        Dialog::around(edit)
+            .title("Status")
+            .button("Ok", move |siv| {
+                let status = siv
+                    .call_on_name(STATUS_EDIT, |edit: &mut EditView| edit.get_content())
+                    .unwrap();
+                match block_on(ProfileUtil::new(db.clone()).set_status(&user, &status)) {
+                    Ok(()) => {
+                        siv.pop_layer();
+                        reopen(siv, db.clone(), key.clone(), &user, 0);
+                    }
+                    Err(err) => siv.add_layer(Dialog::info(err.to_string())),
+                }
+            })
+            .dismiss_button("Cancel"),
+    );
+}
+
+fn bio_screen(
+    db: Arc<Mutex<DatabaseConnection>>,
+    key: Option<PublicKey>,
+    user: UserInfo,
+    bio: &str,
+) -> Box<dyn View> {
+    let editor = EditorView::new(bio, move |siv, body| {
+        block_on(ProfileUtil::new(db.clone()).set_bio(&user, body))?;
+        reopen(siv, db.clone(), key.clone(), &user, 1);
+        Ok(())
+    });
+    let layout = LinearLayout::vertical()
+        .child(TextView::new(format!(
+            "Tell people about yourself.\nFormatting: {}",
+            MARKUP_HELP
+        )))
+        .child(editor);
+    Box::new(layout.full_screen())
+}
@@ -11,6 +11,10 @@ use thiserror::Error;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::Mutex;
 
+use crate::session::set_session_screen;
+
+use super::toast::Toasts;
+
 #[derive(Debug, Error)]
 pub enum StackError {
     #[error("Error during force relayout")]
@@ -27,28 +31,50 @@ pub fn get_stack<'a>(siv: &'a mut Cursive) -> ViewRef<Stack> {
 
 pub struct Stack {
     stack: Arc<Mutex<Vec<Box<dyn View>>>>,
+    /// What each screen on the stack is called in the who's-online list.
+    screens: Arc<Mutex<Vec<&'static str>>>,
+    session_id: u64,
     dirty: bool,
     relayout_sender: Sender<()>,
+    toasts: Toasts,
     _db: Arc<Mutex<DatabaseConnection>>,
 }
 
 impl Stack {
     pub fn new(
         siv: &mut Cursive,
+        session_id: u64,
         relayout_sender: Sender<()>,
         db: Arc<Mutex<DatabaseConnection>>,
     ) -> Self {
         let mut stack = Self {
             stack: Arc::new(Mutex::new(Vec::new())),
+            screens: Arc::new(Mutex::new(Vec::new())),
+            session_id,
             dirty: true,
             relayout_sender,
+            toasts: Toasts::default(),
             _db: db,
         };
         stack.setup_esc(siv);
         stack
     }
 
+    /// Pushes a screen that belongs to the same part of the board as the one below it.
     pub fn push(&mut self, view: Box<dyn View>) -> Result<(), StackError> {
+        let screen = self.screens.blocking_lock().last().copied().unwrap_or("");
+        self.push_screen(view, screen)
+    }
+
+    /// Pushes a screen that starts a new part of the board, called `screen` in the who's-online
+    /// list.
+    pub fn push_screen(
+        &mut self,
+        view: Box<dyn View>,
+        screen: &'static str,
+    ) -> Result<(), StackError> {
+        self.screens.blocking_lock().push(screen);
+        set_session_screen(self.session_id, screen);
         self.dirty = true;
         self.stack.blocking_lock().push(view);
         self.relayout_sender
@@ -58,7 +84,13 @@ impl Stack {
     }
 
     pub fn pop(&mut self, siv: &mut Cursive) -> Result<Box<dyn View>, StackError> {
-        let ret = self.stack.blocking_lock().pop();
+        let ret = {
+            let mut stack = self.stack.blocking_lock();
+            let ret = stack.pop();
+            Self::uncover(&mut stack);
+            ret
+        };
+        Self::pop_screen(&self.screens, self.session_id);
         self.dirty = true;
         self.relayout_sender
             .blocking_send(())
@@ -78,11 +110,43 @@ impl Stack {
         }
     }
 
+    /// The session this stack belongs to, which doubles as its node number.
+    pub fn session_id(&self) -> u64 {
+        self.session_id
+    }
+
+    /// Handle for queueing banners over the stack from outside the cursive thread.
+    pub fn toasts(&self) -> Toasts {
+        self.toasts.clone()
+    }
+
+    /// Gives focus to the screen a pop left on top, so it can catch up with anything that changed
+    /// while it was covered.
+    fn uncover(stack: &mut [Box<dyn View>]) {
+        if let Some(view) = stack.last_mut() {
+            // There's no Cursive to run a callback on here, so screens have to catch up by
+            // themselves.
+            let _ = view.take_focus(Direction::none());
+        }
+    }
+
+    fn pop_screen(screens: &Mutex<Vec<&'static str>>, session_id: u64) {
+        let mut screens = screens.blocking_lock();
+        screens.pop();
+        if let Some(screen) = screens.last() {
+            set_session_screen(session_id, screen);
+        }
+    }
+
     fn setup_esc(&mut self, siv: &mut Cursive) {
         let stack_clone = self.stack.clone();
+        let screens = self.screens.clone();
+        let session_id = self.session_id;
         siv.set_on_post_event(Event::Key(Key::Esc), move |siv| {
             let mut stack = stack_clone.blocking_lock();
             stack.pop();
+            Self::uncover(&mut stack);
+            Self::pop_screen(&screens, session_id);
             if stack.len() == 0 {
                 siv.quit();
             }
@@ -176,5 +240,6 @@ impl View for Stack {
             .last()
             .map(|view| view.draw(printer))
             .unwrap_or_default();
+        self.toasts.draw(printer);
     }
 }
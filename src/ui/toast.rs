@@ -0,0 +1,56 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ssh_ui::cursive::{theme::ColorStyle, Printer};
+
+static TOAST_DURATION: Duration = Duration::from_secs(6);
+
+struct Toast {
+    text: String,
+    shown: Option<Instant>,
+}
+
+/// Queue of transient banners drawn by the `Stack` on top of whatever view is showing.
+#[derive(Clone, Default)]
+pub struct Toasts {
+    queue: Arc<Mutex<VecDeque<Toast>>>,
+}
+
+impl Toasts {
+    pub fn push(&self, text: String) {
+        self.queue
+            .lock()
+            .unwrap()
+            .push_back(Toast { text, shown: None });
+    }
+
+    /// Drops the current toast once it has been shown long enough. Returns whether the banner
+    /// changed and the screen needs redrawing.
+    pub fn expire(&self) -> bool {
+        let mut queue = self.queue.lock().unwrap();
+        let expired = queue
+            .front()
+            .and_then(|toast| toast.shown)
+            .map(|shown| shown.elapsed() >= TOAST_DURATION)
+            .unwrap_or(false);
+        if expired {
+            queue.pop_front();
+        }
+        expired
+    }
+
+    pub fn draw(&self, printer: &Printer) {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(toast) = queue.front_mut() {
+            // The display time starts when the toast is first drawn, not when it was queued.
+            toast.shown.get_or_insert_with(Instant::now);
+            let text = format!(" {} ", toast.text);
+            let width = printer.size.x;
+            printer.with_color(ColorStyle::highlight(), |printer| {
+                printer.print_hline((0, 0), width, " ");
+                printer.print((0, 0), &text);
+            });
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use std::fmt::Display;
 use std::sync::Arc;
 
 use crate::db::gen::prelude::PublicKey;
@@ -11,9 +12,61 @@ use ssh_ui::russh_keys::key::PublicKey as RusshPublicKey;
 use thiserror::Error;
 use tokio::sync::Mutex;
 
+use crate::audit::{AuditKind, AuditUtil};
+use crate::db::now;
+use crate::settings::get_settings;
+
 #[derive(Debug, Clone)]
 pub struct UserId(i32);
 
+/// Handles are limited to ASCII letters, digits, `_` and `-` so an `@handle` mention can always
+/// tell where one ends.
+pub fn is_valid_handle(handle: &str) -> bool {
+    !handle.is_empty()
+        && handle
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Access level of a user, stored as an integer in `user.role`. Variants are ordered by privilege.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    Member,
+    Moderator,
+    Sysop,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Member, Role::Moderator, Role::Sysop];
+
+    pub fn from_i32(value: i32) -> Role {
+        match value {
+            i32::MIN..=0 => Role::Member,
+            1 => Role::Moderator,
+            _ => Role::Sysop,
+        }
+    }
+
+    pub fn as_i32(self) -> i32 {
+        match self {
+            Role::Member => 0,
+            Role::Moderator => 1,
+            Role::Sysop => 2,
+        }
+    }
+}
+
+impl Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Role::Member => "member",
+            Role::Moderator => "moderator",
+            Role::Sysop => "sysop",
+        };
+        f.write_str(name)
+    }
+}
+
 pub struct UserUtil {
     db: Arc<Mutex<DatabaseConnection>>,
     key: Option<RusshPublicKey>,
@@ -22,21 +75,33 @@ pub struct UserUtil {
 
 #[derive(Debug, Clone)]
 pub struct UserInfo {
-    _id: Option<UserId>,
+    id: Option<UserId>,
     pub handle: String,
     pub contact: String,
+    pub role: Role,
 }
 
 impl Default for UserInfo {
     fn default() -> Self {
         Self {
-            _id: None,
+            id: None,
             handle: Default::default(),
             contact: Default::default(),
+            role: Role::Member,
         }
     }
 }
 
+impl UserInfo {
+    pub fn id(&self) -> Option<i32> {
+        self.id.as_ref().map(|id| id.0)
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role >= role
+    }
+}
+
 #[derive(Debug, Error)]
 enum UserUtilError {
     #[error("Key not present")]
@@ -45,6 +110,8 @@ enum UserUtilError {
     NotRegistered,
     #[error("Key is present but user is not")]
     DatabaseConsistencyError,
+    #[error("Handles may only contain letters, digits, _ and -")]
+    InvalidHandle,
 }
 
 impl UserUtil {
@@ -62,6 +129,10 @@ impl UserUtil {
         } else {
             return Err(UserUtilError::KeyNotPresent.into());
         };
+        let handle = handle.trim();
+        if !is_valid_handle(handle) {
+            return Err(UserUtilError::InvalidHandle.into());
+        }
         let mut db = self.db.lock().await.to_owned(); // TODO: Remove unwrap with anyhow
 
         if let Result::Ok(_) = self.get_user().await {
@@ -73,10 +144,30 @@ impl UserUtil {
 
             if let Some(key) = key {
                 if let Some(user) = key.find_related(user::Entity).one(&db).await? {
+                    let mut changes = Vec::new();
+                    if user.handle != handle {
+                        changes.push(format!("handle from {} to {}", user.handle, handle));
+                    }
+                    if user.contact.as_deref() != Some(contact) {
+                        changes.push("contact info".to_string());
+                    }
+                    let user_id = user.id;
                     let mut active = user.into_active_model();
                     active.handle = Set(handle.to_string());
                     active.contact = Set(Some(contact.to_string()));
                     active.update(&mut db).await?;
+                    if !changes.is_empty() {
+                        let detail = format!("Changed {}", changes.join(" and "));
+                        AuditUtil::log_committed(
+                            &db,
+                            AuditKind::Profile,
+                            Some(user_id),
+                            Some(user_id),
+                            Some(key.fingerprint),
+                            &detail,
+                        )
+                        .await;
+                    }
                 } else {
                     return Err(UserUtilError::DatabaseConsistencyError.into());
                 }
@@ -88,6 +179,7 @@ impl UserUtil {
             let user_model = user::ActiveModel {
                 handle: Set(handle.to_string()),
                 contact: Set(Some(contact.to_string())),
+                joined: Set(Some(now())),
                 ..Default::default()
             };
             let user_model = user_model.insert(&mut db).await?;
@@ -98,6 +190,44 @@ impl UserUtil {
                 ..Default::default()
             };
             key_model.insert(&mut db).await?;
+            let fingerprint = key.fingerprint();
+            AuditUtil::log_committed(
+                &db,
+                AuditKind::Registration,
+                Some(user_model.id),
+                Some(user_model.id),
+                Some(fingerprint.clone()),
+                &format!("Registered as {}", handle),
+            )
+            .await;
+            AuditUtil::log_committed(
+                &db,
+                AuditKind::Key,
+                Some(user_model.id),
+                Some(user_model.id),
+                Some(fingerprint),
+                "Added key",
+            )
+            .await;
+        }
+        Ok(())
+    }
+
+    /// Stamps a new login for `user`, keeping the previous one so "new since last visit" has
+    /// something to compare against.
+    pub async fn record_login(&self, user: &UserInfo) -> Result<(), anyhow::Error> {
+        let user_id = match user.id() {
+            Some(user_id) => user_id,
+            None => return Err(UserUtilError::NotRegistered.into()),
+        };
+        let db = self.db.lock().await.to_owned();
+        if let Some(user) = user::Entity::find_by_id(user_id).one(&db).await? {
+            let last_login = user.last_login.clone();
+            let mut active = user.into_active_model();
+            active.previous_login = Set(last_login);
+            active.last_login = Set(Some(now()));
+            active.last_seen = Set(Some(now()));
+            active.update(&db).await?;
         }
         Ok(())
     }
@@ -117,10 +247,17 @@ impl UserUtil {
 
         if let Some(key) = key {
             if let Some(user) = key.find_related(user::Entity).one(&db).await? {
+                // Keys listed in the config are always sysops so a fresh install can be administered.
+                let role = if get_settings().sysop_fingerprints.contains(&key.fingerprint) {
+                    Role::Sysop
+                } else {
+                    Role::from_i32(user.role)
+                };
                 Ok(UserInfo {
-                    _id: Some(UserId(user.id)),
+                    id: Some(UserId(user.id)),
                     handle: user.handle,
                     contact: user.contact.unwrap_or("".into()),
+                    role,
                 })
             } else {
                 Err(UserUtilError::DatabaseConsistencyError.into())